@@ -0,0 +1,913 @@
+// --- Mod Activation Backends ---
+// How a mod folder becomes visible (or invisible) to 3DMigoto.
+//  - "rename": mods live in the Mods folder and are disabled by marking them (see DisabledMarker).
+//  - "link":   mods live in a managed library folder and are materialized into the Mods folder
+//              as a symlink/junction, falling back to a hardlinked (or copied) tree.
+// The mode is a per-game setting (each game has its own database).
+// Extra mod roots (see ModRoot) are handled by role-specific backends next to the primary one.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::{AppError, DISABLED_PREFIX};
+
+pub const ACTIVATION_MODE_RENAME: &str = "rename";
+pub const ACTIVATION_MODE_LINK: &str = "link";
+pub const ROOT_ROLE_ACTIVE: &str = "active";     // Loaded by 3DMigoto, mods toggled in place with the disabled marker
+pub const ROOT_ROLE_LIBRARY: &str = "library";   // Archive, never loaded; mods are enabled by moving them to an active root
+pub const ROOT_ROLE_READONLY: &str = "readonly"; // Loaded, never modified by GMM (e.g. shared loader-level fixes)
+// Written into hardlinked/copied folders by older versions. Such trees are still recognised when removing them.
+// Folders whose name starts with this (any case) are skipped by stock 3DMigoto
+const LOADER_SKIP_PREFIX: &str = "DISABLED";
+const LEGACY_MATERIALIZED_MARKER: &str = ".gmm_materialized";
+
+lazy_static! {
+    // The prefix older versions wrote (and renamed "DISABLED..." folders to); the scan converts it to the configured marker.
+    // Other spellings aren't touched: a name like "Coat_disabled" may just as well be an active mod.
+    static ref LEGACY_DISABLED_PREFIX_REGEX: Regex = Regex::new(r"^DISABLED_?").unwrap();
+}
+
+// How the rename mode marks a disabled mod folder.
+// Stock 3DMigoto only skips folders whose name starts with "DISABLED" (any case), so prefixes and
+// folders must start with it, and suffixes rely on the loader honouring them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", content = "value", rename_all = "lowercase")]
+pub enum DisabledMarker {
+    Prefix(String), // "DISABLED_Mod"
+    Suffix(String), // "Mod.disabled"
+    Folder(String), // "<Mods>/Disabled/<category>/<entity>/Mod"
+}
+
+impl Default for DisabledMarker {
+    fn default() -> Self {
+        DisabledMarker::Prefix(DISABLED_PREFIX.to_string())
+    }
+}
+
+impl DisabledMarker {
+    pub fn validate(&self) -> Result<(), String> {
+        let (DisabledMarker::Prefix(value) | DisabledMarker::Suffix(value) | DisabledMarker::Folder(value)) = self;
+        if value.trim().is_empty() {
+            return Err("The disabled marker cannot be empty.".to_string());
+        }
+        if value.contains(['/', '\\', ':', '*', '?', '"', '<', '>', '|']) {
+            return Err(format!("The disabled marker '{}' contains characters not allowed in folder names.", value));
+        }
+        let skipped_by_loader = value.len() >= LOADER_SKIP_PREFIX.len()
+            && value.is_char_boundary(LOADER_SKIP_PREFIX.len())
+            && value[..LOADER_SKIP_PREFIX.len()].eq_ignore_ascii_case(LOADER_SKIP_PREFIX);
+        match self {
+            DisabledMarker::Prefix(_) | DisabledMarker::Folder(_) if !skipped_by_loader => Err(format!(
+                "3DMigoto still loads folders marked with '{}'. The marker has to start with \"{}\".", value, LOADER_SKIP_PREFIX)),
+            _ => Ok(()),
+        }
+    }
+
+    // Why mods disabled with this marker may still be loaded, if they may
+    pub fn loader_warning(&self) -> Option<String> {
+        match self {
+            DisabledMarker::Suffix(suffix) => Some(format!(
+                "Stock 3DMigoto still loads folders ending in '{}'. Only use a suffix if your loader skips them.", suffix)),
+            _ => None,
+        }
+    }
+
+    // Relative path of the disabled folder for a clean relative path
+    pub fn disabled_relative_path(&self, clean_relative_path: &Path) -> Option<PathBuf> {
+        let filename_str = clean_relative_path.file_name()?.to_string_lossy().to_string();
+        if filename_str.is_empty() { return None; }
+        let with_name = |name: String| match clean_relative_path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.join(name),
+            _ => PathBuf::from(name),
+        };
+        Some(match self {
+            DisabledMarker::Prefix(prefix) => with_name(format!("{}{}", prefix, filename_str)),
+            DisabledMarker::Suffix(suffix) => with_name(format!("{}{}", filename_str, suffix)),
+            DisabledMarker::Folder(folder) => Path::new(folder).join(clean_relative_path),
+        })
+    }
+
+    // Clean relative path of a folder found on disk, and whether it carries the marker
+    pub fn strip(&self, relative_path: &Path) -> (PathBuf, bool) {
+        let filename_str = relative_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let with_name = |name: &str| match relative_path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.join(name),
+            _ => PathBuf::from(name),
+        };
+        match self {
+            DisabledMarker::Prefix(prefix) if filename_str.len() > prefix.len()
+                && filename_str.is_char_boundary(prefix.len())
+                && filename_str[..prefix.len()].eq_ignore_ascii_case(prefix) =>
+                (with_name(&filename_str[prefix.len()..]), true),
+            DisabledMarker::Suffix(suffix) if filename_str.len() > suffix.len()
+                && filename_str.is_char_boundary(filename_str.len() - suffix.len())
+                && filename_str[filename_str.len() - suffix.len()..].eq_ignore_ascii_case(suffix) =>
+                (with_name(&filename_str[..filename_str.len() - suffix.len()]), true),
+            DisabledMarker::Folder(folder) => match relative_path.strip_prefix(folder) {
+                Ok(inner) if !inner.as_os_str().is_empty() => (inner.to_path_buf(), true),
+                _ => (relative_path.to_path_buf(), false),
+            },
+            _ => (relative_path.to_path_buf(), false),
+        }
+    }
+
+    // New relative path for a folder still named with the old DISABLED_ prefix, if it needs converting
+    pub fn normalized_relative_path(&self, relative_path: &Path) -> Option<PathBuf> {
+        if self.strip(relative_path).1 {
+            return None; // Already uses the configured marker
+        }
+        let filename_str = relative_path.file_name()?.to_string_lossy().to_string();
+        if !LEGACY_DISABLED_PREFIX_REGEX.is_match(&filename_str) {
+            return None;
+        }
+        let clean_name = LEGACY_DISABLED_PREFIX_REGEX.replace(&filename_str, "").to_string();
+        if clean_name.trim().is_empty() {
+            return None;
+        }
+        let clean_relative_path = match relative_path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.join(clean_name),
+            _ => PathBuf::from(clean_name),
+        };
+        self.disabled_relative_path(&clean_relative_path)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ActivationSettings {
+    pub mode: String,
+    pub library_path: Option<PathBuf>, // Only used by the link mode
+    pub disabled_marker: DisabledMarker, // Only used by the rename mode
+}
+
+impl Default for ActivationSettings {
+    fn default() -> Self {
+        ActivationSettings { mode: ACTIVATION_MODE_RENAME.to_string(), library_path: None, disabled_marker: DisabledMarker::default() }
+    }
+}
+
+// An extra folder holding mods, registered next to the primary Mods folder
+#[derive(Serialize, Debug, Clone)]
+pub struct ModRoot {
+    pub id: i64,
+    pub path: PathBuf,
+    pub role: String,
+    pub label: Option<String>,
+}
+
+// Settings of the active game's database, loaded in initialize_database
+static ACTIVE_SETTINGS: Lazy<Mutex<ActivationSettings>> = Lazy::new(|| Mutex::new(ActivationSettings::default()));
+static MOD_ROOTS: Lazy<Mutex<Vec<ModRoot>>> = Lazy::new(|| Mutex::new(Vec::new()));
+// Hardlinked/copied trees created by the link mode (Mods folder path -> library source), mirrored
+// from the materialized_folders table so only folders created by us are ever deleted.
+// Changes are queued until main.rs writes them back with take_materialized_changes.
+static MATERIALIZED_FOLDERS: Lazy<Mutex<MaterializedFolders>> = Lazy::new(|| Mutex::new(MaterializedFolders::default()));
+
+#[derive(Default)]
+struct MaterializedFolders {
+    folders: HashMap<PathBuf, PathBuf>,
+    pending: Vec<MaterializedChange>,
+}
+
+// A tree created (`source` set) or removed (`source` None) since the last write-back
+#[derive(Debug, Clone)]
+pub struct MaterializedChange {
+    pub target: PathBuf,
+    pub source: Option<PathBuf>,
+}
+
+pub fn set_active_settings(settings: ActivationSettings) {
+    println!("[activation] Using '{}' activation (library: {:?})", settings.mode, settings.library_path);
+    if let Ok(mut guard) = ACTIVE_SETTINGS.lock() {
+        *guard = settings;
+    }
+}
+
+pub fn active_settings() -> ActivationSettings {
+    ACTIVE_SETTINGS.lock().map(|s| s.clone()).unwrap_or_default()
+}
+
+pub fn set_mod_roots(roots: Vec<ModRoot>) {
+    println!("[activation] {} extra mod roots registered", roots.len());
+    if let Ok(mut guard) = MOD_ROOTS.lock() {
+        *guard = roots;
+    }
+}
+
+pub fn mod_roots() -> Vec<ModRoot> {
+    MOD_ROOTS.lock().map(|r| r.clone()).unwrap_or_default()
+}
+
+pub fn set_materialized_folders(folders: Vec<(PathBuf, PathBuf)>) {
+    if let Ok(mut guard) = MATERIALIZED_FOLDERS.lock() {
+        *guard = MaterializedFolders { folders: folders.into_iter().collect(), pending: Vec::new() };
+    }
+}
+
+// Drains the trees created or removed since the last call, in order
+pub fn take_materialized_changes() -> Vec<MaterializedChange> {
+    MATERIALIZED_FOLDERS.lock().map(|mut guard| std::mem::take(&mut guard.pending)).unwrap_or_default()
+}
+
+fn record_materialized(target: &Path, source: Option<&Path>) {
+    if let Ok(mut guard) = MATERIALIZED_FOLDERS.lock() {
+        match source {
+            Some(source) => guard.folders.insert(target.to_path_buf(), source.to_path_buf()),
+            None => guard.folders.remove(target),
+        };
+        guard.pending.push(MaterializedChange { target: target.to_path_buf(), source: source.map(Path::to_path_buf) });
+    }
+}
+
+// True if `target` is a folder link (symlink or junction) or a tree materialized by GMM
+fn is_materialized_target(target: &Path) -> bool {
+    match fs::symlink_metadata(target) {
+        Ok(metadata) => metadata.file_type().is_symlink() || is_materialized(target),
+        Err(_) => false,
+    }
+}
+
+fn is_materialized(target: &Path) -> bool {
+    MATERIALIZED_FOLDERS.lock().map(|guard| guard.folders.contains_key(target)).unwrap_or(false)
+        || target.join(LEGACY_MATERIALIZED_MARKER).is_file()
+}
+
+pub trait ActivationBackend {
+    fn mode(&self) -> &'static str;
+    // Directory holding the mod sources (walked by the scanner, imports extract here)
+    fn scan_root(&self) -> &Path;
+    // Source folder of a mod and whether it is currently active
+    fn locate(&self, clean_relative_path: &str) -> Option<(PathBuf, bool)>;
+    // Where the source folder of an inactive mod lives
+    fn inactive_path(&self, clean_relative_path: &str) -> Option<PathBuf>;
+    fn set_enabled(&self, clean_relative_path: &str, enable: bool) -> Result<(), AppError>;
+    // Brings the loaded copy of an active mod up to date after its source files were edited.
+    // Only needed where the loaded folder is a copy of the source.
+    fn refresh(&self, _clean_relative_path: &str) -> Result<(), AppError> {
+        Ok(())
+    }
+}
+
+// Backend for the currently configured mode. Falls back to renaming if no library is set.
+pub fn backend_for(base_mods_path: &Path) -> Box<dyn ActivationBackend> {
+    backend_with(base_mods_path, &active_settings())
+}
+
+pub fn backend_with(base_mods_path: &Path, settings: &ActivationSettings) -> Box<dyn ActivationBackend> {
+    match (settings.mode.as_str(), &settings.library_path) {
+        (ACTIVATION_MODE_LINK, Some(library_path)) => Box::new(LinkActivation {
+            mods_path: base_mods_path.to_path_buf(),
+            library_path: library_path.clone(),
+        }),
+        _ => Box::new(RenameActivation {
+            mods_path: base_mods_path.to_path_buf(),
+            marker: settings.disabled_marker.clone(),
+        }),
+    }
+}
+
+pub fn backend_for_root(root: &ModRoot, settings: &ActivationSettings) -> Box<dyn ActivationBackend> {
+    match root.role.as_str() {
+        ROOT_ROLE_LIBRARY => Box::new(ArchiveActivation { root_path: root.path.clone() }),
+        ROOT_ROLE_READONLY => Box::new(ReadOnlyActivation { root_path: root.path.clone() }),
+        _ => Box::new(RenameActivation { mods_path: root.path.clone(), marker: settings.disabled_marker.clone() }),
+    }
+}
+
+// Backend responsible for a mod stored under `root_id` (None = the primary Mods folder).
+// None if the root no longer exists.
+pub fn backend_for_asset(base_mods_path: &Path, root_id: Option<i64>) -> Option<Box<dyn ActivationBackend>> {
+    let settings = active_settings();
+    match root_id {
+        None => Some(backend_with(base_mods_path, &settings)),
+        Some(id) => mod_roots().iter().find(|root| root.id == id).map(|root| backend_for_root(root, &settings)),
+    }
+}
+
+// Role of the mod root stored for an asset. The primary Mods folder (None) is always loaded,
+// so it counts as active like an unknown (removed) root.
+pub fn root_role(root_id: Option<i64>) -> String {
+    root_id.and_then(|id| mod_roots().into_iter().find(|root| root.id == id))
+        .map(|root| root.role)
+        .unwrap_or_else(|| ROOT_ROLE_ACTIVE.to_string())
+}
+
+// Searches the primary Mods folder, then the extra roots, for a mod whose root is unknown
+// (e.g. re-linking a missing asset). Returns the root id (None for the primary folder) alongside.
+pub fn find_asset_root(base_mods_path: &Path, clean_relative_path: &str) -> Option<(Box<dyn ActivationBackend>, Option<i64>)> {
+    let settings = active_settings();
+    let primary = backend_with(base_mods_path, &settings);
+    if primary.locate(clean_relative_path).is_some() {
+        return Some((primary, None));
+    }
+    mod_roots().iter()
+        .map(|root| (backend_for_root(root, &settings), Some(root.id)))
+        .find(|(backend, _)| backend.locate(clean_relative_path).is_some())
+}
+
+// Whether a rename failed only because source and target are on different drives.
+// Any other failure (locked file, missing permission) must not turn into a copy.
+pub fn is_cross_device(error: &io::Error) -> bool {
+    #[cfg(windows)]
+    const CROSS_DEVICE: i32 = 17; // ERROR_NOT_SAME_DEVICE
+    #[cfg(not(windows))]
+    const CROSS_DEVICE: i32 = 18; // EXDEV
+    error.raw_os_error() == Some(CROSS_DEVICE)
+}
+
+// Moves a folder, falling back to copy + delete when the target is on another drive
+pub fn move_folder(source: &Path, target: &Path) -> Result<(), AppError> {
+    if target.exists() {
+        return Err(AppError::ModOperation(format!("'{}' already exists.", target.display())));
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match fs::rename(source, target) {
+        Ok(()) => Ok(()),
+        Err(e) if !is_cross_device(&e) => Err(e.into()),
+        Err(_) => move_folder_by_copy(source, target),
+    }
+}
+
+// Cross-device half of move_folder: copy, then remove the source
+fn move_folder_by_copy(source: &Path, target: &Path) -> Result<(), AppError> {
+    println!("[move_folder] Different drive, copying '{}' -> '{}'", source.display(), target.display());
+    if let Err(e) = copy_folder(source, target, &mut |_| {}) {
+        fs::remove_dir_all(target).ok();
+        return Err(e.into());
+    }
+    // Renaming the source aside first fails as a whole (e.g. a file in use), so the copy can be
+    // dropped with the source untouched. Once renamed, the move is done; leftovers are only logged.
+    let file_name = source.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+    let moved_aside = source.with_file_name(format!(".{}.gmm-moved-{}", file_name, nanos));
+    if let Err(e) = fs::rename(source, &moved_aside) {
+        fs::remove_dir_all(target).ok();
+        return Err(AppError::ModOperation(format!("Copied to '{}' but could not remove '{}': {}. The copy was discarded.", target.display(), source.display(), e)));
+    }
+    if let Err(e) = fs::remove_dir_all(&moved_aside) {
+        eprintln!("[move_folder] Warning: Failed to delete the moved source '{}': {}", moved_aside.display(), e);
+    }
+    Ok(())
+}
+
+// Copies a folder tree, calling `on_file` with each copied file's relative path.
+// Folder links (link-mode activations) are recreated pointing at the same library folder.
+pub fn copy_folder(source: &Path, target: &Path, on_file: &mut dyn FnMut(&Path)) -> io::Result<u64> {
+    let mut copied_bytes = 0;
+    fs::create_dir_all(target)?;
+    for entry in WalkDir::new(source).min_depth(1).into_iter() {
+        let entry = entry.map_err(io::Error::from)?;
+        let relative = entry.path().strip_prefix(source).map_err(io::Error::other)?;
+        let destination = target.join(relative);
+        if entry.path_is_symlink() && entry.path().is_dir() {
+            create_dir_link(&fs::read_link(entry.path())?, &destination)?;
+        } else if entry.file_type().is_dir() {
+            fs::create_dir_all(&destination)?;
+        } else {
+            copied_bytes += fs::copy(entry.path(), &destination)?;
+            on_file(relative);
+        }
+    }
+    Ok(copied_bytes)
+}
+
+// First difference between a copied tree and its source, None if they match. Checks that both hold
+// the same files and folder links with the same sizes, and with `compare_contents` the same bytes.
+pub fn compare_folders(source: &Path, target: &Path, compare_contents: bool) -> io::Result<Option<String>> {
+    let mut target_entries = 0;
+    for entry in WalkDir::new(target).min_depth(1).into_iter() {
+        let entry = entry.map_err(io::Error::from)?;
+        if !entry.file_type().is_dir() || entry.path_is_symlink() {
+            target_entries += 1;
+        }
+    }
+    let mut source_entries = 0;
+    for entry in WalkDir::new(source).min_depth(1).into_iter() {
+        let entry = entry.map_err(io::Error::from)?;
+        let relative = entry.path().strip_prefix(source).map_err(io::Error::other)?;
+        let copy = target.join(relative);
+        if entry.path_is_symlink() && entry.path().is_dir() {
+            source_entries += 1;
+            if fs::read_link(&copy).ok() != Some(fs::read_link(entry.path())?) {
+                return Ok(Some(format!("folder link '{}' differs", relative.display())));
+            }
+        } else if entry.file_type().is_dir() {
+            if !copy.is_dir() {
+                return Ok(Some(format!("folder '{}' is missing", relative.display())));
+            }
+        } else {
+            source_entries += 1;
+            let (Ok(original), Ok(copied)) = (fs::metadata(entry.path()), fs::metadata(&copy)) else {
+                return Ok(Some(format!("'{}' is missing", relative.display())));
+            };
+            if original.len() != copied.len() {
+                return Ok(Some(format!("'{}' has {} bytes instead of {}", relative.display(), copied.len(), original.len())));
+            }
+            if compare_contents && !files_equal(entry.path(), &copy)? {
+                return Ok(Some(format!("'{}' has different contents", relative.display())));
+            }
+        }
+    }
+    if source_entries != target_entries {
+        return Ok(Some(format!("{} entries copied, expected {}", target_entries, source_entries)));
+    }
+    Ok(None)
+}
+
+fn files_equal(a: &Path, b: &Path) -> io::Result<bool> {
+    use std::io::Read;
+    let (mut a, mut b) = (io::BufReader::new(fs::File::open(a)?), io::BufReader::new(fs::File::open(b)?));
+    let (mut buffer_a, mut buffer_b) = (vec![0u8; 64 * 1024], vec![0u8; 64 * 1024]);
+    loop {
+        let read = a.read(&mut buffer_a)?;
+        if read == 0 {
+            return Ok(b.read(&mut buffer_b[..1])? == 0);
+        }
+        match b.read_exact(&mut buffer_b[..read]) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+            result => result?,
+        }
+        if buffer_a[..read] != buffer_b[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+// File count and total size of a folder tree, without following folder links
+pub fn folder_stats(path: &Path) -> io::Result<(usize, u64)> {
+    let mut files = 0;
+    let mut bytes = 0;
+    for entry in WalkDir::new(path).min_depth(1).into_iter() {
+        let entry = entry.map_err(io::Error::from)?;
+        if entry.path_is_symlink() && entry.path().is_dir() {
+            continue;
+        }
+        if entry.file_type().is_file() || entry.file_type().is_symlink() {
+            files += 1;
+            bytes += fs::metadata(entry.path())?.len();
+        }
+    }
+    Ok((files, bytes))
+}
+
+// --- Rename (in-place) ---
+
+pub struct RenameActivation {
+    mods_path: PathBuf,
+    marker: DisabledMarker,
+}
+
+impl ActivationBackend for RenameActivation {
+    fn mode(&self) -> &'static str { ACTIVATION_MODE_RENAME }
+
+    fn scan_root(&self) -> &Path { &self.mods_path }
+
+    fn locate(&self, clean_relative_path: &str) -> Option<(PathBuf, bool)> {
+        let full_path_if_enabled = self.mods_path.join(clean_relative_path);
+        if full_path_if_enabled.is_dir() {
+            return Some((full_path_if_enabled, true));
+        }
+        let full_path_if_disabled = self.inactive_path(clean_relative_path)?;
+        if full_path_if_disabled.is_dir() {
+            return Some((full_path_if_disabled, false));
+        }
+        None
+    }
+
+    fn inactive_path(&self, clean_relative_path: &str) -> Option<PathBuf> {
+        self.marker.disabled_relative_path(Path::new(clean_relative_path))
+            .map(|relative| self.mods_path.join(relative))
+    }
+
+    fn set_enabled(&self, clean_relative_path: &str, enable: bool) -> Result<(), AppError> {
+        let (current_path, is_enabled) = self.locate(clean_relative_path)
+            .ok_or_else(|| AppError::NotFound(format!("Mod folder '{}' not found on disk.", clean_relative_path)))?;
+        if is_enabled == enable {
+            return Ok(());
+        }
+        let target_path = if enable {
+            self.mods_path.join(clean_relative_path)
+        } else {
+            self.inactive_path(clean_relative_path)
+                .ok_or_else(|| AppError::ModOperation(format!("Invalid folder name '{}'", clean_relative_path)))?
+        };
+        if target_path.exists() {
+            return Err(AppError::ModOperation(format!("Cannot rename '{}': '{}' already exists.", current_path.display(), target_path.display())));
+        }
+        println!("[RenameActivation] Renaming '{}' -> '{}'", current_path.display(), target_path.display());
+        if let Some(parent) = target_path.parent() {
+            fs::create_dir_all(parent)?; // The folder marker moves mods into a separate tree
+        }
+        fs::rename(&current_path, &target_path)?;
+        Ok(())
+    }
+}
+
+// --- Link (managed library) ---
+
+pub struct LinkActivation {
+    mods_path: PathBuf,
+    library_path: PathBuf,
+}
+
+impl ActivationBackend for LinkActivation {
+    fn mode(&self) -> &'static str { ACTIVATION_MODE_LINK }
+
+    fn scan_root(&self) -> &Path { &self.library_path }
+
+    fn locate(&self, clean_relative_path: &str) -> Option<(PathBuf, bool)> {
+        let source_path = self.library_path.join(clean_relative_path);
+        if !source_path.is_dir() {
+            return None;
+        }
+        Some((source_path, is_materialized_target(&self.mods_path.join(clean_relative_path))))
+    }
+
+    fn inactive_path(&self, clean_relative_path: &str) -> Option<PathBuf> {
+        Some(self.library_path.join(clean_relative_path))
+    }
+
+    fn set_enabled(&self, clean_relative_path: &str, enable: bool) -> Result<(), AppError> {
+        let (source_path, is_active) = self.locate(clean_relative_path)
+            .ok_or_else(|| AppError::NotFound(format!("Mod folder '{}' not found in the library.", clean_relative_path)))?;
+        if is_active == enable {
+            return Ok(());
+        }
+        let target_path = self.mods_path.join(clean_relative_path);
+        if enable {
+            if fs::symlink_metadata(&target_path).is_ok() {
+                return Err(AppError::ModOperation(format!("Cannot activate '{}': a folder GMM didn't create already exists at '{}'.", clean_relative_path, target_path.display())));
+            }
+            if let Some(parent) = target_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let method = materialize_folder(&source_path, &target_path)?;
+            println!("[LinkActivation] Activated '{}' ({})", clean_relative_path, method);
+        } else {
+            remove_materialized_folder(&target_path)?;
+            println!("[LinkActivation] Deactivated '{}'", clean_relative_path);
+        }
+        Ok(())
+    }
+
+    fn refresh(&self, clean_relative_path: &str) -> Result<(), AppError> {
+        let target_path = self.mods_path.join(clean_relative_path);
+        match fs::symlink_metadata(&target_path) {
+            Ok(metadata) if !metadata.file_type().is_symlink() => {}
+            _ => return Ok(()), // Inactive, or a link that already shows the edited source
+        }
+        let source_path = self.library_path.join(clean_relative_path);
+        remove_materialized_folder(&target_path)?;
+        let method = materialize_folder(&source_path, &target_path)?;
+        println!("[LinkActivation] Refreshed '{}' ({})", clean_relative_path, method);
+        Ok(())
+    }
+}
+
+// --- Archive root (role "library") ---
+
+pub struct ArchiveActivation {
+    root_path: PathBuf,
+}
+
+impl ActivationBackend for ArchiveActivation {
+    fn mode(&self) -> &'static str { ROOT_ROLE_LIBRARY }
+
+    fn scan_root(&self) -> &Path { &self.root_path }
+
+    fn locate(&self, clean_relative_path: &str) -> Option<(PathBuf, bool)> {
+        let path = self.root_path.join(clean_relative_path);
+        path.is_dir().then_some((path, false))
+    }
+
+    fn inactive_path(&self, clean_relative_path: &str) -> Option<PathBuf> {
+        Some(self.root_path.join(clean_relative_path))
+    }
+
+    fn set_enabled(&self, clean_relative_path: &str, enable: bool) -> Result<(), AppError> {
+        if enable {
+            return Err(AppError::ModOperation(format!(
+                "'{}' is archived in '{}'. Move it to an active mods folder to enable it.", clean_relative_path, self.root_path.display())));
+        }
+        Ok(())
+    }
+}
+
+// --- Read-only root ---
+
+pub struct ReadOnlyActivation {
+    root_path: PathBuf,
+}
+
+impl ActivationBackend for ReadOnlyActivation {
+    fn mode(&self) -> &'static str { ROOT_ROLE_READONLY }
+
+    fn scan_root(&self) -> &Path { &self.root_path }
+
+    fn locate(&self, clean_relative_path: &str) -> Option<(PathBuf, bool)> {
+        let path = self.root_path.join(clean_relative_path);
+        path.is_dir().then_some((path, true))
+    }
+
+    fn inactive_path(&self, _clean_relative_path: &str) -> Option<PathBuf> {
+        None // Mods in a read-only root can't be disabled or moved in
+    }
+
+    fn set_enabled(&self, clean_relative_path: &str, enable: bool) -> Result<(), AppError> {
+        if !enable {
+            return Err(AppError::ModOperation(format!(
+                "'{}' lives in the read-only folder '{}' and can't be disabled from GMM.", clean_relative_path, self.root_path.display())));
+        }
+        Ok(())
+    }
+}
+
+// Moves a mod to a new clean relative path, keeping its enabled state. Returns the new source folder.
+pub fn relocate_asset(backend: &dyn ActivationBackend, old_clean_relative_path: &str, new_clean_relative_path: &str) -> Result<PathBuf, AppError> {
+    let (current_path, was_enabled) = backend.locate(old_clean_relative_path)
+        .ok_or_else(|| AppError::NotFound(format!("Mod folder '{}' not found.", old_clean_relative_path)))?;
+    if old_clean_relative_path == new_clean_relative_path {
+        return Ok(current_path);
+    }
+    let invalid_name = |path: &str| AppError::ModOperation(format!("Invalid folder name '{}'", path));
+    let target_path = backend.inactive_path(new_clean_relative_path).ok_or_else(|| invalid_name(new_clean_relative_path))?;
+    if target_path.exists() || backend.locate(new_clean_relative_path).is_some() {
+        return Err(AppError::ModOperation(format!("Target '{}' already exists.", new_clean_relative_path)));
+    }
+
+    // Deactivate, move the inactive source, then restore the previous state at the new location
+    backend.set_enabled(old_clean_relative_path, false)?;
+    let source_path = backend.inactive_path(old_clean_relative_path).ok_or_else(|| invalid_name(old_clean_relative_path))?;
+    if let Some(parent) = target_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if let Err(e) = fs::rename(&source_path, &target_path) {
+        if was_enabled {
+            backend.set_enabled(old_clean_relative_path, true).ok();
+        }
+        return Err(e.into());
+    }
+    if was_enabled {
+        backend.set_enabled(new_clean_relative_path, true)?;
+    }
+    backend.locate(new_clean_relative_path).map(|(path, _)| path)
+        .ok_or_else(|| AppError::NotFound(format!("Mod folder '{}' not found after moving.", new_clean_relative_path)))
+}
+
+// Makes `source` visible at `target`. Returns the method that worked.
+fn materialize_folder(source: &Path, target: &Path) -> Result<&'static str, AppError> {
+    match create_dir_link(source, target) {
+        Ok(()) => return Ok("link"),
+        Err(e) => println!("[materialize_folder] Directory link failed ({}), falling back to hardlinks.", e),
+    }
+
+    let mut copied_files = 0;
+    let result: io::Result<()> = (|| {
+        fs::create_dir_all(target)?;
+        for entry in WalkDir::new(source).min_depth(1).into_iter() {
+            let entry = entry.map_err(io::Error::from)?;
+            let relative = entry.path().strip_prefix(source).map_err(io::Error::other)?;
+            let destination = target.join(relative);
+            if entry.file_type().is_dir() {
+                fs::create_dir_all(&destination)?;
+            } else if fs::hard_link(entry.path(), &destination).is_err() {
+                fs::copy(entry.path(), &destination)?; // Different volume: copy instead
+                copied_files += 1;
+            }
+        }
+        Ok(())
+    })();
+    if let Err(e) = result {
+        fs::remove_dir_all(target).ok();
+        return Err(AppError::ModOperation(format!("Failed to materialize '{}' into '{}': {}", source.display(), target.display(), e)));
+    }
+    record_materialized(target, Some(source));
+    Ok(if copied_files > 0 { "copy" } else { "hardlink" })
+}
+
+fn remove_materialized_folder(target: &Path) -> Result<(), AppError> {
+    let metadata = fs::symlink_metadata(target)?;
+    if metadata.file_type().is_symlink() {
+        remove_dir_link(target)?;
+    } else if is_materialized(target) {
+        fs::remove_dir_all(target)?;
+        record_materialized(target, None);
+    } else {
+        return Err(AppError::ModOperation(format!("Refusing to remove '{}': it is a real folder, not one materialized by GMM.", target.display())));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn create_dir_link(source: &Path, target: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(source, target)
+}
+
+#[cfg(unix)]
+fn remove_dir_link(target: &Path) -> io::Result<()> {
+    fs::remove_file(target)
+}
+
+#[cfg(windows)]
+fn create_dir_link(source: &Path, target: &Path) -> io::Result<()> {
+    // Symlinks need Developer Mode or admin rights; junctions don't
+    if std::os::windows::fs::symlink_dir(source, target).is_ok() {
+        return Ok(());
+    }
+    create_junction(source, target)
+}
+
+// Creates `target` as an NTFS junction to `source` by writing the mount point reparse data directly.
+// No shell is involved, so folder names with `&`, `|` or `^` are safe.
+#[cfg(windows)]
+fn create_junction(source: &Path, target: &Path) -> io::Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::AsRawHandle;
+    use windows::Win32::Foundation::HANDLE;
+    use windows::Win32::Storage::FileSystem::{FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OPEN_REPARSE_POINT};
+    use windows::Win32::System::IO::DeviceIoControl;
+    use windows::Win32::System::Ioctl::FSCTL_SET_REPARSE_POINT;
+    use windows::Win32::System::SystemServices::IO_REPARSE_TAG_MOUNT_POINT;
+    const MAXIMUM_REPARSE_DATA_BUFFER_SIZE: usize = 16 * 1024;
+
+    // Junctions need an absolute target without the \\?\ prefix canonicalize adds
+    let absolute = fs::canonicalize(source)?;
+    let absolute = absolute.to_string_lossy();
+    let absolute = absolute.strip_prefix(r"\\?\").unwrap_or(&absolute);
+    if absolute.starts_with(r"UNC\") {
+        return Err(io::Error::other(format!("Junctions can't point to network folders ('{}').", source.display())));
+    }
+    let print_name: Vec<u16> = std::ffi::OsStr::new(absolute).encode_wide().collect();
+    let substitute_name: Vec<u16> = std::ffi::OsStr::new(&format!(r"\??\{}", absolute)).encode_wide().collect();
+
+    // REPARSE_DATA_BUFFER with a MountPointReparseBuffer: both names NUL-terminated, lengths in bytes without the NUL
+    if (substitute_name.len() + print_name.len() + 2) * 2 + 16 > MAXIMUM_REPARSE_DATA_BUFFER_SIZE {
+        return Err(io::Error::other(format!("Path too long for a junction: '{}'.", source.display())));
+    }
+    let substitute_bytes = (substitute_name.len() * 2) as u16;
+    let print_bytes = (print_name.len() * 2) as u16;
+    let path_buffer_bytes = substitute_bytes + 2 + print_bytes + 2;
+    let mut buffer: Vec<u8> = Vec::with_capacity(16 + path_buffer_bytes as usize);
+    buffer.extend_from_slice(&IO_REPARSE_TAG_MOUNT_POINT.to_le_bytes());
+    buffer.extend_from_slice(&(8 + path_buffer_bytes).to_le_bytes()); // ReparseDataLength
+    buffer.extend_from_slice(&0u16.to_le_bytes()); // Reserved
+    buffer.extend_from_slice(&0u16.to_le_bytes()); // SubstituteNameOffset
+    buffer.extend_from_slice(&substitute_bytes.to_le_bytes());
+    buffer.extend_from_slice(&(substitute_bytes + 2).to_le_bytes()); // PrintNameOffset
+    buffer.extend_from_slice(&print_bytes.to_le_bytes());
+    for unit in substitute_name.iter().chain(&[0]).chain(&print_name).chain(&[0]) {
+        buffer.extend_from_slice(&unit.to_le_bytes());
+    }
+
+    fs::create_dir(target)?;
+    let result = (|| {
+        let dir = fs::OpenOptions::new()
+            .write(true)
+            .custom_flags(FILE_FLAG_OPEN_REPARSE_POINT.0 | FILE_FLAG_BACKUP_SEMANTICS.0)
+            .open(target)?;
+        let mut bytes_returned = 0u32;
+        let result = unsafe {
+            DeviceIoControl(
+                HANDLE(dir.as_raw_handle()),
+                FSCTL_SET_REPARSE_POINT,
+                Some(buffer.as_ptr() as *const _),
+                buffer.len() as u32,
+                None,
+                0,
+                Some(&mut bytes_returned as *mut u32),
+                None,
+            )
+        };
+        result.map_err(|_| io::Error::last_os_error())
+    })();
+    if result.is_err() {
+        fs::remove_dir(target).ok();
+    }
+    result
+}
+
+#[cfg(windows)]
+fn remove_dir_link(target: &Path) -> io::Result<()> {
+    fs::remove_dir(target) // Removes the symlink/junction itself, not the library folder
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::ScratchDir;
+
+    #[test]
+    fn dir_link_handles_shell_metacharacters() {
+        let dir = ScratchDir::new("activation_link");
+        let source = dir.join("library").join("A & B ^ C");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("mod.ini"), "[KeySwap]\nkey = x\n").unwrap();
+        let target = dir.join("Mods").join("A & B ^ C");
+        fs::create_dir_all(target.parent().unwrap()).unwrap();
+
+        create_dir_link(&source, &target).unwrap();
+        assert_eq!(fs::read_to_string(target.join("mod.ini")).unwrap(), "[KeySwap]\nkey = x\n");
+        remove_dir_link(&target).unwrap();
+        assert!(fs::symlink_metadata(&target).is_err());
+        assert!(source.join("mod.ini").is_file());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn junction_handles_shell_metacharacters() {
+        let dir = ScratchDir::new("activation_junction");
+        let source = dir.join("library").join("A & echo pwned ^& B");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("mod.ini"), "key = x").unwrap();
+        let target = dir.join("Mods & more").join("A & echo pwned ^& B");
+        fs::create_dir_all(target.parent().unwrap()).unwrap();
+
+        create_junction(&source, &target).unwrap();
+        assert!(fs::symlink_metadata(&target).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(target.join("mod.ini")).unwrap(), "key = x");
+        remove_dir_link(&target).unwrap();
+        assert!(source.join("mod.ini").is_file());
+    }
+
+    #[test]
+    fn only_the_old_prefix_counts_as_legacy_disabled() {
+        let marker = DisabledMarker::Folder("Disabled".to_string());
+        assert_eq!(
+            marker.normalized_relative_path(Path::new("characters/diluc/DISABLED_RedCoat")),
+            Some(PathBuf::from("Disabled/characters/diluc/RedCoat"))
+        );
+        assert_eq!(marker.normalized_relative_path(Path::new("characters/diluc/Coat_disabled")), None);
+        assert_eq!(marker.normalized_relative_path(Path::new("characters/diluc/Coat.disabled")), None);
+        assert_eq!(marker.normalized_relative_path(Path::new("characters/diluc/(disabled) Coat")), None);
+    }
+
+    #[test]
+    fn markers_the_loader_still_loads_are_refused() {
+        assert!(DisabledMarker::Prefix("DISABLED ".to_string()).validate().is_ok());
+        assert!(DisabledMarker::Prefix("OFF_".to_string()).validate().is_err());
+        assert!(DisabledMarker::Folder("Disabled".to_string()).validate().is_ok());
+        assert!(DisabledMarker::Folder("Inactive".to_string()).validate().is_err());
+        let suffix = DisabledMarker::Suffix(".disabled".to_string());
+        assert!(suffix.validate().is_ok());
+        assert!(suffix.loader_warning().is_some());
+    }
+
+    #[test]
+    fn moves_by_copy_leave_nothing_behind() {
+        let dir = ScratchDir::new("activation_move");
+        let source = dir.join("Library").join("RedCoat");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("mod.ini"), "key = x").unwrap();
+        let target = dir.join("Mods").join("RedCoat");
+        fs::create_dir_all(target.parent().unwrap()).unwrap();
+
+        move_folder_by_copy(&source, &target).unwrap();
+        assert_eq!(fs::read_to_string(target.join("mod.ini")).unwrap(), "key = x");
+        assert_eq!(fs::read_dir(dir.join("Library")).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn real_folders_in_the_mods_path_are_not_active_links() {
+        let dir = ScratchDir::new("activation_locate");
+        let backend = LinkActivation { mods_path: dir.join("Mods"), library_path: dir.join("Library") };
+        fs::create_dir_all(dir.join("Library/characters/diluc/RedCoat")).unwrap();
+        fs::create_dir_all(dir.join("Mods/characters/diluc/RedCoat")).unwrap();
+        assert_eq!(backend.locate("characters/diluc/RedCoat").map(|(_, active)| active), Some(false));
+        assert!(backend.set_enabled("characters/diluc/RedCoat", true).is_err());
+
+        fs::remove_dir(dir.join("Mods/characters/diluc/RedCoat")).unwrap();
+        backend.set_enabled("characters/diluc/RedCoat", true).unwrap();
+        assert_eq!(backend.locate("characters/diluc/RedCoat").map(|(_, active)| active), Some(true));
+        backend.set_enabled("characters/diluc/RedCoat", false).unwrap();
+    }
+
+    #[test]
+    fn copied_trees_are_compared_by_content() {
+        let dir = ScratchDir::new("activation_compare");
+        let source = dir.join("Mods");
+        fs::create_dir_all(source.join("characters/diluc/RedCoat")).unwrap();
+        fs::write(source.join("characters/diluc/RedCoat/mod.ini"), "key = x").unwrap();
+        let target = dir.join("Copy");
+        copy_folder(&source, &target, &mut |_| {}).unwrap();
+        assert_eq!(compare_folders(&source, &target, true).unwrap(), None);
+
+        // Same size, different bytes: only the content check notices
+        fs::write(target.join("characters/diluc/RedCoat/mod.ini"), "key = y").unwrap();
+        assert_eq!(compare_folders(&source, &target, false).unwrap(), None);
+        assert!(compare_folders(&source, &target, true).unwrap().is_some());
+
+        fs::write(target.join("characters/diluc/RedCoat/extra.ini"), "").unwrap();
+        assert!(compare_folders(&source, &target, false).unwrap().is_some());
+    }
+}
@@ -13,8 +13,8 @@ use lazy_static::lazy_static;
 use rusqlite::{Connection, OptionalExtension, Result as SqlResult, params, OpenFlags};
 use serde::{Serialize, Deserialize};
 use std::collections::{HashMap, HashSet};
-use std::fs::{self, File};
-use std::io::{self, BufReader, BufRead, Read, Seek, Cursor, Write};
+use std::fs;
+use std::io::{self, Read, Seek, Cursor, Write};
 use std::path::{Path, PathBuf};
 use std::sync::{Mutex, Arc};
 use tauri::{
@@ -32,6 +32,14 @@ use zip::{ZipArchive, result::ZipError};
 use unrar::{Archive, Process, List, ListSplit};
 use rusqlite::Transaction;
 use std::ffi::OsStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+mod activation;
+mod migoto_ini;
+#[cfg(test)]
+mod test_support;
+use activation::{ActivationSettings, DisabledMarker, ModRoot};
+use migoto_ini::IniDocument;
 
 // --- Structs for Deserializing Definitions ---
 #[derive(Deserialize, Debug, Clone)]
@@ -54,6 +62,7 @@ struct CategoryDefinition {
 struct AssetLocationInfo {
     id: i64,
     clean_relative_path: String, // Stored relative path (e.g., category/entity/mod_name)
+    root_id: Option<i64>,        // Mod root holding the folder (None = primary Mods folder)
     entity_id: i64,
     category_slug: String,
     entity_slug: String,
@@ -64,6 +73,163 @@ struct Preset {
     id: i64,
     name: String,
     is_favorite: bool,
+    mode: String,                   // PRESET_MODE_STRICT or PRESET_MODE_OVERLAY
+    scope_entity_id: Option<i64>,   // Limits the preset to one entity...
+    scope_category_id: Option<i64>, // ...or to one category (never both)
+    included_preset_ids: Vec<i64>,  // Layers applied before this preset's own assets, in order
+    tag_selectors: Vec<PresetTagSelector>, // Applied after the layers; the preset's own assets win over them
+}
+
+// "Enable (or disable) every asset tagged X" as part of a preset. Later selectors win.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PresetTagSelector {
+    tag_id: i64,
+    is_enabled: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct Tag {
+    id: i64,
+    name: String,
+    color: Option<String>,
+    asset_count: i64, // Present assets only
+}
+
+// One row of preset_assets joined with the asset it points at
+#[derive(Debug, Clone)]
+struct PresetAssetState {
+    asset_id: i64,
+    is_enabled: bool,
+    clean_relative_path: String,
+    name: String,
+    entity_slug: String,
+    implied: bool, // Not listed in the preset, disabled because the preset is strict
+    root_id: Option<i64>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct PresetPreviewEntry {
+    asset_id: i64,
+    name: String,
+    entity_slug: String,
+    folder_name: String, // Clean relative path from DB
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct PresetApplyPreview {
+    preset_id: i64,
+    compared_to_preset_id: Option<i64>, // None = compared against the current state on disk
+    to_enable: Vec<PresetPreviewEntry>,
+    to_disable: Vec<PresetPreviewEntry>,
+    unchanged_count: usize,
+    missing: Vec<PresetPreviewEntry>,     // In the preset, but not on disk (or not in the compared preset)
+    not_covered: Vec<PresetPreviewEntry>, // On disk (or in the compared preset), but not in the preset
+}
+
+// Filters for shuffle_assets; all of them narrow the candidate pool
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct ShuffleOptions {
+    #[serde(default)]
+    category_id: Option<i64>,
+    #[serde(default)]
+    favorites_only: bool, // Only favorite assets, or assets that are part of a favorite preset
+    #[serde(default)]
+    preset_id: Option<i64>, // Only assets listed in this preset
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct ShufflePick {
+    entity_id: i64,
+    entity_slug: String,
+    asset_id: i64,
+    asset_name: String,
+}
+
+// --- Portable Preset File Format (export/import) ---
+const PORTABLE_PRESET_FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PortablePreset {
+    format_version: u32,
+    name: String,
+    game: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mode: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scope_entity_slug: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scope_category_slug: Option<String>,
+    // Tags are matched by name; missing ones are created on import
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tag_selectors: Vec<PortableTagSelector>,
+    assets: Vec<PortablePresetAsset>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PortableTagSelector {
+    tag_name: String,
+    is_enabled: bool,
+}
+
+// Assets are identified by stable keys instead of local DB ids
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PortablePresetAsset {
+    entity_slug: String,
+    folder_path: String,         // Clean relative path (category/entity/mod_name)
+    fingerprint: Option<String>, // See compute_folder_fingerprint
+    name: String,
+    is_enabled: bool,
+    // Personal data, only exported and imported on request, and only filled into matched assets that don't have their own
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    is_favorite: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rating: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notes: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct PresetImportMatch {
+    entry_index: usize,
+    asset_id: i64,
+    asset_name: String,
+    match_method: String, // "path", "fingerprint" or "folder_name"
+    is_enabled: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct PresetImportUnmatched {
+    entry_index: usize,
+    entry: PortablePresetAsset,
+    candidates: Vec<PresetPreviewEntry>, // Local assets of the same entity, for manual remapping
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct PresetImportAnalysis {
+    file_path: String,
+    preset_name: String,
+    game: Option<String>,
+    game_warning: Option<String>, // Set when the file targets another game; import_preset refuses it
+    scope_error: Option<String>,  // Set when the scope entity/category doesn't exist locally; import_preset refuses it
+    name_conflict: bool,
+    total_assets: usize,
+    matched: Vec<PresetImportMatch>,
+    unmatched: Vec<PresetImportUnmatched>,
+    duplicates: Vec<PresetImportDuplicate>, // Entries sharing a target asset; import_preset refuses them
+}
+
+// Preset entries (by index) that resolve to the same local asset
+#[derive(Serialize, Debug, Clone)]
+struct PresetImportDuplicate {
+    asset_id: i64,
+    entry_indices: Vec<usize>,
+}
+
+// User override for one entry of an imported preset (asset_id None = skip the entry)
+#[derive(Deserialize, Debug, Clone)]
+struct PresetImportRemap {
+    entry_index: usize,
+    asset_id: Option<i64>,
 }
 
 #[derive(Clone, serde::Serialize)]
@@ -81,16 +247,217 @@ struct DashboardStats {
     disabled_mods: i64,
     uncategorized_mods: i64, // Mods in entities ending with "-other"
     category_counts: HashMap<String, i64>, // Category Name -> Count
+    recently_added_mods: i64, // Added in the last DEFAULT_RECENT_DAYS days
+    recently_used_mods: i64,  // Enabled in the last DEFAULT_RECENT_DAYS days
 }
 
 #[derive(Serialize, Debug, Clone)] // Add Serialize
 struct KeybindInfo {
-    title: String,
-    key: String,
+    title: String,               // Section name, e.g. "KeySwap"
+    key: String,                 // All `key =` values joined for display
+    keys: Vec<String>,
+    back: Vec<String>,
+    bind_type: Option<String>,   // cycle / toggle / hold (3DMigoto defaults to activate)
+    condition: Option<String>,
+    variables: Vec<KeybindVariable>,
+    label: Option<String>,       // From the comment block above the section
+    ini_file: String,            // INI path relative to the mod folder
+}
+
+// One binding taking part in a collision
+#[derive(Serialize, Debug, Clone)]
+struct KeybindBinding {
+    asset_id: i64,
+    asset_name: String,
+    entity_slug: String,
+    section: String,
+    ini_file: String,       // INI path relative to the mod folder
+    raw_key: String,        // As written in the INI
+    normalized_key: String, // e.g. "CTRL+F1"
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct KeybindCollision {
+    key: String, // Main key shared by all bindings in the group
+    bindings: Vec<KeybindBinding>,
+    reserved_actions: Vec<String>, // Game actions on the same key (from definitions)
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct KeybindCollisionReport {
+    scanned_assets: usize,
+    total_bindings: usize,
+    collisions: Vec<KeybindCollision>,
+}
+
+// An INI file of a mod (subfolders included) and whether it is loaded by 3DMigoto
+#[derive(Serialize, Debug, Clone)]
+struct AssetIniFile {
+    file_name: String,      // Clean path relative to the mod folder, without the DISABLED_ prefix
+    disk_file_name: String, // Relative path as currently found on disk
+    is_enabled: bool,
+    is_helper: bool,        // One of EXCLUDED_INI_FILENAMES (orfix.ini, ...)
+}
+
+// --- Operation Journal Types ---
+// New enabled state of an asset; undo sets the opposite
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct AssetStateChange {
+    asset_id: i64,
+    is_enabled: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct IniStateChange {
+    asset_id: i64,
+    ini_file: String, // Clean path relative to the mod folder
+    is_enabled: bool,
+}
+
+// Editable columns of an asset row, captured before and after update_asset_info
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct AssetRecordSnapshot {
+    entity_id: i64,
+    name: String,
+    description: Option<String>,
+    author: Option<String>,
+    category_tag: Option<String>,
+    image_filename: Option<String>,
+    folder_name: String,
+}
+
+// Reversible effect of a library command, stored as JSON in operation_journal
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum JournalOp {
+    // toggle, bulk toggle, preset apply and shuffle
+    SetEnabled { assets: Vec<AssetStateChange>, inis: Vec<IniStateChange> },
+    // rename / relocate through update_asset_info
+    UpdateAsset { asset_id: i64, before: Box<AssetRecordSnapshot>, after: Box<AssetRecordSnapshot> },
+    // delete_asset; trash_id is None when the folder was already gone
+    Trash { asset_id: i64, trash_id: Option<i64> },
+}
+
+impl JournalOp {
+    fn kind(&self) -> &'static str {
+        match self {
+            JournalOp::SetEnabled { .. } => "set_enabled",
+            JournalOp::UpdateAsset { .. } => "update_asset",
+            JournalOp::Trash { .. } => "trash",
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct JournalEntry {
+    id: i64,
+    kind: String,
+    summary: String,
+    created_at: i64, // Unix seconds
+    undone: bool,
+    stale: bool, // A replay failed or its trash entry is gone; undo/redo skip it
+}
+
+// A rolling database backup in <app data>/backups/<game>/
+#[derive(Serialize, Debug, Clone)]
+struct BackupInfo {
+    file_name: String,
+    game_slug: String,
+    reason: String,  // e.g. "pre_migration", "game_switch", "manual"
+    created_at: i64, // Unix seconds
+    size_bytes: u64,
+}
+
+// A deleted mod folder waiting in the trash
+#[derive(Serialize, Debug, Clone)]
+struct TrashEntry {
+    id: i64,
+    asset_id: Option<i64>, // None once the asset row is gone or its folder came back
+    name: String,
+    folder_name: String,   // Clean relative path the mod had before deletion
+    trash_path: String,
+    was_enabled: bool,
+    deleted_at: i64,       // Unix seconds
+}
+
+// An asset whose folder disappeared from disk (kept with its metadata and preset links)
+#[derive(Serialize, Debug, Clone)]
+struct MissingAsset {
+    asset_id: i64,
+    name: String,
+    folder_name: String,
+    entity_slug: String,
+    missing_since: i64, // Unix seconds
+}
+
+// One finding of the INI linter
+#[derive(Serialize, Debug, Clone)]
+struct IniDiagnostic {
+    severity: String, // "error" | "warning" | "info"
+    code: String,     // Stable identifier, e.g. "missing_resource"
+    message: String,
+    ini_file: Option<String>,
+    line: Option<usize>, // 1-based
+    section: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct AssetHealth {
+    asset_id: i64,
+    asset_name: String,
+    entity_slug: String,
+    is_enabled: bool,
+    diagnostics: Vec<IniDiagnostic>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+struct LibraryHealthReport {
+    scanned_assets: usize,
+    assets_with_errors: usize,
+    assets_with_warnings: usize,
+    counts_by_code: HashMap<String, usize>,
+    assets: Vec<AssetHealth>, // Only assets that have diagnostics
+}
+
+// Result of set_disabled_marker
+#[derive(Serialize, Debug, Clone)]
+struct DisabledMarkerChange {
+    converted: usize,        // Disabled folders renamed to the new marker
+    warning: Option<String>, // Set when stock 3DMigoto would still load mods disabled this way
+}
+
+// A `global persist` variable of an asset and its value in d3dx_user.ini
+#[derive(Serialize, Debug, Clone)]
+struct PersistedVariable {
+    ini_file: String,              // INI path relative to the mod folder
+    name: String,                  // Including the leading '$'
+    value: Option<String>,         // None = not persisted yet, the INI default applies
+    default_value: Option<String>, // Initial value from [Constants]
+    user_ini_key: String,          // Key used in d3dx_user.ini
 }
 
-// Type alias for the top-level structure (HashMap: category_slug -> CategoryDefinition)
-type Definitions = HashMap<String, CategoryDefinition>;
+#[derive(Serialize, Debug, Clone)]
+struct KeybindVariable {
+    name: String,        // Including the leading '$'
+    values: Vec<String>, // Cycle values, e.g. `$swapvar = 0,1,2`
+    persist: bool,       // Declared `global persist` in [Constants]
+}
+
+// A key the game itself uses, checked by the keybind collision report
+#[derive(Deserialize, Debug, Clone)]
+struct ReservedKeyDefinition {
+    key: String,    // 3DMigoto key expression, e.g. "VK_F1" or "ctrl x"
+    action: String, // What the game does with it
+}
+
+// Top-level structure of a definitions file: category tables plus optional top-level keys
+#[derive(Deserialize, Debug, Default)]
+struct Definitions {
+    #[serde(default)]
+    reserved_keys: Vec<ReservedKeyDefinition>,
+    #[serde(flatten)]
+    categories: HashMap<String, CategoryDefinition>, // category_slug -> CategoryDefinition
+}
 
 // --- Constants for Settings Keys ---
 const SETTINGS_KEY_MODS_FOLDER: &str = "mods_folder_path";
@@ -98,8 +465,41 @@ const SETTINGS_KEY_APP_VERSION: &str = "app_version";
 const OTHER_ENTITY_SUFFIX: &str = "-other";
 const OTHER_ENTITY_NAME: &str = "Other/Unknown";
 const DB_NAME: &str = "app_data.sqlite";
+const SCHEMA_VERSION: i64 = 1; // Stored as PRAGMA user_version; bump with every change to run_schema_migrations
 const DISABLED_PREFIX: &str = "DISABLED_";
 const TARGET_IMAGE_FILENAME: &str = "preview.png";
+const PRESET_MODE_STRICT: &str = "strict";   // Assets not in the preset (within scope) are disabled on apply
+const PRESET_MODE_OVERLAY: &str = "overlay"; // Only assets listed in the preset are touched
+const SETTINGS_KEY_SHUFFLE_ON_LAUNCH: &str = "shuffle_on_launch"; // "true" to shuffle before launch_executable
+const SETTINGS_KEY_SHUFFLE_OPTIONS: &str = "shuffle_options";     // JSON-encoded ShuffleOptions
+const SHUFFLE_AVOID_RECENT: usize = 3;   // Recent picks per entity that won't be repeated
+const SHUFFLE_HISTORY_KEEP: usize = 20;  // History rows kept per entity
+const D3DX_USER_INI: &str = "d3dx_user.ini"; // Lives in the 3DMigoto folder, the parent of the mods folder
+const SETTINGS_KEY_ACTIVATION_MODE: &str = "activation_mode"; // activation::ACTIVATION_MODE_RENAME | ACTIVATION_MODE_LINK
+const SETTINGS_KEY_MOD_LIBRARY: &str = "mod_library_path";    // Managed library folder for the link mode
+const SETTINGS_KEY_DISABLED_MARKER: &str = "disabled_marker";  // JSON-encoded activation::DisabledMarker
+const SETTINGS_KEY_TRASH_RETENTION_DAYS: &str = "trash_retention_days"; // 0 keeps trashed mods forever
+const DEFAULT_TRASH_RETENTION_DAYS: i64 = 30;
+const TRASH_DIR_NAME: &str = "trash"; // In the app data dir, one subfolder per game
+const JOURNAL_MAX_ENTRIES: i64 = 200;  // Oldest operations are forgotten beyond this
+const DEFAULT_RECENT_DAYS: i64 = 7;     // Window of "recently added" / "recently used" views
+const BACKUP_DIR_NAME: &str = "backups"; // In the app data dir, one subfolder per game
+const BACKUPS_KEEP: usize = 10;          // Per game, oldest are deleted first
+const BACKUP_PAGES_PER_STEP: std::os::raw::c_int = 256;
+const BACKUP_REASON_MIGRATION: &str = "pre_migration";
+const BACKUP_REASON_GAME_SWITCH: &str = "game_switch";
+const BACKUP_REASON_PRE_RESTORE: &str = "pre_restore";
+const BACKUP_REASON_MANUAL: &str = "manual";
+const LIBRARY_FORMAT_VERSION: u32 = 1;
+const LIBRARY_MANIFEST_ENTRY: &str = "manifest.json";
+const LIBRARY_DB_ENTRY: &str = "database.sqlite";
+const LIBRARY_MODS_PREFIX: &str = "mods/"; // Mod folders are stored under their clean relative path
+const LIBRARY_TEMP_DIR_NAME: &str = "library_transfer"; // In the app data dir, holds DB snapshots while exporting/importing
+const SETTINGS_KEY_WRITE_SIDECARS: &str = "write_sidecars"; // "true" to keep a metadata sidecar in every mod folder
+const SIDECAR_FILENAME: &str = ".gmm.toml";
+const SIDECAR_VERSION: u32 = 1;
+const SETTINGS_KEY_SEARCH_INI_SECTIONS: &str = "search_index_ini_sections"; // "true" to make INI section names searchable
+const DEFAULT_SEARCH_PAGE_SIZE: usize = 50;
 
 // --- Error Handling ---
 #[derive(Debug, Error)]
@@ -169,6 +569,16 @@ const PRUNING_START_EVENT: &str = "prune://start";
 const PRUNING_PROGRESS_EVENT: &str = "prune://progress";
 const PRUNING_COMPLETE_EVENT: &str = "prune://complete";
 const PRUNING_ERROR_EVENT: &str = "prune://error";
+// Mods folder relocation
+const RELOCATE_START_EVENT: &str = "relocate://start";
+const RELOCATE_PROGRESS_EVENT: &str = "relocate://progress";
+const RELOCATE_COMPLETE_EVENT: &str = "relocate://complete";
+const RELOCATE_ERROR_EVENT: &str = "relocate://error";
+// Library export / import (one runs at a time, they share the events)
+const LIBRARY_TRANSFER_START_EVENT: &str = "library://start";
+const LIBRARY_TRANSFER_PROGRESS_EVENT: &str = "library://progress";
+const LIBRARY_TRANSFER_COMPLETE_EVENT: &str = "library://complete";
+const LIBRARY_TRANSFER_ERROR_EVENT: &str = "library://error";
 // -------------------------
 
 const SETTINGS_KEY_TRAVELER_MIGRATION_COMPLETE: &str = "traveler_migration_complete_v1"; // Added v1 for potential future migrations
@@ -208,6 +618,9 @@ struct DeducedInfo {
     author: Option<String>,
     description: Option<String>,
     image_filename: Option<String>,
+    sidecar_presets: Vec<SidecarPresetEntry>, // Preset memberships recorded in the sidecar
+    personal: AssetPersonalData,              // Only ever set from the sidecar
+    tags: Vec<String>,                        // INI Tags list and type hint, or the sidecar's tags
 }
 
 #[derive(Clone)]
@@ -223,7 +636,7 @@ struct DeductionMaps {
 
 #[derive(Serialize, Deserialize, Debug)] struct Category { id: i64, name: String, slug: String }
 #[derive(Serialize, Deserialize, Debug)] struct Entity { id: i64, category_id: i64, name: String, slug: String, description: Option<String>, details: Option<String>, base_image: Option<String>, mod_count: i32, enabled_mod_count: Option<i32>, recent_mod_count: Option<i32>, favorite_mod_count: Option<i32> }
-#[derive(Serialize, Deserialize, Debug, Clone)] struct Asset { id: i64, entity_id: i64, name: String, description: Option<String>, folder_name: String, image_filename: Option<String>, author: Option<String>, category_tag: Option<String>, is_enabled: bool }
+#[derive(Serialize, Deserialize, Debug, Clone)] struct Asset { id: i64, entity_id: i64, name: String, description: Option<String>, folder_name: String, image_filename: Option<String>, author: Option<String>, category_tag: Option<String>, is_enabled: bool, added_at: Option<i64>, updated_at: Option<i64>, last_enabled_at: Option<i64>, last_seen_on_disk_at: Option<i64>, #[serde(default)] is_favorite: bool, rating: Option<i64>, notes: Option<String>, #[serde(default)] tags: Vec<String>, #[serde(default)] root_id: Option<i64>, #[serde(default)] clean_folder_name: String }
 
 #[derive(Serialize, Debug, Clone)]
 struct EntityWithCounts {
@@ -271,6 +684,7 @@ fn run_traveler_migration_logic(
 
     let base_mods_path = get_mods_base_path_from_settings(db_state)
         .map_err(|e| format!("[Migration] Failed to get mods base path: {}", e))?;
+    let backend = activation::backend_for(&base_mods_path);
 
     // --- Use a single lock scope for all DB operations ---
     let mut conn_guard = db_state.0.lock().map_err(|_| "[Migration] DB lock poisoned".to_string())?;
@@ -424,76 +838,15 @@ fn run_traveler_migration_logic(
         let new_clean_relative_path_buf = PathBuf::new().join(&target_category_slug).join(&target_slug).join(mod_folder_base_name_from_db.as_ref());
         let new_clean_relative_path_str = new_clean_relative_path_buf.to_string_lossy().replace("\\", "/");
 
-        // Determine the current *actual* path on disk (check enabled/disabled)
-        let disabled_filename_current = format!("{}{}", DISABLED_PREFIX, mod_folder_base_name_from_db);
-        let relative_parent_path_current = current_relative_path_buf.parent();
-
-        let full_path_if_enabled_current = base_mods_path.join(&current_relative_path_buf);
-        let full_path_if_disabled_current = match relative_parent_path_current {
-            Some(parent) if parent.as_os_str().len() > 0 => base_mods_path.join(parent).join(&disabled_filename_current),
-            _ => base_mods_path.join(&disabled_filename_current),
-        };
-
-        let (current_actual_path_on_disk, is_currently_disabled) =
-            if full_path_if_enabled_current.is_dir() {
-                (full_path_if_enabled_current, false)
-            } else if full_path_if_disabled_current.is_dir() {
-                (full_path_if_disabled_current, true)
-            } else {
-                let err = format!("[Migration]   -> ERROR: Source folder not found on disk for asset {} at '{}' or '{}'. Skipping.", asset_id, full_path_if_enabled_current.display(), full_path_if_disabled_current.display());
-                println!("{}", err);
-                errors.push(err);
-                continue; // Skip this asset
-            };
-        println!("[Migration]   -> Current path on disk: '{}' (Disabled: {})", current_actual_path_on_disk.display(), is_currently_disabled);
-
-        // Construct the new *actual* destination path on disk, preserving disabled state
-        let new_folder_name_on_disk = if is_currently_disabled {
-            format!("{}{}", DISABLED_PREFIX, mod_folder_base_name_from_db)
-        } else {
-            mod_folder_base_name_from_db.to_string()
-        };
-        let new_actual_dest_path_on_disk = base_mods_path.join(&target_category_slug).join(&target_slug).join(&new_folder_name_on_disk);
-        println!("[Migration]   -> New destination path on disk: '{}'", new_actual_dest_path_on_disk.display());
-
-        // --- Perform Filesystem Move (before DB commit, but after tx start) ---
-        if current_actual_path_on_disk == new_actual_dest_path_on_disk {
-             println!("[Migration]   -> Skipping filesystem move (paths are the same). Might indicate only DB update needed.");
-        } else {
-            // Ensure parent directory exists
-            if let Some(parent) = new_actual_dest_path_on_disk.parent() {
-                 if !parent.exists() {
-                     println!("[Migration]   -> Creating parent directory: {}", parent.display());
-                     if let Err(e) = fs::create_dir_all(parent) {
-                         let err = format!("[Migration]   -> ERROR: Failed to create parent directory '{}': {}. Skipping asset {}.", parent.display(), e, asset_id);
-                         println!("{}", err);
-                         errors.push(err);
-                         continue; // Skip this asset
-                     }
-                 }
-            } else {
-                 let err = format!("[Migration]   -> ERROR: Cannot determine parent directory for new path '{}'. Skipping asset {}.", new_actual_dest_path_on_disk.display(), asset_id);
-                 println!("{}", err);
-                 errors.push(err);
-                 continue; // Skip this asset
-            }
-
-            // Check if target exists unexpectedly
-            if new_actual_dest_path_on_disk.exists() {
-                let err = format!("[Migration]   -> ERROR: Target path '{}' already exists. Skipping asset {}.", new_actual_dest_path_on_disk.display(), asset_id);
+        // --- Perform Filesystem Move (before DB commit, but after tx start), preserving disabled state ---
+        match activation::relocate_asset(backend.as_ref(), &current_clean_relative_path, &new_clean_relative_path_str) {
+            Ok(new_path) => println!("[Migration]   -> Folder now at '{}'", new_path.display()),
+            Err(e) => {
+                let err = format!("[Migration]   -> ERROR: Failed to move folder for asset {}: {}. Skipping.", asset_id, e);
                 println!("{}", err);
                 errors.push(err);
                 continue; // Skip this asset
             }
-
-            // Perform the rename
-            println!("[Migration]   -> Moving '{}' -> '{}'", current_actual_path_on_disk.display(), new_actual_dest_path_on_disk.display());
-            if let Err(e) = fs::rename(&current_actual_path_on_disk, &new_actual_dest_path_on_disk) {
-                 let err = format!("[Migration]   -> ERROR: Failed to move folder for asset {}: {}. Skipping.", asset_id, e);
-                 println!("{}", err);
-                 errors.push(err);
-                 continue; // Skip this asset
-            }
         }
 
         // --- Update Database Record (within transaction) ---
@@ -532,6 +885,7 @@ fn run_traveler_migration_logic(
 
         // --- Commit Transaction ---
         tx.commit().map_err(|e| format!("[Migration] Failed to commit transaction: {}", e))?;
+        persist_materialized_folders(&conn_guard);
 
         let final_msg = format!("Traveler migration completed successfully. Migrated {} assets.", migrated_count);
         println!("[Migration] {}", final_msg);
@@ -544,7 +898,9 @@ fn run_traveler_migration_logic(
         for e in &errors {
             eprintln!("  - {}", e);
         }
-        // Rollback happens automatically when `tx` is dropped due to error return
+        // Rollback happens automatically when `tx` is dropped; moved folders keep their link-mode records
+        drop(tx);
+        persist_materialized_folders(&conn_guard);
         Err(format!("{}\n{}", err_summary, errors.join("\n")))
     }
 }
@@ -693,7 +1049,26 @@ fn get_internal_db_slug(db_path: &PathBuf) -> Result<Option<String>, AppError> {
     get_setting_value(&conn, DB_INTERNAL_GAME_SLUG_KEY)
 }
 
-fn find_asset_ini_paths(conn: &Connection, asset_id: i64, base_mods_path: &PathBuf) -> Result<Vec<PathBuf>, AppError> {
+// .ini files of a mod including subfolders, as (path relative to the mod folder, full path).
+// Folders 3DMigoto skips (DISABLED prefix) are not entered; DISABLED-prefixed files are still listed.
+fn list_ini_files_recursive(mod_folder_path: &Path) -> Vec<(String, PathBuf)> {
+    WalkDir::new(mod_folder_path).min_depth(1).into_iter()
+        .filter_entry(|e| !e.file_type().is_dir() || !e.file_name().to_string_lossy().to_uppercase().starts_with(DISABLED_PREFIX))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file() && e.path().extension().is_some_and(|ext| ext.eq_ignore_ascii_case("ini")))
+        .filter_map(|e| {
+            let relative = e.path().strip_prefix(mod_folder_path).ok()?.to_string_lossy().replace("\\", "/");
+            Some((relative, e.into_path()))
+        })
+        .collect()
+}
+
+// True if 3DMigoto skips this INI (its file name starts with DISABLED)
+fn is_disabled_ini(relative_path: &str) -> bool {
+    relative_path.rsplit('/').next().unwrap_or(relative_path).to_uppercase().starts_with(DISABLED_PREFIX)
+}
+
+fn find_asset_ini_paths(conn: &Connection, asset_id: i64, base_mods_path: &PathBuf) -> Result<Vec<(String, PathBuf)>, AppError> {
     println!("[find_asset_ini_paths] CALLED for asset ID: {}", asset_id);
     let asset_info = get_asset_location_info(conn, asset_id)?;
 
@@ -704,37 +1079,19 @@ fn find_asset_ini_paths(conn: &Connection, asset_id: i64, base_mods_path: &PathB
         println!("[find_asset_ini_paths] ERROR: Filename extracted from DB path is empty: {}", asset_info.clean_relative_path);
         return Err(AppError::ModOperation("Current filename is empty".to_string()));
      }
-    let disabled_filename = format!("{}{}", DISABLED_PREFIX, filename_str);
-    let relative_parent_path = relative_path_buf.parent();
-
-    let full_path_if_enabled = base_mods_path.join(&relative_path_buf);
-    let full_path_if_disabled = match relative_parent_path {
-        Some(parent) if parent.as_os_str().len() > 0 => base_mods_path.join(parent).join(&disabled_filename),
-        _ => base_mods_path.join(&disabled_filename),
-    };
-
-    let mod_folder_path = if full_path_if_enabled.is_dir() {
-        println!("[find_asset_ini_paths] Found enabled path: {}", full_path_if_enabled.display());
-        full_path_if_enabled
-    } else if full_path_if_disabled.is_dir() {
-        println!("[find_asset_ini_paths] Found disabled path: {}", full_path_if_disabled.display());
-        full_path_if_disabled
-    } else {
-        println!("[find_asset_ini_paths] Mod folder not found for asset ID {}. Checked {} and {}", asset_id, full_path_if_enabled.display(), full_path_if_disabled.display());
-        return Ok(Vec::new()); // Return empty vec if folder not found
+    let mod_folder_path = match locate_asset_folder(base_mods_path, asset_info.root_id, &asset_info.clean_relative_path) {
+        Some((path, is_enabled)) => {
+            println!("[find_asset_ini_paths] Found {} path: {}", if is_enabled { "enabled" } else { "disabled" }, path.display());
+            path
+        }
+        None => {
+            println!("[find_asset_ini_paths] Mod folder not found for asset ID {} (path: {}).", asset_id, asset_info.clean_relative_path);
+            return Ok(Vec::new()); // Return empty vec if folder not found
+        }
     };
 
-    // --- Collect all .ini files ---
-    let mut ini_paths = Vec::new();
-    for entry in WalkDir::new(&mod_folder_path).max_depth(1).min_depth(1).into_iter().filter_map(|e| e.ok()) {
-        if entry.file_type().is_file() {
-            if let Some(ext) = entry.path().extension() {
-                if ext.to_ascii_lowercase() == "ini" {
-                    ini_paths.push(entry.path().to_path_buf());
-                }
-            }
-        }
-    }
+    // --- Collect all .ini files, subfolders included ---
+    let ini_paths = list_ini_files_recursive(&mod_folder_path);
     println!("[find_asset_ini_paths] Found {} INI files in {}: {:?}", ini_paths.len(), mod_folder_path.display(), ini_paths);
     Ok(ini_paths) // Return the collected paths
 }
@@ -820,6 +1177,31 @@ fn fetch_deduction_maps(conn: &Connection) -> SqlResult<DeductionMaps> {
     })
 }
 
+// Metadata a mod declares in the [Mod]/[Settings]/[Info]/[General] sections of its INI; later sections win
+#[derive(Debug, Default)]
+struct IniModMetadata {
+    name: Option<String>,
+    author: Option<String>,
+    description: Option<String>,
+    target: Option<String>,
+    mod_type: Option<String>,
+    tags: Vec<String>,
+}
+
+fn read_ini_mod_metadata(ini: &Ini) -> IniModMetadata {
+    let mut meta = IniModMetadata::default();
+    for section_name in ["Mod", "Settings", "Info", "General"] {
+        let Some(section) = ini.section(Some(section_name)) else { continue };
+        if let Some(name) = section.get("Name").or_else(|| section.get("ModName")) { meta.name = Some(name.trim().to_string()); }
+        if let Some(author) = section.get("Author") { meta.author = Some(author.trim().to_string()); }
+        if let Some(desc) = section.get("Description") { meta.description = Some(desc.trim().to_string()); }
+        if let Some(target) = section.get("Target").or_else(|| section.get("Entity")).or_else(|| section.get("Character")) { meta.target = Some(target.trim().to_string()); }
+        if let Some(typ) = section.get("Type").or_else(|| section.get("Category")) { meta.mod_type = Some(typ.trim().to_string()); }
+        if let Some(tags) = section.get("Tags") { meta.tags.extend(split_tag_list(tags)); }
+    }
+    meta
+}
+
 fn deduce_mod_info_v2(
     mod_folder_path: &PathBuf,
     base_mods_path: &PathBuf,
@@ -841,17 +1223,33 @@ fn deduce_mod_info_v2(
         mod_name: mod_folder_name.clone(),
         mod_type_tag: None, author: None, description: None,
         image_filename: find_preview_image(mod_folder_path),
+        sidecar_presets: Vec::new(),
+        personal: AssetPersonalData::default(),
+        tags: Vec::new(),
     };
 
     let mut found_entity_slug: Option<String> = None;
     let mut ini_target_hint: Option<String> = None;
     let mut ini_type_hint: Option<String> = None;
 
-    // --- 1. Try Matching Mod Folder Name (Highest Priority) ---
-    println!("[Deduce V2] P1: Trying mod folder name matching: '{}'", mod_folder_name);
-    if let Some(slug) = find_entity_slug_from_hint(&mod_folder_name, maps) {
-        found_entity_slug = Some(slug);
-        println!("[Deduce V2]   -> Found entity via mod folder name: '{}' -> {}", mod_folder_name, found_entity_slug.as_ref().unwrap());
+    // --- 0. GMM Sidecar (Overrides Everything Else) ---
+    let sidecar = read_mod_sidecar(mod_folder_path);
+    if let Some(sidecar) = &sidecar {
+        println!("[Deduce V2] P0: Found sidecar. Entity={:?}, Name={:?}", sidecar.entity, sidecar.name);
+        match &sidecar.entity {
+            Some(slug) if maps.entity_slug_to_id.contains_key(slug) => found_entity_slug = Some(slug.clone()),
+            Some(slug) => println!("[Deduce V2]   -> Sidecar entity '{}' is unknown, deducing instead.", slug),
+            None => {}
+        }
+    }
+
+    // --- 1. Try Matching Mod Folder Name ---
+    if found_entity_slug.is_none() {
+        println!("[Deduce V2] P1: Trying mod folder name matching: '{}'", mod_folder_name);
+        if let Some(slug) = find_entity_slug_from_hint(&mod_folder_name, maps) {
+            found_entity_slug = Some(slug);
+            println!("[Deduce V2]   -> Found entity via mod folder name: '{}' -> {}", mod_folder_name, found_entity_slug.as_ref().unwrap());
+        }
     }
 
     // --- 2. Check Parent Folders for ENTITY Match ---
@@ -878,24 +1276,22 @@ fn deduce_mod_info_v2(
     let ini_path_option = WalkDir::new(mod_folder_path)
         .max_depth(1).min_depth(1).into_iter()
         .filter_map(|e| e.ok())
-        .find(|entry| entry.file_type().is_file() && entry.path().extension().map_or(false, |ext| ext.eq_ignore_ascii_case("ini")))
+        .find(|entry| entry.file_type().is_file() && entry.path().extension().is_some_and(|ext| ext.eq_ignore_ascii_case("ini")))
         .map(|e| e.into_path());
 
     if let Some(ini_path) = ini_path_option {
         println!("[Deduce V2] Found INI: {}", ini_path.display());
         if let Ok(ini_content) = fs::read_to_string(&ini_path) {
             if let Ok(ini) = Ini::load_from_str(&ini_content) {
-                 for section_name in ["Mod", "Settings", "Info", "General"] {
-                    if let Some(section) = ini.section(Some(section_name)) {
-                        // Update metadata if found
-                        if let Some(name) = section.get("Name").or_else(|| section.get("ModName")) { info.mod_name = name.trim().to_string(); }
-                        if let Some(author) = section.get("Author") { info.author = Some(author.trim().to_string()); }
-                        if let Some(desc) = section.get("Description") { info.description = Some(desc.trim().to_string()); }
-                        // Get hints (even if entity found, these might be useful someday)
-                        if let Some(target) = section.get("Target").or_else(|| section.get("Entity")).or_else(|| section.get("Character")) { ini_target_hint = Some(target.trim().to_string()); }
-                        if let Some(typ) = section.get("Type").or_else(|| section.get("Category")) { info.mod_type_tag = Some(typ.trim().to_string()); ini_type_hint = info.mod_type_tag.clone(); } // Store type hint
-                    }
-                }
+                let meta = read_ini_mod_metadata(&ini);
+                if let Some(name) = meta.name { info.mod_name = name; }
+                if meta.author.is_some() { info.author = meta.author; }
+                if meta.description.is_some() { info.description = meta.description; }
+                // Get hints (even if entity found, these might be useful someday)
+                ini_target_hint = meta.target;
+                if meta.mod_type.is_some() { info.mod_type_tag = meta.mod_type; ini_type_hint = info.mod_type_tag.clone(); } // Store type hint
+                info.tags.extend(meta.tags);
+                info.tags.extend(ini_type_hint.as_deref().and_then(normalize_tag_name));
                 println!("[Deduce V2] INI parsed. Name='{}', Author='{:?}', TargetHint='{:?}', TypeHint='{:?}'", info.mod_name, info.author, ini_target_hint, ini_type_hint);
             } else {
                 eprintln!("[Deduce V2] Warning: Failed to parse INI content from {}", ini_path.display());
@@ -907,6 +1303,21 @@ fn deduce_mod_info_v2(
         println!("[Deduce V2] No INI file found in mod folder.");
     }
 
+    // Metadata saved in the sidecar wins over the INI
+    if let Some(sidecar) = sidecar {
+        if let Some(name) = sidecar.name.filter(|n| !n.trim().is_empty()) { info.mod_name = name; }
+        if sidecar.author.is_some() { info.author = sidecar.author; }
+        if sidecar.description.is_some() { info.description = sidecar.description; }
+        if sidecar.category_tag.is_some() { info.mod_type_tag = sidecar.category_tag; }
+        if let Some(tags) = sidecar.tags { info.tags = tags.iter().filter_map(|t| normalize_tag_name(t)).collect(); }
+        info.sidecar_presets = sidecar.presets;
+        info.personal = AssetPersonalData {
+            is_favorite: sidecar.is_favorite,
+            rating: sidecar.rating.filter(|r| (1..=5).contains(r)),
+            notes: sidecar.notes,
+        };
+    }
+
     // --- 4. Try Matching INI Target Hint (if entity still not found) ---
     if found_entity_slug.is_none() {
         if let Some(target_hint) = &ini_target_hint {
@@ -1116,9 +1527,25 @@ fn deduce_mod_info_v2(
     Some(info)
 }
 
+// Fails if another asset row is registered at `folder_name` in the root (UNIQUE (root_id, folder_name)).
+// Rows of deleted or vanished mods keep their folder so they can be restored, and block it until then.
+fn ensure_folder_unclaimed(conn: &Connection, root_id: Option<i64>, folder_name: &str, asset_id: i64) -> CmdResult<()> {
+    let claimed: Option<(String, bool)> = conn.query_row(
+        "SELECT name, missing_since IS NOT NULL FROM assets WHERE root_id IS ?1 AND folder_name = ?2 AND id != ?3",
+        params![root_id, folder_name, asset_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).optional().map_err(|e| format!("Failed to check '{}': {}", folder_name, e))?;
+    match claimed {
+        None => Ok(()),
+        Some((name, true)) => Err(format!(
+            "'{}' still belongs to '{}', which was deleted or went missing. Restore it or remove it for good first.", folder_name, name)),
+        Some((name, false)) => Err(format!("'{}' is already used by '{}'.", folder_name, name)),
+    }
+}
+
 fn get_asset_location_info(conn: &Connection, asset_id: i64) -> Result<AssetLocationInfo, AppError> {
     conn.query_row(
-        "SELECT a.id, a.folder_name, a.entity_id, c.slug, e.slug
+        "SELECT a.id, a.folder_name, a.entity_id, c.slug, e.slug, a.root_id
          FROM assets a
          JOIN entities e ON a.entity_id = e.id
          JOIN categories c ON e.category_id = c.id
@@ -1129,6 +1556,7 @@ fn get_asset_location_info(conn: &Connection, asset_id: i64) -> Result<AssetLoca
                 id: row.get(0)?,
                 // Ensure forward slashes when reading
                 clean_relative_path: row.get::<_, String>(1)?.replace("\\", "/"),
+                root_id: row.get(5)?,
                 entity_id: row.get(2)?,
                 category_slug: row.get(3)?,
                 entity_slug: row.get(4)?,
@@ -1235,7 +1663,9 @@ fn write_app_config(app_handle: &AppHandle, config: &AppConfig) -> Result<(), Ap
 }
 
 // --- Helper Function to get current enabled state (reusable) ---
-fn get_current_asset_enabled_state(conn: &Connection, asset_id: i64, base_mods_path: &PathBuf) -> Result<bool, AppError> {
+// Shared by apply_preset and preview_preset_apply so the preview can't disagree with the apply.
+// None if the mod folder is missing from disk.
+fn get_current_asset_enabled_state(conn: &Connection, asset_id: i64, base_mods_path: &Path) -> Result<Option<bool>, AppError> {
     let asset_info = get_asset_location_info(conn, asset_id)?; // Reuse existing helper
 
     let relative_path_buf = PathBuf::from(&asset_info.clean_relative_path);
@@ -1246,65 +1676,258 @@ fn get_current_asset_enabled_state(conn: &Connection, asset_id: i64, base_mods_p
         return Err(AppError::ModOperation("Current filename is empty".to_string()));
     }
 
-    // Check ONLY the enabled path based on the CLEAN relative path from DB
-    let full_path_if_enabled = base_mods_path.join(&relative_path_buf);
+    // Enabled state as reported by the activation backend
+    Ok(locate_asset_backend(base_mods_path, asset_info.root_id, &asset_info.clean_relative_path).map(|(_, enabled)| enabled))
+}
 
-    Ok(full_path_if_enabled.is_dir()) // Return true if the 'enabled' path exists
+// Activation backend of an asset and whether its folder is enabled, or None if the folder is missing
+fn locate_asset_backend(base_mods_path: &Path, root_id: Option<i64>, clean_relative_path: &str) -> Option<(Box<dyn activation::ActivationBackend>, bool)> {
+    activation::backend_for_asset(base_mods_path, root_id)
+        .and_then(|backend| backend.locate(clean_relative_path).map(|(_, is_enabled)| (backend, is_enabled)))
 }
 
-// --- Definition Syncing ---
-fn sync_definitions(conn: &mut Connection, app_handle: &AppHandle, active_game_slug: &str) -> Result<(), AppError> {
-    let definition_resource_path = format!("definitions/{}.toml", active_game_slug);
-    println!("Attempting to sync definitions from resource: {}", definition_resource_path);
+// --- Helper to find an asset folder on disk in either state ---
+// Returns the mod's source folder and whether it is enabled, or None if it doesn't exist.
+// Goes through the activation backend of the mod root stored for the asset
+// (the configured rename/library backend for the primary Mods folder).
+fn locate_asset_folder(base_mods_path: &Path, root_id: Option<i64>, clean_relative_path: &str) -> Option<(PathBuf, bool)> {
+    activation::backend_for_asset(base_mods_path, root_id)
+        .and_then(|backend| backend.locate(clean_relative_path))
+}
 
-    let definitions: Definitions = match app_handle.path_resolver().resolve_resource(&definition_resource_path) {
-        Some(path) => {
-            println!("Found definition file at: {}", path.display());
-            match fs::read_to_string(&path) {
-                Ok(toml_content) => {
-                    match toml::from_str(&toml_content) {
-                        Ok(defs) => {
-                            println!("Successfully parsed definitions for '{}'.", active_game_slug);
-                            defs
-                        },
-                        Err(e) => {
-                            eprintln!("ERROR: Failed to parse TOML from {}: {}. Using empty definitions.", path.display(), e);
-                            HashMap::new()
-                        }
-                    }
-                },
-                Err(e) => {
-                    eprintln!("ERROR: Failed to read definition file {}: {}. Using empty definitions.", path.display(), e);
-                    HashMap::new()
-                }
+// Path of a found asset folder relative to the Mods folder (falls back to the clean path for library folders)
+fn asset_disk_relative_path(base_mods_path: &Path, found_path: &Path, clean_relative_path: &str) -> String {
+    found_path.strip_prefix(base_mods_path)
+        .map(|p| p.to_string_lossy().replace("\\", "/"))
+        .unwrap_or_else(|_| clean_relative_path.to_string())
+}
+
+// --- Content Fingerprinting ---
+// FNV-1a (64-bit). Used instead of DefaultHasher because fingerprints are written
+// to exported files and must stay stable across Rust versions and machines.
+fn fnv1a_update(hash: &mut u64, bytes: &[u8]) {
+    for byte in bytes {
+        *hash ^= *byte as u64;
+        *hash = hash.wrapping_mul(0x100000001b3);
+    }
+}
+
+// Fingerprint of a mod folder: relative file paths + sizes, plus full INI contents.
+// Preview images and the metadata sidecar are skipped since the app rewrites them.
+fn compute_folder_fingerprint(dir_path: &Path) -> Option<String> {
+    if !dir_path.is_dir() { return None; }
+    let preview_names = ["preview.png", "preview.jpg", "icon.png", "icon.jpg", "thumbnail.png", "thumbnail.jpg"];
+
+    let mut files: Vec<(String, PathBuf, u64)> = Vec::new();
+    for entry in WalkDir::new(dir_path).min_depth(1).into_iter().filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() { continue; }
+        let relative = match entry.path().strip_prefix(dir_path) {
+            Ok(p) => p.to_string_lossy().replace("\\", "/").to_lowercase(),
+            Err(_) => continue,
+        };
+        if preview_names.contains(&relative.as_str()) || relative == SIDECAR_FILENAME { continue; }
+        let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        files.push((relative, entry.into_path(), size));
+    }
+    if files.is_empty() { return None; }
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for (relative, full_path, size) in &files {
+        fnv1a_update(&mut hash, relative.as_bytes());
+        fnv1a_update(&mut hash, &size.to_le_bytes());
+        if relative.ends_with(".ini") {
+            if let Ok(content) = fs::read(full_path) {
+                fnv1a_update(&mut hash, &content);
             }
-        },
-        None => {
-            eprintln!("ERROR: Definition file resource '{}' not found. Using empty definitions.", definition_resource_path);
-            HashMap::new()
         }
-    };
-
-    if definitions.is_empty() {
-        println!("Skipping definition sync as no definitions were loaded for '{}'.", active_game_slug);
-        return Ok(());
     }
+    Some(format!("{:016x}", hash))
+}
 
-    println!("Loaded {} categories from definitions for '{}'. Starting sync.", definitions.len(), active_game_slug);
-    
-    let tx = conn.transaction()?;
+// --- Metadata Sidecars ---
+// Optional `.gmm.toml` kept in each mod folder. Scans read it before any other hint,
+// so a fresh install pointed at the same folders rebuilds the library as it was.
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct ModSidecar {
+    #[serde(default)]
+    version: u32,
+    name: Option<String>,
+    entity: Option<String>, // Entity slug
+    author: Option<String>,
+    description: Option<String>,
+    category_tag: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    is_favorite: bool,
+    rating: Option<i64>,
+    notes: Option<String>,
+    tags: Option<Vec<String>>, // None keeps whatever the INI suggests
+    #[serde(default)]
+    presets: Vec<SidecarPresetEntry>, // Last so TOML can write it as an array of tables
+}
 
-    for (category_slug, category_def) in definitions.iter() {
-        tx.execute("INSERT OR REPLACE INTO categories (name, slug) VALUES (?1, ?2)", params![category_def.name, category_slug])?;
-        let category_id: i64 = tx.query_row("SELECT id FROM categories WHERE slug = ?1", params![category_slug], |row| row.get(0))?;
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct SidecarPresetEntry {
+    name: String,
+    is_enabled: bool,
+}
 
-        let mut existing_slugs: HashSet<String> = {
-            let mut stmt = tx.prepare("SELECT slug FROM entities WHERE category_id = ?1")?;
-            let slug_iter = stmt.query_map(params![category_id], |row| row.get(0))?;
-            let mut slugs = HashSet::new();
-            for slug_result in slug_iter {
-                slugs.insert(slug_result?);
-            }
+// Reads the sidecar of a mod folder. Missing or unparsable files yield None.
+fn read_mod_sidecar(mod_folder_path: &Path) -> Option<ModSidecar> {
+    let sidecar_path = mod_folder_path.join(SIDECAR_FILENAME);
+    let content = fs::read_to_string(&sidecar_path).ok()?;
+    match toml::from_str::<ModSidecar>(&content) {
+        Ok(sidecar) => Some(sidecar),
+        Err(e) => {
+            eprintln!("[read_mod_sidecar] Ignoring unparsable sidecar {}: {}", sidecar_path.display(), e);
+            None
+        }
+    }
+}
+
+fn write_asset_sidecar(conn: &Connection, base_mods_path: &Path, asset_id: i64) -> Result<(), AppError> {
+    let (mut sidecar, folder_name, root_id) = conn.query_row(
+        "SELECT a.name, e.slug, a.author, a.description, a.category_tag, a.folder_name, a.is_favorite, a.rating, a.notes, a.root_id
+         FROM assets a JOIN entities e ON a.entity_id = e.id WHERE a.id = ?1",
+        params![asset_id],
+        |row| Ok((ModSidecar {
+            version: SIDECAR_VERSION,
+            name: Some(row.get(0)?),
+            entity: Some(row.get(1)?),
+            author: row.get(2)?,
+            description: row.get(3)?,
+            category_tag: row.get(4)?,
+            is_favorite: row.get::<_, i64>(6)? == 1,
+            rating: row.get(7)?,
+            notes: row.get(8)?,
+            tags: None,
+            presets: Vec::new(),
+        }, row.get::<_, String>(5)?.replace("\\", "/"), row.get::<_, Option<i64>>(9)?)),
+    ).map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => AppError::NotFound(format!("Asset with ID {} not found.", asset_id)),
+        _ => AppError::from(e),
+    })?;
+
+    let backend = activation::backend_for_asset(base_mods_path, root_id)
+        .ok_or_else(|| AppError::NotFound(format!("Mod folder not found on disk for '{}'.", folder_name)))?;
+    if activation::root_role(root_id) == activation::ROOT_ROLE_READONLY { return Ok(()); }
+    let (folder_path, _) = backend.locate(&folder_name)
+        .ok_or_else(|| AppError::NotFound(format!("Mod folder not found on disk for '{}'.", folder_name)))?;
+
+    let mut stmt = conn.prepare(
+        "SELECT p.name, pa.is_enabled FROM preset_assets pa JOIN presets p ON pa.preset_id = p.id WHERE pa.asset_id = ?1 ORDER BY p.name"
+    )?;
+    sidecar.presets = stmt.query_map(params![asset_id], |row| Ok(SidecarPresetEntry { name: row.get(0)?, is_enabled: row.get::<_, i64>(1)? == 1 }))?
+        .collect::<Result<Vec<_>, _>>()?;
+    let tags = fetch_asset_tag_names(conn, asset_id)?;
+    sidecar.tags = (!tags.is_empty()).then_some(tags);
+
+    let content = toml::to_string_pretty(&sidecar)
+        .map_err(|e| AppError::ModOperation(format!("Failed to serialize sidecar: {}", e)))?;
+    fs::write(folder_path.join(SIDECAR_FILENAME), content)?;
+    Ok(())
+}
+
+fn sidecars_enabled(conn: &Connection) -> bool {
+    matches!(get_setting_value(conn, SETTINGS_KEY_WRITE_SIDECARS), Ok(Some(v)) if v == "true")
+}
+
+// Rewrites the sidecars of the given assets if the setting is on. Failures are only logged.
+fn sync_asset_sidecars(conn: &Connection, asset_ids: &[i64]) {
+    if asset_ids.is_empty() || !sidecars_enabled(conn) { return; }
+    let base_mods_path = match get_setting_value(conn, SETTINGS_KEY_MODS_FOLDER) {
+        Ok(Some(path)) => PathBuf::from(path),
+        _ => return,
+    };
+    for asset_id in asset_ids {
+        if let Err(e) = write_asset_sidecar(conn, &base_mods_path, *asset_id) {
+            eprintln!("[sync_asset_sidecars] Failed to write sidecar for asset {}: {}", asset_id, e);
+        }
+    }
+}
+
+fn preset_member_ids(conn: &Connection, preset_id: i64) -> Vec<i64> {
+    conn.prepare("SELECT asset_id FROM preset_assets WHERE preset_id = ?1")
+        .and_then(|mut stmt| stmt.query_map(params![preset_id], |row| row.get(0))?.collect())
+        .unwrap_or_default()
+}
+
+// Recreates the preset memberships listed in the sidecar of a newly scanned asset.
+// Only presets that already exist (matched by name) are joined; returns the names that don't.
+fn restore_sidecar_presets(conn: &Connection, asset_id: i64, presets: &[SidecarPresetEntry]) -> Result<Vec<String>, AppError> {
+    let mut skipped = Vec::new();
+    for entry in presets {
+        let preset_id: Option<i64> = conn.query_row("SELECT id FROM presets WHERE name = ?1", params![entry.name], |row| row.get(0)).optional()?;
+        let Some(preset_id) = preset_id else {
+            skipped.push(entry.name.clone());
+            continue;
+        };
+        conn.execute(
+            "INSERT OR IGNORE INTO preset_assets (preset_id, asset_id, is_enabled) VALUES (?1, ?2, ?3)",
+            params![preset_id, asset_id, if entry.is_enabled { 1 } else { 0 }],
+        )?;
+    }
+    Ok(skipped)
+}
+
+// Reads the bundled definitions file of a game. Errors are logged and yield empty definitions.
+fn load_game_definitions(app_handle: &AppHandle, game_slug: &str) -> Definitions {
+    let definition_resource_path = format!("definitions/{}.toml", game_slug);
+    println!("Attempting to load definitions from resource: {}", definition_resource_path);
+
+    match app_handle.path_resolver().resolve_resource(&definition_resource_path) {
+        Some(path) => {
+            println!("Found definition file at: {}", path.display());
+            match fs::read_to_string(&path) {
+                Ok(toml_content) => {
+                    match toml::from_str(&toml_content) {
+                        Ok(defs) => {
+                            println!("Successfully parsed definitions for '{}'.", game_slug);
+                            defs
+                        },
+                        Err(e) => {
+                            eprintln!("ERROR: Failed to parse TOML from {}: {}. Using empty definitions.", path.display(), e);
+                            Definitions::default()
+                        }
+                    }
+                },
+                Err(e) => {
+                    eprintln!("ERROR: Failed to read definition file {}: {}. Using empty definitions.", path.display(), e);
+                    Definitions::default()
+                }
+            }
+        },
+        None => {
+            eprintln!("ERROR: Definition file resource '{}' not found. Using empty definitions.", definition_resource_path);
+            Definitions::default()
+        }
+    }
+}
+
+// --- Definition Syncing ---
+fn sync_definitions(conn: &mut Connection, app_handle: &AppHandle, active_game_slug: &str) -> Result<(), AppError> {
+    let definitions = load_game_definitions(app_handle, active_game_slug);
+
+    if definitions.categories.is_empty() {
+        println!("Skipping definition sync as no definitions were loaded for '{}'.", active_game_slug);
+        return Ok(());
+    }
+
+    println!("Loaded {} categories from definitions for '{}'. Starting sync.", definitions.categories.len(), active_game_slug);
+    
+    let tx = conn.transaction()?;
+
+    for (category_slug, category_def) in definitions.categories.iter() {
+        tx.execute("INSERT OR REPLACE INTO categories (name, slug) VALUES (?1, ?2)", params![category_def.name, category_slug])?;
+        let category_id: i64 = tx.query_row("SELECT id FROM categories WHERE slug = ?1", params![category_slug], |row| row.get(0))?;
+
+        let mut existing_slugs: HashSet<String> = {
+            let mut stmt = tx.prepare("SELECT slug FROM entities WHERE category_id = ?1")?;
+            let slug_iter = stmt.query_map(params![category_id], |row| row.get(0))?;
+            let mut slugs = HashSet::new();
+            for slug_result in slug_iter {
+                slugs.insert(slug_result?);
+            }
             slugs
         };
 
@@ -1345,7 +1968,7 @@ fn initialize_database(app_handle: &AppHandle, active_game_slug: &str) -> Result
             "BEGIN;
              CREATE TABLE categories ( id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT UNIQUE NOT NULL, slug TEXT UNIQUE NOT NULL );
              CREATE TABLE entities ( id INTEGER PRIMARY KEY AUTOINCREMENT, category_id INTEGER NOT NULL, name TEXT NOT NULL, slug TEXT UNIQUE NOT NULL, description TEXT, details TEXT, base_image TEXT, FOREIGN KEY (category_id) REFERENCES categories (id) ON DELETE CASCADE );
-             CREATE TABLE assets ( id INTEGER PRIMARY KEY AUTOINCREMENT, entity_id INTEGER NOT NULL, name TEXT NOT NULL, description TEXT, folder_name TEXT NOT NULL UNIQUE, image_filename TEXT, author TEXT, category_tag TEXT, FOREIGN KEY (entity_id) REFERENCES entities (id) ON DELETE CASCADE );
+             CREATE TABLE assets ( id INTEGER PRIMARY KEY AUTOINCREMENT, entity_id INTEGER NOT NULL, name TEXT NOT NULL, description TEXT, folder_name TEXT NOT NULL, image_filename TEXT, author TEXT, category_tag TEXT, FOREIGN KEY (entity_id) REFERENCES entities (id) ON DELETE CASCADE );
              CREATE TABLE settings ( key TEXT PRIMARY KEY NOT NULL, value TEXT NOT NULL );
              CREATE TABLE presets ( id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT UNIQUE NOT NULL, is_favorite INTEGER NOT NULL DEFAULT 0 );
              CREATE TABLE preset_assets ( preset_id INTEGER NOT NULL, asset_id INTEGER NOT NULL, is_enabled INTEGER NOT NULL, PRIMARY KEY (preset_id, asset_id), FOREIGN KEY (preset_id) REFERENCES presets(id) ON DELETE CASCADE, FOREIGN KEY (asset_id) REFERENCES assets(id) ON DELETE CASCADE );
@@ -1368,6 +1991,30 @@ fn initialize_database(app_handle: &AppHandle, active_game_slug: &str) -> Result
         }
     }
 
+    // --- Backup before migrating a database with an older schema ---
+    if !needs_schema_setup {
+        let stored_schema_version = get_schema_version(&conn)?;
+        if stored_schema_version < SCHEMA_VERSION {
+            println!("Schema version {} is older than {}, backing up before migrating.", stored_schema_version, SCHEMA_VERSION);
+            let backups_dir = get_backups_dir(&data_dir, active_game_slug);
+            match backup_connection(&conn, &backups_dir, BACKUP_REASON_MIGRATION) {
+                Ok(_) => prune_backups(&backups_dir),
+                Err(e) => eprintln!("Warning: Pre-migration backup failed: {}", e),
+            }
+        }
+    }
+
+    // --- Schema Migrations (run for new and existing databases) ---
+    run_schema_migrations(&conn)?;
+    activation::set_active_settings(load_activation_settings(&conn)?);
+    activation::set_mod_roots(load_mod_roots(&conn)?);
+    activation::set_materialized_folders(load_materialized_folders(&conn)?);
+    match purge_expired_trash(&conn) {
+        Ok(count) if count > 0 => println!("Purged {} expired trash entries.", count),
+        Ok(_) => {}
+        Err(e) => eprintln!("Warning: Failed to purge expired trash: {}", e),
+    }
+
     // --- Version-based Definition Syncing ---
     let current_app_version = app_handle.package_info().version.to_string();
     let stored_app_version_res = get_setting_value(&conn, SETTINGS_KEY_APP_VERSION);
@@ -1411,6 +2058,174 @@ fn initialize_database(app_handle: &AppHandle, active_game_slug: &str) -> Result
     Ok(conn)
 }
 
+// --- Schema Migrations ---
+// Each step checks whether it was already applied, so this is safe to run on every startup.
+fn run_schema_migrations(conn: &Connection) -> Result<(), AppError> {
+    // Preset mode + scope. Existing presets keep their old behaviour (overlay, unscoped).
+    if !table_has_column(conn, "presets", "mode")? {
+        println!("[Migration] Adding mode/scope columns to presets table.");
+        conn.execute_batch(
+            "BEGIN;
+             ALTER TABLE presets ADD COLUMN mode TEXT NOT NULL DEFAULT 'overlay';
+             ALTER TABLE presets ADD COLUMN scope_entity_id INTEGER;
+             ALTER TABLE presets ADD COLUMN scope_category_id INTEGER;
+             COMMIT;",
+        )?;
+    }
+    // Layered presets
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS preset_layers ( preset_id INTEGER NOT NULL, included_preset_id INTEGER NOT NULL, position INTEGER NOT NULL, PRIMARY KEY (preset_id, included_preset_id), FOREIGN KEY (preset_id) REFERENCES presets(id) ON DELETE CASCADE, FOREIGN KEY (included_preset_id) REFERENCES presets(id) ON DELETE CASCADE )",
+        [],
+    )?;
+    // Persisted mod variables (d3dx_user.ini values) stored with presets
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS preset_asset_variables ( preset_id INTEGER NOT NULL, asset_id INTEGER NOT NULL, ini_file TEXT NOT NULL, variable TEXT NOT NULL, value TEXT NOT NULL, PRIMARY KEY (preset_id, asset_id, ini_file, variable), FOREIGN KEY (preset_id) REFERENCES presets(id) ON DELETE CASCADE, FOREIGN KEY (asset_id) REFERENCES assets(id) ON DELETE CASCADE )",
+        [],
+    )?;
+    // Per-INI enabled states stored with presets
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS preset_asset_inis ( preset_id INTEGER NOT NULL, asset_id INTEGER NOT NULL, ini_file TEXT NOT NULL, is_enabled INTEGER NOT NULL, PRIMARY KEY (preset_id, asset_id, ini_file), FOREIGN KEY (preset_id) REFERENCES presets(id) ON DELETE CASCADE, FOREIGN KEY (asset_id) REFERENCES assets(id) ON DELETE CASCADE )",
+        [],
+    )?;
+    // Extra mod roots (the primary Mods folder stays in settings); assets.root_id NULL = primary folder
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS mod_roots ( id INTEGER PRIMARY KEY AUTOINCREMENT, path TEXT UNIQUE NOT NULL, role TEXT NOT NULL DEFAULT 'active', label TEXT )",
+        [],
+    )?;
+    if !table_has_column(conn, "assets", "root_id")? {
+        println!("[Migration] Adding root_id column to assets.");
+        conn.execute("ALTER TABLE assets ADD COLUMN root_id INTEGER REFERENCES mod_roots(id) ON DELETE SET NULL", [])?;
+    }
+    // Hardlinked/copied trees created by the link mode; only these are ever deleted from the Mods folder
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS materialized_folders ( target_path TEXT PRIMARY KEY, source_path TEXT NOT NULL )",
+        [],
+    )?;
+    // Shuffle history (avoids repeating recent picks)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS shuffle_history ( id INTEGER PRIMARY KEY AUTOINCREMENT, entity_id INTEGER NOT NULL, asset_id INTEGER NOT NULL, picked_at INTEGER NOT NULL, FOREIGN KEY (entity_id) REFERENCES entities(id) ON DELETE CASCADE, FOREIGN KEY (asset_id) REFERENCES assets(id) ON DELETE CASCADE )",
+        [],
+    )?;
+    // Soft deletion: vanished assets are marked instead of dropped, deleted folders go to the trash
+    if !table_has_column(conn, "assets", "missing_since")? {
+        println!("[Migration] Adding missing_since column to assets.");
+        conn.execute("ALTER TABLE assets ADD COLUMN missing_since INTEGER", [])?;
+    }
+    // Asset timestamps (unix seconds). Rows from before this migration have no added_at.
+    for column in ["added_at", "updated_at", "last_enabled_at", "last_seen_on_disk_at"] {
+        if !table_has_column(conn, "assets", column)? {
+            println!("[Migration] Adding {} column to assets.", column);
+            conn.execute(&format!("ALTER TABLE assets ADD COLUMN {} INTEGER", column), [])?;
+        }
+    }
+    // User tags (many-to-many with assets) and tag-based preset selectors
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS tags ( id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL UNIQUE COLLATE NOCASE, color TEXT );
+         CREATE TABLE IF NOT EXISTS asset_tags ( asset_id INTEGER NOT NULL, tag_id INTEGER NOT NULL, PRIMARY KEY (asset_id, tag_id), FOREIGN KEY (asset_id) REFERENCES assets(id) ON DELETE CASCADE, FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE );
+         CREATE INDEX IF NOT EXISTS idx_asset_tags_tag ON asset_tags (tag_id);
+         CREATE TABLE IF NOT EXISTS preset_tag_selectors ( preset_id INTEGER NOT NULL, tag_id INTEGER NOT NULL, is_enabled INTEGER NOT NULL, position INTEGER NOT NULL, PRIMARY KEY (preset_id, tag_id), FOREIGN KEY (preset_id) REFERENCES presets(id) ON DELETE CASCADE, FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE );"
+    )?;
+    // Per-asset favorite flag, 1-5 rating and personal notes
+    if !table_has_column(conn, "assets", "is_favorite")? {
+        println!("[Migration] Adding favorite/rating/notes columns to assets.");
+        conn.execute_batch(
+            "ALTER TABLE assets ADD COLUMN is_favorite INTEGER NOT NULL DEFAULT 0;
+             ALTER TABLE assets ADD COLUMN rating INTEGER;
+             ALTER TABLE assets ADD COLUMN notes TEXT;"
+        )?;
+    }
+    // Full-text search index, rowid = asset id. Kept in sync by triggers so every writer updates it.
+    if !table_has_column(conn, "assets", "ini_sections")? {
+        println!("[Migration] Adding ini_sections column to assets.");
+        conn.execute("ALTER TABLE assets ADD COLUMN ini_sections TEXT", [])?;
+    }
+    // Folder names are unique per mod root, not library-wide (same path in two roots = two mods)
+    let assets_sql: String = conn.query_row("SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'assets'", [], |row| row.get(0))?;
+    if assets_sql.contains("folder_name TEXT NOT NULL UNIQUE") {
+        println!("[Migration] Making asset folder names unique per mod root.");
+        rebuild_assets_table(conn)?;
+    }
+    conn.execute_batch(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_assets_root_folder ON assets (IFNULL(root_id, 0), folder_name);
+         CREATE INDEX IF NOT EXISTS idx_assets_folder_name ON assets (folder_name);"
+    )?;
+    let search_index_exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'assets_fts')", [], |row| row.get(0)
+    )?;
+    conn.execute_batch(&format!(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS assets_fts USING fts5(name, author, description, tags, folder_path, ini_sections, tokenize = 'unicode61 remove_diacritics 2', prefix = '2 3');
+         CREATE VIRTUAL TABLE IF NOT EXISTS assets_fts_vocab USING fts5vocab(assets_fts, 'row');
+         CREATE TRIGGER IF NOT EXISTS assets_fts_insert AFTER INSERT ON assets BEGIN {refresh_new} END;
+         CREATE TRIGGER IF NOT EXISTS assets_fts_update AFTER UPDATE OF name, author, description, folder_name, ini_sections ON assets BEGIN {refresh_new} END;
+         CREATE TRIGGER IF NOT EXISTS assets_fts_delete AFTER DELETE ON assets BEGIN DELETE FROM assets_fts WHERE rowid = old.id; END;
+         CREATE TRIGGER IF NOT EXISTS asset_tags_fts_insert AFTER INSERT ON asset_tags BEGIN {refresh_tagged_new} END;
+         CREATE TRIGGER IF NOT EXISTS asset_tags_fts_delete AFTER DELETE ON asset_tags BEGIN {refresh_tagged_old} END;
+         CREATE TRIGGER IF NOT EXISTS tags_fts_rename AFTER UPDATE OF name ON tags BEGIN {refresh_renamed} END;",
+        refresh_new = search_index_refresh_sql("a.id = new.id"),
+        refresh_tagged_new = search_index_refresh_sql("a.id = new.asset_id"),
+        refresh_tagged_old = search_index_refresh_sql("a.id = old.asset_id"),
+        refresh_renamed = search_index_refresh_sql("a.id IN (SELECT asset_id FROM asset_tags WHERE tag_id = new.id)"),
+    ))?;
+    if !search_index_exists {
+        println!("[Migration] Building search index.");
+        rebuild_search_index_rows(conn)?;
+    }
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS operation_journal ( id INTEGER PRIMARY KEY AUTOINCREMENT, kind TEXT NOT NULL, summary TEXT NOT NULL, operation TEXT NOT NULL, created_at INTEGER NOT NULL, undone INTEGER NOT NULL DEFAULT 0, stale INTEGER NOT NULL DEFAULT 0 )",
+        [],
+    )?;
+    // Entries that can no longer be replayed are kept in the history but skipped by undo/redo
+    if !table_has_column(conn, "operation_journal", "stale")? {
+        println!("[Migration] Adding stale column to operation_journal.");
+        conn.execute("ALTER TABLE operation_journal ADD COLUMN stale INTEGER NOT NULL DEFAULT 0", [])?;
+    }
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS trash ( id INTEGER PRIMARY KEY AUTOINCREMENT, asset_id INTEGER, name TEXT NOT NULL, folder_name TEXT NOT NULL, root_id INTEGER, trash_path TEXT NOT NULL, was_enabled INTEGER NOT NULL DEFAULT 0, deleted_at INTEGER NOT NULL, FOREIGN KEY (asset_id) REFERENCES assets(id) ON DELETE SET NULL )",
+        [],
+    )?;
+    conn.execute_batch(&format!("PRAGMA user_version = {};", SCHEMA_VERSION))?;
+    Ok(())
+}
+
+// Schema version the database was last migrated to, 0 before versions were stored
+fn get_schema_version(conn: &Connection) -> Result<i64, AppError> {
+    Ok(conn.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+}
+
+// Recreates the assets table without the inline UNIQUE(folder_name), which SQLite can't drop in place.
+// The search triggers reference the table, so they are dropped too; run_schema_migrations recreates them.
+fn rebuild_assets_table(conn: &Connection) -> Result<(), AppError> {
+    const COLUMNS: &str = "id, entity_id, name, description, folder_name, image_filename, author, category_tag, root_id, missing_since, added_at, updated_at, last_enabled_at, last_seen_on_disk_at, is_favorite, rating, notes, ini_sections";
+    conn.execute_batch("PRAGMA foreign_keys = OFF;")?;
+    let result = conn.execute_batch(&format!(
+        "BEGIN;
+         DROP TRIGGER IF EXISTS assets_fts_insert;
+         DROP TRIGGER IF EXISTS assets_fts_update;
+         DROP TRIGGER IF EXISTS assets_fts_delete;
+         DROP TRIGGER IF EXISTS asset_tags_fts_insert;
+         DROP TRIGGER IF EXISTS asset_tags_fts_delete;
+         DROP TRIGGER IF EXISTS tags_fts_rename;
+         CREATE TABLE assets_rebuilt ( id INTEGER PRIMARY KEY AUTOINCREMENT, entity_id INTEGER NOT NULL, name TEXT NOT NULL, description TEXT, folder_name TEXT NOT NULL, image_filename TEXT, author TEXT, category_tag TEXT, root_id INTEGER REFERENCES mod_roots(id) ON DELETE SET NULL, missing_since INTEGER, added_at INTEGER, updated_at INTEGER, last_enabled_at INTEGER, last_seen_on_disk_at INTEGER, is_favorite INTEGER NOT NULL DEFAULT 0, rating INTEGER, notes TEXT, ini_sections TEXT, FOREIGN KEY (entity_id) REFERENCES entities (id) ON DELETE CASCADE );
+         INSERT INTO assets_rebuilt ({columns}) SELECT {columns} FROM assets;
+         DROP TABLE assets;
+         ALTER TABLE assets_rebuilt RENAME TO assets;
+         COMMIT;",
+        columns = COLUMNS,
+    ));
+    if result.is_err() {
+        conn.execute_batch("ROLLBACK;").ok();
+    }
+    conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+    Ok(result?)
+}
+
+fn table_has_column(conn: &Connection, table: &str, column: &str) -> Result<bool, AppError> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let column_names = stmt.query_map([], |row| row.get::<_, String>(1))?
+        .collect::<SqlResult<Vec<String>>>()?;
+    Ok(column_names.iter().any(|name| name == column))
+}
+
 // --- Utility Functions ---
 fn get_app_data_dir(app_handle: &AppHandle) -> Result<PathBuf, AppError> { // Internal error type
     app_handle.path_resolver()
@@ -1433,6 +2248,68 @@ fn get_mods_base_path_from_settings(db_state: &DbState) -> Result<PathBuf, AppEr
         .ok_or_else(|| AppError::Config("Mods folder path not set".to_string()))
 }
 
+fn load_mod_roots(conn: &Connection) -> Result<Vec<ModRoot>, AppError> { // Internal error type
+    let mut stmt = conn.prepare("SELECT id, path, role, label FROM mod_roots ORDER BY id")?;
+    let roots = stmt.query_map([], |row| Ok(ModRoot {
+        id: row.get(0)?,
+        path: PathBuf::from(row.get::<_, String>(1)?),
+        role: row.get(2)?,
+        label: row.get(3)?,
+    }))?.collect::<SqlResult<Vec<ModRoot>>>()?;
+    Ok(roots)
+}
+
+fn load_materialized_folders(conn: &Connection) -> Result<Vec<(PathBuf, PathBuf)>, AppError> { // Internal error type
+    let mut stmt = conn.prepare("SELECT target_path, source_path FROM materialized_folders")?;
+    let folders = stmt.query_map([], |row| Ok((PathBuf::from(row.get::<_, String>(0)?), PathBuf::from(row.get::<_, String>(1)?))))?
+        .collect::<SqlResult<Vec<_>>>()?;
+    Ok(folders)
+}
+
+// Writes the link-mode trees created or removed since the last call back to materialized_folders.
+// Failures are only logged; a forgotten tree is refused for deletion rather than removed.
+fn persist_materialized_folders(conn: &Connection) {
+    for change in activation::take_materialized_changes() {
+        let target = change.target.to_string_lossy().to_string();
+        let result = match &change.source {
+            Some(source) => conn.execute(
+                "INSERT OR REPLACE INTO materialized_folders (target_path, source_path) VALUES (?1, ?2)",
+                params![target, source.to_string_lossy().to_string()],
+            ),
+            None => conn.execute("DELETE FROM materialized_folders WHERE target_path = ?1", params![target]),
+        };
+        if let Err(e) = result {
+            eprintln!("[persist_materialized_folders] Failed to record '{}': {}", target, e);
+        }
+    }
+}
+
+// Points the recorded link-mode trees at their new place after the Mods folder moved (Internal error type)
+fn rebase_materialized_folders(conn: &Connection, old_mods_path: &Path, new_mods_path: &Path) -> Result<(), AppError> {
+    persist_materialized_folders(conn);
+    for (target, _) in load_materialized_folders(conn)? {
+        if let Ok(relative) = target.strip_prefix(old_mods_path) {
+            conn.execute(
+                "UPDATE materialized_folders SET target_path = ?1 WHERE target_path = ?2",
+                params![new_mods_path.join(relative).to_string_lossy().to_string(), target.to_string_lossy().to_string()],
+            )?;
+        }
+    }
+    activation::set_materialized_folders(load_materialized_folders(conn)?);
+    Ok(())
+}
+
+fn load_activation_settings(conn: &Connection) -> Result<ActivationSettings, AppError> { // Internal error type
+    Ok(ActivationSettings {
+        mode: get_setting_value(conn, SETTINGS_KEY_ACTIVATION_MODE)?.unwrap_or_else(|| activation::ACTIVATION_MODE_RENAME.to_string()),
+        library_path: get_setting_value(conn, SETTINGS_KEY_MOD_LIBRARY)?.filter(|p| !p.is_empty()).map(PathBuf::from),
+        disabled_marker: match get_setting_value(conn, SETTINGS_KEY_DISABLED_MARKER)? {
+            Some(json) => serde_json::from_str(&json)?,
+            None => DisabledMarker::default(),
+        },
+    })
+}
+
 // Helper to get entity mods path using settings (Internal error type)
 // FIX: Removed unused app_handle parameter
 fn get_entity_mods_path(db_state: &DbState, entity_slug: &str) -> Result<PathBuf, AppError> {
@@ -1548,9 +2425,23 @@ fn launch_executable_elevated(path: String) -> Result<(), String> {
 }
 
 #[command]
-async fn launch_executable(path: String, _app_handle: AppHandle) -> CmdResult<()> { // app_handle might not be needed now
+async fn launch_executable(path: String, db_state: State<'_, DbState>, app_handle: AppHandle) -> CmdResult<()> {
     println!("Attempting to launch (non-elevated) via Command::new: {}", path);
 
+    // Rotate skins before launching if enabled. A failed shuffle shouldn't block the game.
+    let shuffle_options = {
+        let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+        let enabled = get_setting_value(&conn, SETTINGS_KEY_SHUFFLE_ON_LAUNCH).map_err(|e| e.to_string())?
+            .is_some_and(|v| v == "true");
+        if enabled { Some(read_shuffle_options(&conn).map_err(|e| e.to_string())?) } else { None }
+    };
+    if let Some(options) = shuffle_options {
+        match run_shuffle(&db_state, &app_handle, &options) {
+            Ok(picks) => println!("[launch_executable] Shuffled {} entities before launch.", picks.len()),
+            Err(e) => eprintln!("[launch_executable] Shuffle on launch failed: {}", e),
+        }
+    }
+
     // FIX: Use Command::new for launching executables
     let cmd = Command::new(path) // Use the path directly as the command
         // .args([]) // Add arguments if needed later
@@ -1679,7 +2570,7 @@ fn get_entities_by_category(category_slug: String, db_state: State<DbState>) ->
      // Fetch full entity details - ORDER BY to put 'Other' first
      let mut stmt = conn.prepare(
         "SELECT e.id, e.category_id, e.name, e.slug, e.description, e.details, e.base_image, COUNT(a.id) as mod_count
-         FROM entities e LEFT JOIN assets a ON e.id = a.entity_id
+         FROM entities e LEFT JOIN assets a ON e.id = a.entity_id AND a.missing_since IS NULL
          WHERE e.category_id = ?1
          GROUP BY e.id
          ORDER BY
@@ -1712,7 +2603,7 @@ fn get_entity_details(entity_slug: String, db_state: State<DbState>) -> CmdResul
         
         let mut stmt = conn.prepare(
             "SELECT e.id, e.category_id, e.name, e.slug, e.description, e.details, e.base_image, COUNT(a.id) as mod_count
-             FROM entities e LEFT JOIN assets a ON e.id = a.entity_id
+             FROM entities e LEFT JOIN assets a ON e.id = a.entity_id AND a.missing_since IS NULL
              WHERE e.slug = ?1 GROUP BY e.id"
         ).map_err(|e| format!("[get_entity_details] DB prepare error: {}", e))?;
         
@@ -1740,23 +2631,23 @@ fn get_entity_details(entity_slug: String, db_state: State<DbState>) -> CmdResul
     // Create a mutable copy we'll update with the additional counts
     let mut entity = entity_info;
     
-    // PART 2: Get folder paths from DB with a separate brief lock
-    let asset_folder_paths: Vec<String> = {
+    // PART 2: Get folder paths and roots from DB with a separate brief lock
+    let asset_folder_paths: Vec<(String, Option<i64>)> = {
         let conn_guard = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
         let conn = &*conn_guard;
         
         // Prepare statement and collect all folder paths while holding lock
-        let mut stmt = conn.prepare("SELECT folder_name FROM assets WHERE entity_id = ?1")
+        let mut stmt = conn.prepare("SELECT folder_name, root_id FROM assets WHERE entity_id = ?1 AND missing_since IS NULL")
             .map_err(|e| format!("[get_entity_details] Error preparing folder query: {}", e))?;
             
-        let folder_iter = stmt.query_map(params![entity.id], |row| row.get::<_, String>(0))
+        let folder_iter = stmt.query_map(params![entity.id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<i64>>(1)?)))
             .map_err(|e| format!("[get_entity_details] Error executing folder query: {}", e))?;
             
         // Collect all paths into a Vec to release the lock sooner
         let mut paths = Vec::new();
         for result in folder_iter {
             match result {
-                Ok(path) => paths.push(path.replace("\\", "/")),
+                Ok((path, root_id)) => paths.push((path.replace("\\", "/"), root_id)),
                 Err(e) => println!("[get_entity_details] Warning: Error fetching path: {}", e),
             }
         }
@@ -1778,19 +2669,9 @@ fn get_entity_details(entity_slug: String, db_state: State<DbState>) -> CmdResul
     
     // PART 4: Count enabled mods by checking disk paths (NO DB LOCK NEEDED)
     let mut enabled_count = 0;
-    for clean_relative_path_str in &asset_folder_paths {
-        let clean_relative_path = PathBuf::from(clean_relative_path_str);
-        let filename_osstr = match clean_relative_path.file_name() {
-            Some(name) => name,
-            None => continue, // Skip if we can't get filename
-        };
-        
-        let filename_str = filename_osstr.to_string_lossy();
-        if filename_str.is_empty() { continue; }
-        
-        // Check only enabled state path
-        let full_path_if_enabled = base_mods_path.join(&clean_relative_path);
-        if full_path_if_enabled.is_dir() {
+    for (clean_relative_path_str, root_id) in &asset_folder_paths {
+        // Enabled state as the mod's root and activation mode see it
+        if let Some((_, true)) = locate_asset_folder(&base_mods_path, *root_id, clean_relative_path_str) {
             enabled_count += 1;
         }
     }
@@ -1801,13 +2682,11 @@ fn get_entity_details(entity_slug: String, db_state: State<DbState>) -> CmdResul
         let conn_guard = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
         let conn = &*conn_guard;
         
-        // Count recent mods (approximation using ID sorting, assuming higher IDs are more recent)
+        // Count mods added in the last DEFAULT_RECENT_DAYS days
         if entity.mod_count > 0 {
             match conn.query_row(
-                "SELECT COUNT(*) FROM assets 
-                 WHERE entity_id = ?1 
-                 AND id > (SELECT MAX(id) - (COUNT(*) / 4) FROM assets WHERE entity_id = ?1)",
-                params![entity.id],
+                "SELECT COUNT(*) FROM assets WHERE entity_id = ?1 AND missing_since IS NULL AND added_at >= ?2",
+                params![entity.id, recent_cutoff(DEFAULT_RECENT_DAYS)],
                 |row| row.get::<_, i32>(0),
             ) {
                 Ok(count) => {
@@ -1822,12 +2701,9 @@ fn get_entity_details(entity_slug: String, db_state: State<DbState>) -> CmdResul
             entity.recent_mod_count = Some(0);
         }
         
-        // Count mods in favorite presets
+        // Count favorite mods
         match conn.query_row(
-            "SELECT COUNT(DISTINCT a.id) FROM assets a
-             JOIN preset_assets pa ON a.id = pa.asset_id
-             JOIN presets p ON pa.preset_id = p.id
-             WHERE a.entity_id = ?1 AND p.is_favorite = 1",
+            "SELECT COUNT(*) FROM assets WHERE entity_id = ?1 AND missing_since IS NULL AND is_favorite = 1",
             params![entity.id],
             |row| row.get::<_, i32>(0),
         ) {
@@ -1845,8 +2721,75 @@ fn get_entity_details(entity_slug: String, db_state: State<DbState>) -> CmdResul
     Ok(entity)
 }
 
+// --- Asset Helpers ---
+const ASSET_COLUMNS: &str = "id, entity_id, name, description, folder_name, image_filename, author, category_tag, added_at, updated_at, last_enabled_at, last_seen_on_disk_at, is_favorite, rating, notes,
+    (SELECT GROUP_CONCAT(tg.name, char(31)) FROM asset_tags ast JOIN tags tg ON tg.id = ast.tag_id WHERE ast.asset_id = assets.id), root_id";
+
+fn asset_from_row(row: &rusqlite::Row) -> SqlResult<Asset> {
+    Ok(Asset {
+        id: row.get(0)?,
+        entity_id: row.get(1)?,
+        name: row.get(2)?,
+        description: row.get(3)?,
+        // CLEAN relative path from DB, see resolve_asset_on_disk
+        folder_name: row.get::<_, String>(4)?.replace("\\", "/"),
+        image_filename: row.get(5)?,
+        author: row.get(6)?,
+        category_tag: row.get(7)?,
+        is_enabled: false, // Determined from disk
+        added_at: row.get(8)?,
+        updated_at: row.get(9)?,
+        last_enabled_at: row.get(10)?,
+        last_seen_on_disk_at: row.get(11)?,
+        is_favorite: row.get::<_, i64>(12)? == 1,
+        rating: row.get(13)?,
+        notes: row.get(14)?,
+        tags: split_tag_concat(row.get(15)?),
+        root_id: row.get(16)?,
+        clean_folder_name: String::new(), // Filled by resolve_asset_on_disk
+    })
+}
+
+// ORDER BY for get_assets_for_entity. Unset values sort last.
+fn asset_sort_clause(sort_by: Option<&str>) -> CmdResult<&'static str> {
+    match sort_by.unwrap_or("name") {
+        "name" => Ok("name"),
+        "favorite" => Ok("is_favorite DESC, name"),
+        "rating" => Ok("rating IS NULL, rating DESC, name"),
+        "added" => Ok("added_at IS NULL, added_at DESC, name"),
+        "updated" => Ok("updated_at IS NULL, updated_at DESC, name"),
+        "last_enabled" => Ok("last_enabled_at IS NULL, last_enabled_at DESC, name"),
+        other => Err(format!("Unknown sort '{}'.", other)),
+    }
+}
+
+fn validate_rating(rating: Option<i64>) -> CmdResult<()> {
+    match rating {
+        Some(r) if !(1..=5).contains(&r) => Err(format!("Rating must be between 1 and 5, got {}.", r)),
+        _ => Ok(()),
+    }
+}
+
+// Fills in the enabled state and swaps folder_name for the path actually found on disk.
+// None if the mod folder doesn't exist in either state.
+fn resolve_asset_on_disk(base_mods_path: &Path, mut asset: Asset) -> Option<Asset> {
+    let (found_path, is_enabled) = locate_asset_folder(base_mods_path, asset.root_id, &asset.folder_name)?;
+    asset.is_enabled = is_enabled;
+    asset.clean_folder_name = asset.folder_name.clone(); // Display path, without any disabled marker
+    asset.folder_name = asset_disk_relative_path(base_mods_path, &found_path, &asset.folder_name);
+    Some(asset)
+}
+
 #[command]
-fn get_assets_for_entity(entity_slug: String, db_state: State<DbState>, _app_handle: AppHandle) -> CmdResult<Vec<Asset>> {
+fn get_assets_for_entity(
+    entity_slug: String,
+    sort_by: Option<String>,
+    favorites_only: Option<bool>,
+    min_rating: Option<i64>,
+    db_state: State<DbState>,
+    _app_handle: AppHandle
+) -> CmdResult<Vec<Asset>> {
+    let order_by = asset_sort_clause(sort_by.as_deref())?;
     let base_mods_path = get_mods_base_path_from_settings(&db_state)
                              .map_err(|e| format!("[get_assets_for_entity {}] Error getting base mods path: {}", entity_slug, e))?;
 
@@ -1864,27 +2807,13 @@ fn get_assets_for_entity(entity_slug: String, db_state: State<DbState>, _app_han
     })?;
 
     // --- Prepare Statement ---
-    let mut stmt = conn.prepare(
-        "SELECT id, entity_id, name, description, folder_name, image_filename, author, category_tag
-         FROM assets WHERE entity_id = ?1 ORDER BY name"
-    ).map_err(|e| format!("[get_assets_for_entity {}] DB Error preparing asset statement: {}", entity_slug, e))?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM assets WHERE entity_id = ?1 AND missing_since IS NULL
+         AND (?2 = 0 OR is_favorite = 1) AND (?3 IS NULL OR rating >= ?3) ORDER BY {}", ASSET_COLUMNS, order_by
+    )).map_err(|e| format!("[get_assets_for_entity {}] DB Error preparing asset statement: {}", entity_slug, e))?;
 
     // --- Query Rows ---
-    let asset_rows_result = stmt.query_map(params![entity_id], |row| {
-        let folder_name_raw: String = row.get(4)?;
-        Ok(Asset {
-            id: row.get(0)?,
-            entity_id: row.get(1)?,
-            name: row.get(2)?,
-            description: row.get(3)?,
-            // Store the CLEAN relative path from DB directly for now
-            folder_name: folder_name_raw.replace("\\", "/"),
-            image_filename: row.get(5)?,
-            author: row.get(6)?,
-            category_tag: row.get(7)?,
-            is_enabled: false, // Default, will be determined below
-        })
-    });
+    let asset_rows_result = stmt.query_map(params![entity_id, favorites_only.unwrap_or(false) as i64, min_rating], asset_from_row);
 
     let mut assets_to_return = Vec::new();
 
@@ -1892,49 +2821,11 @@ fn get_assets_for_entity(entity_slug: String, db_state: State<DbState>, _app_han
         Ok(asset_iter) => {
              for (index, asset_result) in asset_iter.enumerate() {
                  match asset_result {
-                     Ok(mut asset_from_db) => {
-                         // --- Corrected State Detection Logic ---
-                         // `asset_from_db.folder_name` currently holds the CLEAN relative path from DB
-                         let clean_relative_path_from_db = PathBuf::from(&asset_from_db.folder_name);
-
-                         // Construct potential paths based on the CLEAN relative path
-                         let filename_osstr = clean_relative_path_from_db.file_name().unwrap_or_default();
-                         let filename_str = filename_osstr.to_string_lossy();
-                         if filename_str.is_empty() {
-                             continue;
-                         }
-                         let disabled_filename = format!("{}{}", DISABLED_PREFIX, filename_str);
-                         let relative_parent_path = clean_relative_path_from_db.parent();
-
-                         // Path if enabled = base / clean_relative_path
-                         let full_path_if_enabled = base_mods_path.join(&clean_relative_path_from_db);
-
-                         // Path if disabled = base / relative_parent / disabled_filename
-                         let full_path_if_disabled = match relative_parent_path {
-                            Some(parent) if parent.as_os_str().len() > 0 => base_mods_path.join(parent).join(&disabled_filename),
-                            _ => base_mods_path.join(&disabled_filename), // No parent or parent is root
-                         };
-
-                         // Determine state based on which path exists
-                         if full_path_if_enabled.is_dir() {
-                             asset_from_db.is_enabled = true;
-                             // Set folder_name to the actual path found on disk
-                             asset_from_db.folder_name = clean_relative_path_from_db.to_string_lossy().replace("\\", "/");
-                         } else if full_path_if_disabled.is_dir() {
-                             asset_from_db.is_enabled = false;
-                             // Set folder_name to the actual path found on disk (the disabled one)
-                              let disabled_relative_path = match relative_parent_path {
-                                 Some(parent) if parent.as_os_str().len() > 0 => parent.join(&disabled_filename),
-                                 _ => PathBuf::from(&disabled_filename),
-                              };
-                             asset_from_db.folder_name = disabled_relative_path.to_string_lossy().replace("\\", "/");
-                         } else {
-                             // Mod folder doesn't exist in either state
-                             continue; // Skip this asset
+                     Ok(asset_from_db) => {
+                         // Skipped when the mod folder doesn't exist in either state
+                         if let Some(asset) = resolve_asset_on_disk(&base_mods_path, asset_from_db) {
+                             assets_to_return.push(asset);
                          }
-
-                         assets_to_return.push(asset_from_db);
-                         // --- End Corrected State Detection ---
                      }
                      Err(e) => {
                          eprintln!("[get_assets_for_entity {}] Error processing asset row index {}: {}", entity_slug, index, e);
@@ -1961,12 +2852,12 @@ fn toggle_asset_enabled(entity_slug: String, asset: Asset, db_state: State<DbSta
     let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
 
     // Fetch the CLEAN STORED relative path from DB using asset ID
-    let clean_relative_path_from_db_str = {
+    let (clean_relative_path_from_db_str, root_id) = {
          let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
-         conn.query_row::<String, _, _>(
-            "SELECT folder_name FROM assets WHERE id = ?1", // Expecting clean path here
+         conn.query_row::<(String, Option<i64>), _, _>(
+            "SELECT folder_name, root_id FROM assets WHERE id = ?1", // Expecting clean path here
             params![asset.id],
-            |row| row.get(0),
+            |row| Ok((row.get(0)?, row.get(1)?)),
          ).map_err(|e| format!("Failed to get relative path from DB for asset ID {}: {}", asset.id, e))?
     };
      // Ensure forward slashes for PathBuf consistency
@@ -1975,61 +2866,32 @@ fn toggle_asset_enabled(entity_slug: String, asset: Asset, db_state: State<DbSta
      println!("[toggle_asset_enabled] Clean relative path from DB: '{}'", clean_relative_path_from_db.display());
 
 
-    // --- FIX: Construct potential paths correctly ---
-    let filename_osstr = clean_relative_path_from_db.file_name().ok_or_else(|| format!("Could not extract filename from DB path: {}", clean_relative_path_from_db.display()))?;
-    let filename_str = filename_osstr.to_string_lossy();
-    if filename_str.is_empty() {
-        return Err(format!("Filename extracted from DB path is empty: {}", clean_relative_path_from_db.display()));
+    // Determine the CURRENT state based on the *actual* state on disk
+    let located = activation::backend_for_asset(&base_mods_path, root_id)
+        .and_then(|backend| backend.locate(&clean_relative_path_from_db_str).map(|found| (backend, found)));
+    let (backend, (current_full_path, current_is_enabled)) = located.ok_or_else(|| {
+        println!("[toggle_asset_enabled] Error: Mod folder not found on disk based on DB relative path!");
+        format!(
+            "Cannot toggle mod '{}': Folder not found for DB path '{}' in any mods folder. Did the folder get moved or deleted?",
+            asset.name, // Use the display name from the asset object
+            clean_relative_path_from_db.display() // Show the clean path we checked against
+        )
+    })?;
+    let new_enabled_state = !current_is_enabled;
+    println!("[toggle_asset_enabled] Detected state on disk: {} (found {})", if current_is_enabled { "ENABLED" } else { "DISABLED" }, current_full_path.display());
+
+    backend.set_enabled(&clean_relative_path_from_db_str, new_enabled_state)
+        .map_err(|e| format!("Failed to toggle '{}': {}", current_full_path.display(), e))?;
+
+    println!("[toggle_asset_enabled] Toggled successfully. New logical state should be: {}", new_enabled_state);
+    if let Ok(conn) = db_state.0.lock() {
+        let changes = vec![AssetStateChange { asset_id: asset.id, is_enabled: new_enabled_state }];
+        record_enabled_at(&conn, &changes);
+        journal_operation(&conn, &format!("{} '{}'", if new_enabled_state { "Enabled" } else { "Disabled" }, asset.name),
+            &JournalOp::SetEnabled { assets: changes, inis: Vec::new() });
     }
-    let disabled_filename = format!("{}{}", DISABLED_PREFIX, filename_str);
-    let relative_parent_path = clean_relative_path_from_db.parent();
-
-    // Full path if enabled = base / clean_relative_path
-    let full_path_if_enabled = base_mods_path.join(&clean_relative_path_from_db);
-
-    // Full path if disabled = base / relative_parent / disabled_filename
-    let full_path_if_disabled = match relative_parent_path {
-       Some(parent) if parent.as_os_str().len() > 0 => base_mods_path.join(parent).join(&disabled_filename),
-       _ => base_mods_path.join(&disabled_filename), // No parent or parent is root
-    };
-
-    println!("[toggle_asset_enabled] Constructed enabled path check: {}", full_path_if_enabled.display());
-    println!("[toggle_asset_enabled] Constructed disabled path check: {}", full_path_if_disabled.display());
 
-
-    // Determine the CURRENT full path and the TARGET full path based on the *actual* state on disk
-    let (current_full_path, target_full_path, new_enabled_state) =
-        if full_path_if_enabled.is_dir() { // Check if the ENABLED path exists
-            // It's currently enabled on disk, target is the disabled path
-             println!("[toggle_asset_enabled] Detected state on disk: ENABLED (found {})", full_path_if_enabled.display());
-            (full_path_if_enabled, full_path_if_disabled, false) // New state will be disabled
-        } else if full_path_if_disabled.is_dir() { // Check if the DISABLED path exists
-            // It's currently disabled on disk, target is the enabled path
-             println!("[toggle_asset_enabled] Detected state on disk: DISABLED (found {})", full_path_if_disabled.display());
-            (full_path_if_disabled, full_path_if_enabled, true) // New state will be enabled
-        } else {
-            // Neither exists, something is wrong. Error based on DB path.
-             println!("[toggle_asset_enabled] Error: Mod folder not found on disk based on DB relative path!");
-            // Use the better error message from before
-             return Err(format!(
-                "Cannot toggle mod '{}': Folder not found at expected locations derived from DB path '{}' (Checked {} and {}). Did the folder get moved or deleted?",
-                asset.name, // Use the display name from the asset object
-                clean_relative_path_from_db.display(), // Show the clean path we checked against
-                full_path_if_enabled.display(),
-                full_path_if_disabled.display()
-            ));
-        };
-
-    println!("[toggle_asset_enabled] Current actual path: {}", current_full_path.display());
-    println!("[toggle_asset_enabled] Target path for rename: {}", target_full_path.display());
-
-    // Perform the rename
-    fs::rename(&current_full_path, &target_full_path)
-        .map_err(|e| format!("Failed to rename '{}' to '{}': {}", current_full_path.display(), target_full_path.display(), e))?;
-
-    println!("[toggle_asset_enabled] Renamed successfully. New logical state should be: {}", new_enabled_state);
-
-    // Return the actual NEW state after the rename
+    // Return the actual NEW state after the toggle
     Ok(new_enabled_state)
 }
 
@@ -2042,6 +2904,7 @@ fn get_asset_image_path(
     // --- Data needed from DB ---
     let base_mods_path_str: String;
     let clean_relative_path_str: String;
+    let root_id: Option<i64>;
     let image_filename: String;
 
     // --- Acquire lock *only* for DB reads ---
@@ -2056,16 +2919,17 @@ fn get_asset_image_path(
             .ok_or_else(|| format!("[get_asset_image_path ID: {}] Mods folder path not set", asset_id))?;
 
         // 2. Fetch asset info (clean path and image filename) using asset_id
-        let (fetched_path, fetched_image_opt): (String, Option<String>) = conn.query_row(
-            "SELECT folder_name, image_filename FROM assets WHERE id = ?1",
+        let (fetched_path, fetched_image_opt, fetched_root_id): (String, Option<String>, Option<i64>) = conn.query_row(
+            "SELECT folder_name, image_filename, root_id FROM assets WHERE id = ?1",
             params![asset_id],
-            |row| Ok((row.get(0)?, row.get(1)?))
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))
         ).map_err(|e| match e {
             rusqlite::Error::QueryReturnedNoRows => format!("[get_asset_image_path ID: {}] Asset not found.", asset_id),
             _ => format!("[get_asset_image_path ID: {}] DB Error getting asset info: {}", asset_id, e),
         })?;
 
         clean_relative_path_str = fetched_path.replace("\\", "/"); // Normalize path separators immediately
+        root_id = fetched_root_id;
         image_filename = match fetched_image_opt {
              Some(name) if !name.is_empty() => name,
              _ => {
@@ -2085,30 +2949,17 @@ fn get_asset_image_path(
     let clean_relative_path_buf = PathBuf::from(&clean_relative_path_str); // Already normalized
 
     // 3. Determine current folder path (enabled or disabled)
-    let mod_folder_filename_osstr = clean_relative_path_buf.file_name()
-        .ok_or_else(|| format!("[get_asset_image_path ID: {}] Cannot get folder filename from '{}'", asset_id, clean_relative_path_str))?;
-    let mod_folder_filename_str = mod_folder_filename_osstr.to_string_lossy();
-    let disabled_mod_folder_filename = format!("{}{}", DISABLED_PREFIX, mod_folder_filename_str);
-    let relative_parent_path = clean_relative_path_buf.parent();
-
-    let full_path_if_enabled = base_mods_path.join(&clean_relative_path_buf);
-    let full_path_if_disabled = match relative_parent_path {
-        Some(parent) if parent.as_os_str().len() > 0 => base_mods_path.join(parent).join(&disabled_mod_folder_filename),
-        _ => base_mods_path.join(&disabled_mod_folder_filename),
-    };
-
-    // Use is_dir which might be slightly faster than exists() if we only care about directories
-    let current_mod_folder_path = if full_path_if_enabled.is_dir() {
-        println!("[get_asset_image_path ID: {}] Found enabled path: {}", asset_id, full_path_if_enabled.display());
-        full_path_if_enabled
-    } else if full_path_if_disabled.is_dir() {
-        println!("[get_asset_image_path ID: {}] Found disabled path: {}", asset_id, full_path_if_disabled.display());
-        full_path_if_disabled
-    } else {
-        // Folder not found. This isn't necessarily an error for *this* function,
-        // but we can't construct the image path. Return an error.
-        println!("[get_asset_image_path ID: {}] Mod folder not found on disk.", asset_id);
-        return Err(format!("Mod folder for asset ID {} not found on disk (Checked '{}' and '{}').", asset_id, full_path_if_enabled.display(), full_path_if_disabled.display()));
+    let current_mod_folder_path = match locate_asset_folder(&base_mods_path, root_id, &clean_relative_path_str) {
+        Some((path, is_enabled)) => {
+            println!("[get_asset_image_path ID: {}] Found {} path: {}", asset_id, if is_enabled { "enabled" } else { "disabled" }, path.display());
+            path
+        }
+        None => {
+            // Folder not found. This isn't necessarily an error for *this* function,
+            // but we can't construct the image path. Return an error.
+            println!("[get_asset_image_path ID: {}] Mod folder not found on disk.", asset_id);
+            return Err(format!("Mod folder for asset ID {} not found on disk (path '{}').", asset_id, clean_relative_path_buf.display()));
+        }
     };
 
     // 4. Construct the FULL path to the image file within the found folder
@@ -2172,6 +3023,8 @@ fn open_mods_folder(_app_handle: AppHandle, db_state: State<DbState>) -> CmdResu
 async fn scan_mods_directory(db_state: State<'_, DbState>, app_handle: AppHandle) -> CmdResult<()> {
     println!("Starting robust mod directory scan with pruning...");
     let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+    // The link activation mode keeps mod sources in the managed library, so that is what gets scanned
+    let base_mods_path = activation::backend_for(&base_mods_path).scan_root().to_path_buf();
     println!("Scanning base path: {}", base_mods_path.display());
 
     if !base_mods_path.is_dir() {
@@ -2193,21 +3046,33 @@ async fn scan_mods_directory(db_state: State<'_, DbState>, app_handle: AppHandle
         data_dir.join(DB_NAME)
     };
     let db_path_str = db_path.to_string_lossy().to_string();
-    let base_mods_path_clone = base_mods_path.clone();
     let app_handle_clone = app_handle.clone();
     let maps_clone = deduction_maps.clone();
+    // Disabled marker of the rename mode (library folders in the link mode always use clean names)
+    let activation_settings = activation::active_settings();
+    let scan_marker = (activation_settings.mode == activation::ACTIVATION_MODE_RENAME).then(|| activation_settings.disabled_marker.clone());
+
+    // --- Roots to scan: (root id, path, disabled marker). Primary folder first, then reachable extra roots ---
+    let mut scan_roots: Vec<(Option<i64>, PathBuf, Option<DisabledMarker>)> = vec![(None, base_mods_path.clone(), scan_marker)];
+    let mut unavailable_root_ids = HashSet::<i64>::new();
+    for root in activation::mod_roots() {
+        if root.path.is_dir() {
+            let root_marker = (root.role == activation::ROOT_ROLE_ACTIVE).then(|| activation_settings.disabled_marker.clone());
+            scan_roots.push((Some(root.id), root.path, root_marker));
+        } else {
+            // e.g. an unplugged drive: keep its mods instead of pruning them
+            println!("[Scan Prep] Mod root '{}' is not reachable. Its mods won't be pruned.", root.path.display());
+            unavailable_root_ids.insert(root.id);
+        }
+    }
 
     println!("[Scan Prep] Calculating total potential mod folders...");
-    let potential_mod_folders_for_count: Vec<PathBuf> = WalkDir::new(&base_mods_path)
-        .min_depth(1)
-        .into_iter()
+    let potential_mod_folders_for_count: Vec<PathBuf> = scan_roots.iter()
+        .flat_map(|(_, root_path, _)| WalkDir::new(root_path).min_depth(1).into_iter())
         .filter_map(|e| e.ok().filter(|entry| entry.file_type().is_dir()))
         .filter(|e| {
-             // Temporary check for rename condition as well for count (might be slightly inaccurate if rename fails later)
-             let path = e.path();
-             let filename = path.file_name().unwrap_or_default().to_string_lossy();
-             // Check for INI OR if it needs renaming (so it's counted)
-             has_ini_file(&path.to_path_buf()) || (filename.starts_with("DISABLED") && !filename.starts_with(DISABLED_PREFIX))
+             // Folders needing a disabled-marker conversion are mod folders too, so this covers them
+             has_ini_file(&e.path().to_path_buf())
          })
         .map(|e| e.path().to_path_buf())
         .collect();
@@ -2227,13 +3092,14 @@ async fn scan_mods_directory(db_state: State<'_, DbState>, app_handle: AppHandle
         // --- Fetch ALL asset IDs and their CLEAN relative paths from DB first ---
         let mut initial_db_assets = HashMap::<i64, String>::new(); // asset_id -> clean_relative_path
         { // Scope for the statement
-            let mut stmt = conn.prepare("SELECT id, folder_name FROM assets")
+            let mut stmt = conn.prepare("SELECT id, folder_name, root_id FROM assets")
                 .map_err(|e| format!("Failed to prepare asset fetch statement: {}", e))?;
-            let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)));
+            let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<i64>>(2)?)));
              let row_iter = rows.map_err(|e| format!("Error creating asset query iterator: {}", e))?;
             for row_result in row_iter {
                  match row_result {
-                     Ok((id, folder_name)) => {
+                     Ok((_, _, Some(root_id))) if unavailable_root_ids.contains(&root_id) => {} // Never pruned
+                     Ok((id, folder_name, _)) => {
                          initial_db_assets.insert(id, folder_name.replace("\\", "/"));
                      }
                      Err(e) => {
@@ -2251,30 +3117,33 @@ async fn scan_mods_directory(db_state: State<'_, DbState>, app_handle: AppHandle
         let mut processed_mod_paths = HashSet::new(); // Track processed paths to avoid duplicates if structure is odd
         let mut found_asset_ids = HashSet::<i64>::new(); // Track IDs found on disk
         let mut renamed_count = 0; // Count renamed folders
+        let scan_time = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+        let index_ini_sections = search_ini_sections_enabled(&conn);
 
-        // --- Iterate using WalkDir ---
-        let mut walker = WalkDir::new(&base_mods_path_clone).min_depth(1).into_iter();
-
-        while let Some(entry_result) = walker.next() {
-            match entry_result {
-                Ok(entry) => {
-                    // Use mutable path as it might be changed by rename logic
-                    let mut current_path = entry.path().to_path_buf();
-                    let is_directory = entry.file_type().is_dir(); // Check type once
-
-                    if is_directory && !processed_mod_paths.contains(&current_path) {
-                        // --- START: Check for DISABLED without underscore and rename ---
-                        let filename_osstr = current_path.file_name().unwrap_or_default();
-                        let filename_str = filename_osstr.to_string_lossy();
-
-                        let needs_rename = filename_str.starts_with("DISABLED") && !filename_str.starts_with(DISABLED_PREFIX);
-                        let mut current_path_for_processing = current_path.clone(); // Path to use for has_ini and processing
+        // --- Iterate every root using WalkDir ---
+        for (root_id, base_mods_path_clone, scan_marker) in &scan_roots {
+            println!("[Scan Task] Scanning root: {}", base_mods_path_clone.display());
+            let mut walker = WalkDir::new(base_mods_path_clone).min_depth(1).into_iter();
 
-                        if needs_rename {
-                            let new_filename = format!("{}{}", DISABLED_PREFIX, filename_str.strip_prefix("DISABLED").unwrap_or(&filename_str));
-                            if let Some(parent_path) = current_path.parent() {
-                                let new_path = parent_path.join(&new_filename);
-                                println!("[Scan Task - Rename] Found incorrect prefix: '{}'. Renaming to '{}'", current_path.display(), new_path.display());
+            while let Some(entry_result) = walker.next() {
+                match entry_result {
+                    Ok(entry) => {
+                        // Use mutable path as it might be changed by rename logic
+                        let mut current_path = entry.path().to_path_buf();
+                        let is_directory = entry.file_type().is_dir(); // Check type once
+
+                        if is_directory && !processed_mod_paths.contains(&current_path) {
+                            // --- START: Convert other disabled spellings to the configured marker ---
+                            let mut current_path_for_processing = current_path.clone(); // Path to use for has_ini and processing
+                            let normalized_relative_path = match (scan_marker, current_path.strip_prefix(base_mods_path_clone)) {
+                                (Some(marker), Ok(relative)) if has_ini_file(&current_path) => marker.normalized_relative_path(relative),
+                                _ => None,
+                            };
+
+                            if let Some(new_relative_path) = normalized_relative_path {
+                                let new_path = base_mods_path_clone.join(&new_relative_path);
+                                let filename_str = current_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                                println!("[Scan Task - Rename] Found other disabled spelling: '{}'. Renaming to '{}'", current_path.display(), new_path.display());
 
                                 // Emit progress before rename attempt
                                 app_handle_clone.emit_all(SCAN_PROGRESS_EVENT, ScanProgress {
@@ -2284,7 +3153,14 @@ async fn scan_mods_directory(db_state: State<'_, DbState>, app_handle: AppHandle
                                      message: format!("Renaming: {}", filename_str)
                                 }).unwrap_or_else(|e| eprintln!("Failed to emit rename progress: {}", e));
 
-                                match fs::rename(&current_path, &new_path) {
+                                let rename_result = if new_path.exists() {
+                                    Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("'{}' already exists", new_path.display())))
+                                } else {
+                                    new_path.parent().map_or(Ok(()), fs::create_dir_all).and_then(|_| fs::rename(&current_path, &new_path))
+                                };
+                                // The old path is gone (or unusable), don't let WalkDir descend into it
+                                walker.skip_current_dir();
+                                match rename_result {
                                     Ok(_) => {
                                         println!("[Scan Task - Rename] Successfully renamed.");
                                         current_path_for_processing = new_path; // Use the NEW path for further processing
@@ -2293,141 +3169,187 @@ async fn scan_mods_directory(db_state: State<'_, DbState>, app_handle: AppHandle
                                     Err(e) => {
                                         eprintln!("[Scan Task - Rename] ERROR: Failed to rename folder '{}': {}. Skipping folder.", current_path.display(), e);
                                         errors_count += 1;
-                                        // Don't process this folder if rename failed
-                                        walker.skip_current_dir(); // Skip children as well
                                         continue; // Move to the next entry in WalkDir
                                     }
                                 }
-                            } else {
-                                eprintln!("[Scan Task - Rename] ERROR: Cannot get parent path for '{}'. Skipping rename and folder.", current_path.display());
-                                errors_count += 1;
-                                walker.skip_current_dir(); // Skip children
-                                continue; // Move to the next entry
                             }
-                        }
-                        // --- END: Rename Check ---
-
-                        // Now check if the (potentially renamed) folder has an INI file
-                        if has_ini_file(&current_path_for_processing) {
-                            // This is a mod folder (or was successfully renamed to be treated as one)
-                            processed_count += 1; // Increment processed count *here*
-                            processed_mod_paths.insert(current_path_for_processing.clone()); // Add the path we actually processed
-                            let path_display = current_path_for_processing.display().to_string();
-                            let folder_name_only = current_path_for_processing.file_name().unwrap_or_default().to_string_lossy();
-
-                            // Emit progress for actual mod processing
-                            app_handle_clone.emit_all(SCAN_PROGRESS_EVENT, ScanProgress {
-                                processed: processed_count,
-                                total: total_to_process,
-                                current_path: Some(path_display.clone()),
-                                message: format!("Processing: {}", folder_name_only)
-                            }).unwrap_or_else(|e| eprintln!("Failed to emit scan progress: {}", e));
-
-                            // --- Start Original Deduction/DB Logic (using current_path_for_processing) ---
-                            match deduce_mod_info_v2(&current_path_for_processing, &base_mods_path_clone, &maps_clone) {
-                                Some(deduced) => {
-                                    println!("[Scan Task] Deduced slug for '{}': {}", path_display, deduced.entity_slug);
-                                    let target_entity_id_result: Option<i64> = maps_clone.entity_slug_to_id.get(&deduced.entity_slug).copied();
-
-                                    if let Some(target_entity_id) = target_entity_id_result {
-                                        println!("[Scan Task] Found entity ID {} for slug '{}'", target_entity_id, deduced.entity_slug);
-
-                                        let relative_path_buf = match current_path_for_processing.strip_prefix(&base_mods_path_clone) {
-                                            Ok(p) => p.to_path_buf(),
-                                            Err(_) => {
-                                                eprintln!("[Scan Task] Error: Could not strip base path prefix from '{}'. Skipping.", path_display);
-                                                errors_count += 1;
-                                                continue; // Skip only this mod folder deduction/DB part
+                            // --- END: Rename Check ---
+
+                            // Now check if the (potentially renamed) folder has an INI file
+                            if has_ini_file(&current_path_for_processing) {
+                                // This is a mod folder (or was successfully renamed to be treated as one)
+                                processed_count += 1; // Increment processed count *here*
+                                processed_mod_paths.insert(current_path_for_processing.clone()); // Add the path we actually processed
+                                let path_display = current_path_for_processing.display().to_string();
+                                let folder_name_only = current_path_for_processing.file_name().unwrap_or_default().to_string_lossy();
+
+                                // Emit progress for actual mod processing
+                                app_handle_clone.emit_all(SCAN_PROGRESS_EVENT, ScanProgress {
+                                    processed: processed_count,
+                                    total: total_to_process,
+                                    current_path: Some(path_display.clone()),
+                                    message: format!("Processing: {}", folder_name_only)
+                                }).unwrap_or_else(|e| eprintln!("Failed to emit scan progress: {}", e));
+
+                                // --- Start Original Deduction/DB Logic (using current_path_for_processing) ---
+                                match deduce_mod_info_v2(&current_path_for_processing, base_mods_path_clone, &maps_clone) {
+                                    Some(deduced) => {
+                                        println!("[Scan Task] Deduced slug for '{}': {}", path_display, deduced.entity_slug);
+                                        let target_entity_id_result: Option<i64> = maps_clone.entity_slug_to_id.get(&deduced.entity_slug).copied();
+
+                                        if let Some(target_entity_id) = target_entity_id_result {
+                                            println!("[Scan Task] Found entity ID {} for slug '{}'", target_entity_id, deduced.entity_slug);
+
+                                            let relative_path_buf = match current_path_for_processing.strip_prefix(base_mods_path_clone) {
+                                                Ok(p) => p.to_path_buf(),
+                                                Err(_) => {
+                                                    eprintln!("[Scan Task] Error: Could not strip base path prefix from '{}'. Skipping.", path_display);
+                                                    errors_count += 1;
+                                                    continue; // Skip only this mod folder deduction/DB part
+                                                }
+                                            };
+
+                                            // --- Critical: Strip the configured disabled marker to get the CLEAN path ---
+                                            let clean_relative_path_buf = match scan_marker {
+                                                Some(marker) => marker.strip(&relative_path_buf).0,
+                                                None => relative_path_buf.clone(),
+                                            };
+                                            let relative_path_to_store = clean_relative_path_buf.to_string_lossy().replace("\\", "/");
+                                            println!("[Scan Task] Calculated DB path: '{}'", relative_path_to_store);
+
+                                            // Same folder in the same root, else a mod moved here from another
+                                            // (reachable) root outside GMM that hasn't been seen in this scan yet
+                                            let mut existing_db_asset_id: Option<i64> = conn.query_row(
+                                                "SELECT id FROM assets WHERE root_id IS ?1 AND folder_name = ?2",
+                                                params![root_id, relative_path_to_store],
+                                                |row| row.get(0),
+                                            ).optional().map_err(|e| format!("DB error checking for existing asset '{}': {}", relative_path_to_store, e))?;
+                                            if existing_db_asset_id.is_none() {
+                                                let mut stmt = conn.prepare("SELECT id, root_id FROM assets WHERE folder_name = ?1 AND root_id IS NOT ?2")
+                                                    .map_err(|e| format!("DB error checking for moved asset '{}': {}", relative_path_to_store, e))?;
+                                                let candidates = stmt.query_map(params![relative_path_to_store, root_id], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, Option<i64>>(1)?)))
+                                                    .and_then(|rows| rows.collect::<SqlResult<Vec<_>>>())
+                                                    .map_err(|e| format!("DB error checking for moved asset '{}': {}", relative_path_to_store, e))?;
+                                                existing_db_asset_id = candidates.into_iter()
+                                                    .find(|(id, old_root_id)| {
+                                                        !found_asset_ids.contains(id)
+                                                            && !old_root_id.is_some_and(|old| unavailable_root_ids.contains(&old))
+                                                            && activation::backend_for_asset(&scan_roots[0].1, *old_root_id).and_then(|b| b.locate(&relative_path_to_store)).is_none()
+                                                    })
+                                                    .map(|(id, _)| id);
                                             }
-                                        };
-
-                                        let filename_osstr = relative_path_buf.file_name().unwrap_or_default();
-                                        let filename_str = filename_osstr.to_string_lossy();
-                                        // --- Critical: Ensure stripping the CORRECT prefix after potential rename ---
-                                        let clean_filename = filename_str.strip_prefix(DISABLED_PREFIX).unwrap_or(&filename_str);
-                                        // ---
-                                        let relative_parent_path = relative_path_buf.parent();
-                                        let relative_path_to_store = match relative_parent_path {
-                                            Some(parent) if parent.as_os_str().len() > 0 => parent.join(clean_filename).to_string_lossy().to_string(),
-                                            _ => clean_filename.to_string(),
-                                        };
-                                        let relative_path_to_store = relative_path_to_store.replace("\\", "/");
-                                        println!("[Scan Task] Calculated DB path: '{}'", relative_path_to_store);
-
-                                        let existing_db_asset_id: Option<i64> = conn.query_row(
-                                            "SELECT id FROM assets WHERE entity_id = ?1 AND folder_name = ?2",
-                                            params![target_entity_id, relative_path_to_store],
-                                            |row| row.get(0),
-                                        ).optional().map_err(|e| format!("DB error checking for existing asset '{}': {}", relative_path_to_store, e))?;
-
-                                        if let Some(asset_id) = existing_db_asset_id {
-                                            println!("[Scan Task] Asset already in DB (ID: {}), path '{}'. Marking as found.", asset_id, relative_path_to_store);
-                                            found_asset_ids.insert(asset_id);
-                                            // mods_updated_count += 1; // Optional update logic here
-                                        } else {
-                                            println!("[Scan Task] Inserting new asset: EntityID={}, Name='{}', Path='{}'", target_entity_id, deduced.mod_name, relative_path_to_store);
-                                            let insert_result = conn.execute(
-                                                "INSERT INTO assets (entity_id, name, description, folder_name, image_filename, author, category_tag) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-                                                params![
-                                                    target_entity_id,
-                                                    deduced.mod_name,
-                                                    deduced.description,
-                                                    relative_path_to_store,
-                                                    deduced.image_filename,
-                                                    deduced.author,
-                                                    deduced.mod_type_tag
-                                                ]
-                                            );
-
-                                            match insert_result {
-                                                Ok(changes) => {
-                                                    if changes > 0 {
-                                                        mods_added_count += 1;
-                                                        let new_id = conn.last_insert_rowid();
-                                                        found_asset_ids.insert(new_id);
-                                                        println!("[Scan Task]   -> Insert successful (New ID: {})", new_id);
-                                                    } else {
-                                                        eprintln!("[Scan Task]   -> Insert reported 0 changes for '{}'.", relative_path_to_store);
-                                                        errors_count += 1;
+
+                                            if let Some(asset_id) = existing_db_asset_id {
+                                                println!("[Scan Task] Asset already in DB (ID: {}), path '{}'. Marking as found.", asset_id, relative_path_to_store);
+                                                found_asset_ids.insert(asset_id);
+                                                // INIs may have been edited outside GMM since the last scan
+                                                if index_ini_sections {
+                                                    if let Err(e) = refresh_asset_ini_sections(&conn, &current_path_for_processing, asset_id) {
+                                                        eprintln!("[Scan Task]   -> Failed to index INI sections of asset {}: {}", asset_id, e);
+                                                    }
+                                                }
+                                                // Keep the recorded root in sync (mod moved between roots outside GMM)
+                                                // and bring back mods that were marked missing
+                                                match conn.execute(
+                                                    "UPDATE assets SET root_id = ?1, missing_since = NULL WHERE id = ?2 AND (root_id IS NOT ?1 OR missing_since IS NOT NULL)",
+                                                    params![root_id, asset_id],
+                                                ) {
+                                                    Ok(changes) if changes > 0 => {
+                                                        mods_updated_count += 1;
+                                                        // A trashed copy no longer belongs to this asset once its folder is back
+                                                        conn.execute("UPDATE trash SET asset_id = NULL WHERE asset_id = ?1", params![asset_id]).ok();
                                                     }
+                                                    Ok(_) => {}
+                                                    Err(e) => eprintln!("[Scan Task]   -> Failed to update root of asset {}: {}", asset_id, e),
                                                 }
-                                                Err(e) => {
-                                                    if e.to_string().contains("UNIQUE constraint failed: assets.folder_name") {
-                                                        eprintln!("[Scan Task]   -> Insert failed due to UNIQUE constraint on folder_name '{}'. Asset might exist under a different entity or needs pruning. Skipping insert.", relative_path_to_store);
-                                                        // Maybe don't count as error if pruning will fix it?
-                                                    } else {
+                                            } else {
+                                                println!("[Scan Task] Inserting new asset: EntityID={}, Name='{}', Path='{}'", target_entity_id, deduced.mod_name, relative_path_to_store);
+                                                let insert_result = conn.execute(
+                                                    "INSERT INTO assets (entity_id, name, description, folder_name, image_filename, author, category_tag, root_id, added_at, updated_at, last_seen_on_disk_at, is_favorite, rating, notes) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?9, ?9, ?10, ?11, ?12)",
+                                                    params![
+                                                        target_entity_id,
+                                                        deduced.mod_name,
+                                                        deduced.description,
+                                                        relative_path_to_store,
+                                                        deduced.image_filename,
+                                                        deduced.author,
+                                                        deduced.mod_type_tag,
+                                                        root_id,
+                                                        scan_time,
+                                                        deduced.personal.is_favorite as i64,
+                                                        deduced.personal.rating,
+                                                        deduced.personal.notes
+                                                    ]
+                                                );
+
+                                                match insert_result {
+                                                    Ok(changes) => {
+                                                        if changes > 0 {
+                                                            mods_added_count += 1;
+                                                            let new_id = conn.last_insert_rowid();
+                                                            found_asset_ids.insert(new_id);
+                                                            println!("[Scan Task]   -> Insert successful (New ID: {})", new_id);
+                                                            match restore_sidecar_presets(&conn, new_id, &deduced.sidecar_presets) {
+                                                                Ok(skipped) if !skipped.is_empty() => println!("[Scan Task]   -> Sidecar presets not found, skipped: {:?}", skipped),
+                                                                Ok(_) => {}
+                                                                Err(e) => eprintln!("[Scan Task]   -> Failed to restore sidecar presets for asset {}: {}", new_id, e),
+                                                            }
+                                                            if let Err(e) = assign_tags(&conn, new_id, &deduced.tags) {
+                                                                eprintln!("[Scan Task]   -> Failed to assign tags to asset {}: {}", new_id, e);
+                                                            }
+                                                            if index_ini_sections {
+                                                                if let Err(e) = refresh_asset_ini_sections(&conn, &current_path_for_processing, new_id) {
+                                                                    eprintln!("[Scan Task]   -> Failed to index INI sections of asset {}: {}", new_id, e);
+                                                                }
+                                                            }
+                                                        } else {
+                                                            eprintln!("[Scan Task]   -> Insert reported 0 changes for '{}'.", relative_path_to_store);
+                                                            errors_count += 1;
+                                                        }
+                                                    }
+                                                    Err(e) => {
                                                         eprintln!("[Scan Task]   -> DB error inserting new asset '{}': {}", relative_path_to_store, e);
                                                         errors_count += 1;
                                                     }
                                                 }
                                             }
+                                        } else {
+                                            eprintln!("[Scan Task] CRITICAL ERROR: Deduced slug '{}' for path '{}' does NOT exist in the entity map! Skipping mod. Check DB initialization and deduction logic.", deduced.entity_slug, path_display);
+                                            errors_count += 1;
                                         }
-                                    } else {
-                                        eprintln!("[Scan Task] CRITICAL ERROR: Deduced slug '{}' for path '{}' does NOT exist in the entity map! Skipping mod. Check DB initialization and deduction logic.", deduced.entity_slug, path_display);
+                                    }
+                                    None => {
+                                        eprintln!("[Scan Task] Error: Failed to deduce mod info for path '{}'", path_display);
                                         errors_count += 1;
                                     }
                                 }
-                                None => {
-                                    eprintln!("[Scan Task] Error: Failed to deduce mod info for path '{}'", path_display);
-                                    errors_count += 1;
-                                }
+                                // --- End Original Deduction/DB Logic ---
+                                walker.skip_current_dir(); // Skip children after processing a mod folder
                             }
-                            // --- End Original Deduction/DB Logic ---
-                            walker.skip_current_dir(); // Skip children after processing a mod folder
+                            // If it's a directory but doesn't have an INI (and wasn't renamed+processed),
+                            // we just let WalkDir continue into its children.
                         }
-                        // If it's a directory but doesn't have an INI (and wasn't renamed+processed),
-                        // we just let WalkDir continue into its children.
+                        // If it's not a directory, or already processed, ignore.
+                    }
+                    Err(e) => {
+                         eprintln!("[Scan Task] Error accessing path during scan: {}", e);
+                         errors_count += 1;
                     }
-                    // If it's not a directory, or already processed, ignore.
-                }
-                Err(e) => {
-                     eprintln!("[Scan Task] Error accessing path during scan: {}", e);
-                     errors_count += 1;
                 }
             }
+        } // End roots loop
+
+        // --- Stamp every mod found on disk ---
+        let found_ids: Vec<i64> = found_asset_ids.iter().copied().collect();
+        for chunk in found_ids.chunks(500) {
+            let placeholders = chunk.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+            let sql = format!("UPDATE assets SET last_seen_on_disk_at = {} WHERE id IN ({})", scan_time, placeholders);
+            if let Err(e) = conn.execute(&sql, rusqlite::params_from_iter(chunk)) {
+                eprintln!("[Scan Task] Failed to update last seen times: {}", e);
+            }
         }
 
-        // --- Pruning Logic (Remains the same) ---
+        // --- Pruning Logic: mods missing from disk are only marked, keeping their metadata and preset links ---
         let mut mods_to_prune_ids = Vec::new();
         for (asset_id, _clean_path) in initial_db_assets.iter() {
             if !found_asset_ids.contains(asset_id) {
@@ -2439,7 +3361,7 @@ async fn scan_mods_directory(db_state: State<'_, DbState>, app_handle: AppHandle
         let mut pruning_errors_count = 0;
 
         if !mods_to_prune_ids.is_empty() {
-            println!("[Scan Task Pruning] Found {} mods in DB missing from disk. Pruning...", prune_count);
+            println!("[Scan Task Pruning] Found {} mods in DB missing from disk. Marking them missing...", prune_count);
             app_handle_clone.emit_all(PRUNING_START_EVENT, prune_count).ok();
 
              let ids_to_delete_sql: Vec<Box<dyn rusqlite::ToSql>> = mods_to_prune_ids
@@ -2449,9 +3371,10 @@ async fn scan_mods_directory(db_state: State<'_, DbState>, app_handle: AppHandle
 
             if !ids_to_delete_sql.is_empty() {
                 let placeholders = ids_to_delete_sql.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-                let sql = format!("DELETE FROM assets WHERE id IN ({})", placeholders);
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+                let sql = format!("UPDATE assets SET missing_since = {} WHERE missing_since IS NULL AND id IN ({})", now, placeholders);
 
-                app_handle_clone.emit_all(PRUNING_PROGRESS_EVENT, format!("Deleting {} entries...", ids_to_delete_sql.len())).ok();
+                app_handle_clone.emit_all(PRUNING_PROGRESS_EVENT, format!("Marking {} entries as missing...", ids_to_delete_sql.len())).ok();
 
                 let delete_result = conn.execute(&sql, rusqlite::params_from_iter(ids_to_delete_sql))
                                         .map_err(|e| format!("DB error during pruning: {}", e));
@@ -2459,7 +3382,7 @@ async fn scan_mods_directory(db_state: State<'_, DbState>, app_handle: AppHandle
                 match delete_result {
                     Ok(count) => {
                          pruned_count = count;
-                         println!("[Scan Task Pruning] Successfully pruned {} asset entries.", pruned_count);
+                         println!("[Scan Task Pruning] Marked {} asset entries as missing.", pruned_count);
                          app_handle_clone.emit_all(PRUNING_COMPLETE_EVENT, pruned_count).ok();
                     },
                     Err(e) => {
@@ -2487,7 +3410,7 @@ async fn scan_mods_directory(db_state: State<'_, DbState>, app_handle: AppHandle
          Ok(Ok((processed, added, _updated, errors, pruned, renamed))) => { // Add renamed here
              let rename_msg = if renamed > 0 { format!(" Renamed {} incorrectly prefixed folders.", renamed) } else { "".to_string() };
              let summary = format!(
-                 "Scan complete. Processed {} mod folders. Added {} new mods. Pruned {} missing mods.{} {} errors occurred.",
+                 "Scan complete. Processed {} mod folders. Added {} new mods. Marked {} mods as missing.{} {} errors occurred.",
                  processed, added, pruned, rename_msg, errors
             );
              println!("{}", summary);
@@ -2511,7 +3434,7 @@ async fn scan_mods_directory(db_state: State<'_, DbState>, app_handle: AppHandle
 #[command]
 fn get_total_asset_count(db_state: State<DbState>) -> CmdResult<i64> {
     let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
-    conn.query_row("SELECT COUNT(*) FROM assets", [], |row| row.get(0))
+    conn.query_row("SELECT COUNT(*) FROM assets WHERE missing_since IS NULL", [], |row| row.get(0))
         .map_err(|e| e.to_string())
 }
 
@@ -2537,6 +3460,7 @@ fn update_asset_info(
     let current_info = get_asset_location_info(conn, asset_id)
         .map_err(|e| format!("Failed get current asset info: {}", e))?;
     println!("[update_asset_info] Current Info: {:?}", current_info);
+    let snapshot_before = fetch_asset_snapshot(conn, asset_id).map_err(|e| e.to_string())?;
 
     // --- 2. Relocation Logic ---
     let needs_relocation = new_target_entity_slug.is_some() && new_target_entity_slug.as_deref() != Some(&current_info.entity_slug);
@@ -2560,49 +3484,21 @@ fn update_asset_info(
             |row| Ok((row.get(0)?, row.get(1)?)),
         ).map_err(|e| format!("DB Error getting new target entity info: {}", e))?;
 
-        // --- Determine Current Full Path on Disk (Check Enabled/Disabled) ---
+        // --- Construct New Relative Path (for DB) ---
         let current_relative_path_buf = PathBuf::from(&current_info.clean_relative_path);
-        let current_filename_osstr = current_relative_path_buf.file_name().ok_or("Cannot get current filename")?;
-        let current_filename_str = current_filename_osstr.to_string_lossy();
-        let disabled_filename = format!("{}{}", DISABLED_PREFIX, current_filename_str);
-        let relative_parent_path = current_relative_path_buf.parent();
-        let full_path_if_enabled = base_mods_path.join(&current_relative_path_buf);
-        let full_path_if_disabled = match relative_parent_path {
-           Some(parent) if parent.as_os_str().len() > 0 => base_mods_path.join(parent).join(&disabled_filename),
-           _ => base_mods_path.join(&disabled_filename),
-        };
-        let current_full_path = if full_path_if_enabled.is_dir() { full_path_if_enabled }
-            else if full_path_if_disabled.is_dir() { full_path_if_disabled }
-            else { return Err(format!("Cannot relocate: Source folder not found at '{}' or disabled variant.", full_path_if_enabled.display())); };
-        println!("[update_asset_info] Current full path on disk: {}", current_full_path.display());
-
-        // --- Construct New Relative (for DB) and Full (for Disk) Paths ---
-        let mod_base_name = current_filename_str.trim_start_matches(DISABLED_PREFIX);
-        let new_relative_path_buf = PathBuf::new().join(&new_category_slug).join(target_slug).join(mod_base_name);
+        let mod_base_name = current_relative_path_buf.file_name().ok_or("Cannot get current filename")?.to_string_lossy().to_string();
+        let new_relative_path_buf = PathBuf::new().join(&new_category_slug).join(target_slug).join(&mod_base_name);
         final_relative_path_str = new_relative_path_buf.to_string_lossy().replace("\\", "/"); // For DB
-
-        // Determine the name to use on disk (keep disabled prefix if present)
-        let new_filename_to_use_on_disk = if current_full_path.file_name().map_or(false, |name| name.to_string_lossy().starts_with(DISABLED_PREFIX)) {
-             disabled_filename // Keep disabled prefix
-        } else {
-             mod_base_name.to_string() // Use clean name
-        };
-        let new_full_dest_path_on_disk = base_mods_path.join(&new_category_slug).join(target_slug).join(&new_filename_to_use_on_disk);
         println!("[update_asset_info] New relative path for DB: {}", final_relative_path_str);
-        println!("[update_asset_info] New full destination path on disk: {}", new_full_dest_path_on_disk.display());
 
-        // --- Create Parent Directory & Perform Move ---
-        if let Some(parent) = new_full_dest_path_on_disk.parent() {
-            fs::create_dir_all(parent).map_err(|e| e.to_string())? // Add map_err
-       } else {
-            return Err("Could not determine parent for new path".into());
-       }
-        if new_full_dest_path_on_disk.exists() { return Err(format!("Cannot relocate: Target path '{}' already exists.", new_full_dest_path_on_disk.display())); }
-        fs::rename(&current_full_path, &new_full_dest_path_on_disk)
-            .map_err(|e| e.to_string())?; // Add map_err
-        // --- END FIX 2 ---
-
-        println!("[update_asset_info] Successfully moved mod folder.");
+        // --- Move the folder, keeping its enabled/disabled state ---
+        ensure_folder_unclaimed(conn, current_info.root_id, &final_relative_path_str, asset_id)
+            .map_err(|e| format!("Cannot relocate: {}", e))?;
+        let backend = activation::backend_for_asset(&base_mods_path, current_info.root_id)
+            .ok_or_else(|| format!("Cannot relocate: Source folder not found for '{}'.", current_info.clean_relative_path))?;
+        let new_full_dest_path_on_disk = activation::relocate_asset(backend.as_ref(), &current_info.clean_relative_path, &final_relative_path_str)
+            .map_err(|e| format!("Cannot relocate: {}", e))?;
+        println!("[update_asset_info] Successfully moved mod folder to {}.", new_full_dest_path_on_disk.display());
 
         final_entity_id = new_entity_id;
         final_path_on_disk = Some(new_full_dest_path_on_disk);
@@ -2616,19 +3512,9 @@ fn update_asset_info(
         relocated_path
     } else {
         // If no relocation, determine current path (enabled/disabled) based on current_info
-        let current_relative_path_buf = PathBuf::from(&current_info.clean_relative_path);
-        let current_filename_osstr = current_relative_path_buf.file_name().ok_or("Cannot get current filename")?;
-        let current_filename_str = current_filename_osstr.to_string_lossy();
-        let disabled_filename = format!("{}{}", DISABLED_PREFIX, current_filename_str);
-        let relative_parent_path = current_relative_path_buf.parent();
-        let full_path_if_enabled = base_mods_path.join(&current_relative_path_buf);
-        let full_path_if_disabled = match relative_parent_path {
-            Some(parent) if parent.as_os_str().len() > 0 => base_mods_path.join(parent).join(&disabled_filename),
-            _ => base_mods_path.join(&disabled_filename),
-        };
-        if full_path_if_enabled.is_dir() { full_path_if_enabled }
-        else if full_path_if_disabled.is_dir() { full_path_if_disabled }
-        else { return Err(format!("Mod folder not found on disk at '{}' or disabled variant.", full_path_if_enabled.display())); }
+        locate_asset_folder(&base_mods_path, current_info.root_id, &current_info.clean_relative_path)
+            .map(|(path, _)| path)
+            .ok_or_else(|| format!("Mod folder not found on disk for '{}'.", current_info.clean_relative_path))?
     };
     println!("[update_asset_info] Confirmed mod path on disk for image: {}", mod_folder_on_disk.display());
 
@@ -2678,7 +3564,7 @@ fn update_asset_info(
     // --- 5. Update Database ---
     println!("[update_asset_info] Attempting DB update for asset ID {}...", asset_id);
     let changes = conn.execute(
-        "UPDATE assets SET name = ?1, description = ?2, author = ?3, category_tag = ?4, image_filename = ?5, entity_id = ?6, folder_name = ?7 WHERE id = ?8",
+        "UPDATE assets SET name = ?1, description = ?2, author = ?3, category_tag = ?4, image_filename = ?5, entity_id = ?6, folder_name = ?7, updated_at = ?9 WHERE id = ?8",
         params![
             name, // Use name from arguments
             description,
@@ -2687,84 +3573,159 @@ fn update_asset_info(
             image_filename_to_save, // Use the determined filename
             final_entity_id,        // Use potentially updated entity ID
             final_relative_path_str, // Use potentially updated relative path (for DB only)
-            asset_id
+            asset_id,
+            SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
         ]
     ).map_err(|e| format!("Failed update asset info in DB for ID {}: {}", asset_id, e))?;
 
     println!("[update_asset_info] DB update executed. Changes: {}", changes);
     if changes == 0 { eprintln!("[update_asset_info] Warning: DB update affected 0 rows for asset ID {}.", asset_id); }
 
+    // Preview images are overwritten in place and can't be undone; the row and folder location can
+    match fetch_asset_snapshot(conn, asset_id) {
+        Ok(snapshot_after) if snapshot_after != snapshot_before => {
+            let summary = if needs_relocation { format!("Moved '{}'", snapshot_after.name) } else { format!("Edited '{}'", snapshot_after.name) };
+            journal_operation(conn, &summary, &JournalOp::UpdateAsset { asset_id, before: Box::new(snapshot_before), after: Box::new(snapshot_after) });
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("[update_asset_info] Warning: Could not journal the change: {}", e),
+    }
+    sync_asset_sidecars(conn, &[asset_id]);
+
     println!("[update_asset_info] Asset ID {} updated successfully. END", asset_id);
     Ok(())
 }
 
-#[command]
-fn delete_asset(asset_id: i64, db_state: State<DbState>) -> CmdResult<()> {
-     println!("[delete_asset] Attempting to delete asset ID: {}", asset_id);
+// Favorite flag, 1-5 rating and notes the user keeps on an asset
+#[derive(Debug, Clone, Default)]
+struct AssetPersonalData {
+    is_favorite: bool,
+    rating: Option<i64>,
+    notes: Option<String>,
+}
 
-    let conn_guard = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
-    let conn = &*conn_guard;
-    println!("[delete_asset] DB lock acquired.");
+fn fetch_asset_personal_data(conn: &Connection, asset_id: i64) -> Result<AssetPersonalData, AppError> {
+    Ok(conn.query_row(
+        "SELECT is_favorite, rating, notes FROM assets WHERE id = ?1",
+        params![asset_id],
+        |row| Ok(AssetPersonalData { is_favorite: row.get::<_, i64>(0)? == 1, rating: row.get(1)?, notes: row.get(2)? }),
+    )?)
+}
 
-    // --- 1. Get Asset Info ---
-    let asset_info = get_asset_location_info(conn, asset_id)
-        .map_err(|e| format!("Failed to get asset info for deletion: {}", e))?;
-    println!("[delete_asset] Asset info found: {:?}", asset_info);
+// Writes one of the personal columns (favorite, rating, notes) of an asset
+fn update_asset_personal_field(conn: &Connection, asset_id: i64, column: &str, value: &dyn rusqlite::ToSql) -> CmdResult<()> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    let changes = conn.execute(&format!("UPDATE assets SET {} = ?1, updated_at = ?2 WHERE id = ?3", column), params![value, now, asset_id])
+        .map_err(|e| format!("Failed to update asset {}: {}", asset_id, e))?;
+    if changes == 0 {
+        return Err(format!("Asset with ID {} not found.", asset_id));
+    }
+    sync_asset_sidecars(conn, &[asset_id]);
+    Ok(())
+}
 
-    // --- 2. Get Base Mods Path ---
-    let base_mods_path_str = get_setting_value(conn, SETTINGS_KEY_MODS_FOLDER)
-        .map_err(|e| format!("Failed to query mods folder setting: {}", e))?
-        .ok_or_else(|| "Mods folder path not set".to_string())?;
-    let base_mods_path = PathBuf::from(base_mods_path_str);
+#[command]
+fn set_asset_favorite(asset_id: i64, is_favorite: bool, db_state: State<DbState>) -> CmdResult<()> {
+    println!("[set_asset_favorite] Asset ID {}: favorite = {}", asset_id, is_favorite);
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    update_asset_personal_field(&conn, asset_id, "is_favorite", &(is_favorite as i64))
+}
 
-    // --- 3. Determine Full Path on Disk (Check Enabled/Disabled) ---
-     let relative_path_buf = PathBuf::from(&asset_info.clean_relative_path);
-     let filename_osstr = relative_path_buf.file_name().ok_or_else(|| format!("Could not extract filename from DB path: {}", asset_info.clean_relative_path))?;
-     let filename_str = filename_osstr.to_string_lossy();
-     let disabled_filename = format!("{}{}", DISABLED_PREFIX, filename_str);
-     let relative_parent_path = relative_path_buf.parent();
-
-     let full_path_if_enabled = base_mods_path.join(&relative_path_buf);
-     let full_path_if_disabled = match relative_parent_path {
-        Some(parent) if parent.as_os_str().len() > 0 => base_mods_path.join(parent).join(&disabled_filename),
-        _ => base_mods_path.join(&disabled_filename),
-     };
+// None clears the rating
+#[command]
+fn set_asset_rating(asset_id: i64, rating: Option<i64>, db_state: State<DbState>) -> CmdResult<()> {
+    println!("[set_asset_rating] Asset ID {}: rating = {:?}", asset_id, rating);
+    validate_rating(rating)?;
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    update_asset_personal_field(&conn, asset_id, "rating", &rating)
+}
 
-    let path_to_delete = if full_path_if_enabled.is_dir() {
-        Some(full_path_if_enabled)
-    } else if full_path_if_disabled.is_dir() {
-        Some(full_path_if_disabled)
-    } else {
-         // Folder not found, maybe already deleted? Log a warning but proceed to DB deletion.
-         eprintln!("[delete_asset] Warning: Mod folder not found on disk for asset ID {}. Checked {} and {}. Proceeding with DB deletion.",
-             asset_id, full_path_if_enabled.display(), full_path_if_disabled.display());
-         None
-    };
+// Blank notes are stored as NULL
+#[command]
+fn set_asset_notes(asset_id: i64, notes: Option<String>, db_state: State<DbState>) -> CmdResult<()> {
+    let notes = notes.filter(|n| !n.trim().is_empty());
+    println!("[set_asset_notes] Asset ID {}: {} chars", asset_id, notes.as_ref().map_or(0, |n| n.len()));
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    update_asset_personal_field(&conn, asset_id, "notes", &notes)
+}
 
-    // --- 4. Delete Folder from Filesystem ---
-    if let Some(path) = path_to_delete {
-         println!("[delete_asset] Deleting folder: {}", path.display());
-         fs::remove_dir_all(&path)
-            .map_err(|e| format!("Failed to delete mod folder '{}': {}", path.display(), e))?;
-         println!("[delete_asset] Folder deleted successfully.");
-    }
+// Moves the mod folder into the trash and marks the asset missing. Metadata and preset links
+// are kept until the trash entry is purged (see purge_trash / trash_retention_days).
+#[command]
+fn delete_asset(asset_id: i64, db_state: State<DbState>, app_handle: AppHandle) -> CmdResult<()> {
+     println!("[delete_asset] Attempting to delete asset ID: {}", asset_id);
+
+    let conn_guard = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let conn = &*conn_guard;
+    println!("[delete_asset] DB lock acquired.");
 
-    // --- 5. Delete from Database ---
-    println!("[delete_asset] Deleting asset ID {} from database.", asset_id);
-    let changes = conn.execute("DELETE FROM assets WHERE id = ?1", params![asset_id])
-        .map_err(|e| format!("Failed to delete asset ID {} from database: {}", asset_id, e))?;
+    let base_mods_path_str = get_setting_value(conn, SETTINGS_KEY_MODS_FOLDER)
+        .map_err(|e| format!("Failed to query mods folder setting: {}", e))?
+        .ok_or_else(|| "Mods folder path not set".to_string())?;
+    let base_mods_path = PathBuf::from(base_mods_path_str);
+    let trash_dir = get_trash_dir(&app_handle, conn).map_err(|e| format!("Failed to resolve trash folder: {}", e))?;
 
-     if changes == 0 {
-         // This shouldn't happen if get_asset_location_info succeeded, but good to log.
-         eprintln!("[delete_asset] Warning: Database delete affected 0 rows for asset ID {}.", asset_id);
-     } else {
-         println!("[delete_asset] Database entry deleted successfully.");
-     }
+    let asset_name: String = conn.query_row("SELECT name FROM assets WHERE id = ?1", params![asset_id], |row| row.get(0))
+        .map_err(|e| format!("Failed to get asset name: {}", e))?;
+    let trash_id = trash_asset(conn, &base_mods_path, &trash_dir, asset_id)?;
+    journal_operation(conn, &format!("Deleted '{}'", asset_name), &JournalOp::Trash { asset_id, trash_id });
 
-    println!("[delete_asset] Asset ID {} deleted successfully. END", asset_id);
+    println!("[delete_asset] Asset ID {} moved to trash. END", asset_id);
     Ok(())
 }
 
+// Filesystem and DB part of delete_asset. Returns the trash entry (None if the folder was already gone).
+fn trash_asset(conn: &Connection, base_mods_path: &Path, trash_dir: &Path, asset_id: i64) -> CmdResult<Option<i64>> {
+    // --- 1. Get Asset Info ---
+    let asset_info = get_asset_location_info(conn, asset_id)
+        .map_err(|e| format!("Failed to get asset info for deletion: {}", e))?;
+    println!("[trash_asset] Asset info found: {:?}", asset_info);
+    let asset_name: String = conn.query_row("SELECT name FROM assets WHERE id = ?1", params![asset_id], |row| row.get(0))
+        .map_err(|e| format!("Failed to get asset name: {}", e))?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+
+    // --- 2. Determine Full Path on Disk (Check Enabled/Disabled) ---
+    let clean_relative_path = asset_info.clean_relative_path.as_str();
+    let root_id = asset_info.root_id;
+    let trash_id = match activation::backend_for_asset(base_mods_path, root_id).filter(|backend| backend.locate(clean_relative_path).is_some()) {
+        Some(backend) => {
+            let was_enabled = backend.locate(clean_relative_path).is_some_and(|(_, is_enabled)| is_enabled);
+            // Link mode: drop the materialized copy in the Mods folder before moving the source
+            backend.set_enabled(clean_relative_path, false)
+                .map_err(|e| format!("Failed to deactivate mod before deletion: {}", e))?;
+            let (source_path, _) = backend.locate(clean_relative_path)
+                .ok_or_else(|| format!("Mod folder '{}' disappeared while deactivating.", clean_relative_path))?;
+
+            // --- 3. Move Folder to the Trash ---
+            let leaf_name = Path::new(clean_relative_path).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            let trash_path = trash_dir.join(format!("{}_{}_{}", now, asset_id, leaf_name));
+            println!("[trash_asset] Moving folder to trash: {} -> {}", source_path.display(), trash_path.display());
+            if let Err(e) = activation::move_folder(&source_path, &trash_path) {
+                if was_enabled {
+                    backend.set_enabled(clean_relative_path, true).ok();
+                }
+                return Err(format!("Failed to move mod folder '{}' to the trash: {}", source_path.display(), e));
+            }
+            conn.execute(
+                "INSERT INTO trash (asset_id, name, folder_name, root_id, trash_path, was_enabled, deleted_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![asset_id, asset_name, clean_relative_path, root_id, trash_path.to_string_lossy(), was_enabled, now],
+            ).map_err(|e| format!("Folder was moved to '{}' but the trash entry could not be saved: {}", trash_path.display(), e))?;
+            Some(conn.last_insert_rowid())
+        }
+        None => {
+            // Folder not found, maybe already deleted? Log a warning and only mark the asset.
+            eprintln!("[trash_asset] Warning: Mod folder not found on disk for asset ID {} (path '{}'). Marking it missing.",
+                asset_id, clean_relative_path);
+            None
+        }
+    };
+
+    // --- 4. Soft-delete in Database ---
+    conn.execute("UPDATE assets SET missing_since = ?1 WHERE id = ?2", params![now, asset_id])
+        .map_err(|e| format!("Failed to mark asset ID {} as deleted: {}", asset_id, e))?;
+    Ok(trash_id)
+}
+
 #[command]
 async fn read_binary_file(path: String) -> Result<Vec<u8>, String> {
     println!("[read_binary_file] Reading path: {}", path);
@@ -3014,31 +3975,18 @@ fn analyze_archive(
                 if let Some((_ini_path, ini_content)) = ini_contents.iter().find(|(p, _)| p.starts_with(&root_prefix) && p.trim_start_matches(&root_prefix).find('/') == None) {
                     println!("[analyze_archive] Found INI in root {}: {}", root_prefix, _ini_path);
                     if let Ok(ini) = Ini::load_from_str(ini_content) {
-                        // --- Temporary storage for extracted hints ---
-                        let mut extracted_target: Option<String> = None;
-                        let mut extracted_type: Option<String> = None;
-                        // ---
-                        for section_name in ["Mod", "Settings", "Info", "General"] {
-                            if let Some(section) = ini.section(Some(section_name)) {
-                                // Extract Name, Author
-                                let name_val = section.get("Name").or_else(|| section.get("ModName"));
-                                // Use the INI name if found, otherwise keep the initial filename guess
-                                if let Some(name) = name_val {
-                                    let cleaned_ini_name = MOD_NAME_CLEANUP_REGEX.replace_all(name, "").trim().to_string();
-                                    if !cleaned_ini_name.is_empty() {
-                                        deduced_mod_name = Some(cleaned_ini_name);
-                                    }
-                                }
-                                let author_val = section.get("Author");
-                                if author_val.is_some() { deduced_author = author_val.map(String::from); }
-
-                                // Extract Raw Hints
-                                let target_val = section.get("Target").or_else(|| section.get("Entity")).or_else(|| section.get("Character"));
-                                if target_val.is_some() { extracted_target = target_val.map(|s| s.trim().to_string()); }
-                                let type_val = section.get("Type").or_else(|| section.get("Category"));
-                                if type_val.is_some() { extracted_type = type_val.map(|s| s.trim().to_string()); }
+                        let meta = read_ini_mod_metadata(&ini);
+                        // Use the INI name if found, otherwise keep the initial filename guess
+                        if let Some(name) = meta.name {
+                            let cleaned_ini_name = MOD_NAME_CLEANUP_REGEX.replace_all(&name, "").trim().to_string();
+                            if !cleaned_ini_name.is_empty() {
+                                deduced_mod_name = Some(cleaned_ini_name);
                             }
                         }
+                        if meta.author.is_some() { deduced_author = meta.author; }
+                        // Extract Raw Hints
+                        let extracted_target = meta.target;
+                        let extracted_type = meta.mod_type;
                         // Log extracted hints and assign to outer scope
                         println!("[analyze_archive] INI Extracted Hints: Target='{:?}', Type='{:?}'", extracted_target, extracted_type);
                         raw_ini_target_found = extracted_target;
@@ -3370,6 +4318,7 @@ fn read_archive_file_content(archive_path_str: String, internal_file_path: Strin
 }
 
 #[command]
+#[allow(clippy::too_many_arguments)]
 fn import_archive(
     archive_path_str: String,
     target_entity_slug: String,
@@ -3381,6 +4330,7 @@ fn import_archive(
     image_data: Option<Vec<u8>>,
     selected_preview_absolute_path: Option<String>,
     preset_ids: Option<Vec<i64>>,
+    target_root_id: Option<i64>, // None imports into the primary Mods folder
     db_state: State<DbState>
 ) -> CmdResult<()> {
     println!("[import_archive] Importing '{}', internal path '{}' for entity '{}'. Image Data Provided: {}. Add to presets: {:?}",
@@ -3413,7 +4363,18 @@ fn import_archive(
 
     let target_mod_folder_name = mod_name.trim().replace(" ", "_").replace(".", "_").replace("'", "").replace("\"", "");
     if target_mod_folder_name.is_empty() { return Err("Mod Name results in invalid folder name.".to_string()); }
-    let final_mod_dest_path = base_mods_path.join(&target_category_slug).join(&target_entity_slug).join(&target_mod_folder_name);
+    let backend = match target_root_id {
+        None => activation::backend_for(&base_mods_path),
+        Some(root_id) => {
+            let root = activation::mod_roots().into_iter().find(|r| r.id == root_id)
+                .ok_or_else(|| format!("Mod folder with ID {} not found.", root_id))?;
+            if root.role == activation::ROOT_ROLE_READONLY {
+                return Err(format!("'{}' is read-only; mods can't be imported into it.", root.path.display()));
+            }
+            activation::backend_for_root(&root, &activation::active_settings())
+        }
+    };
+    let final_mod_dest_path = backend.scan_root().join(&target_category_slug).join(&target_entity_slug).join(&target_mod_folder_name);
 
     fs::create_dir_all(&final_mod_dest_path)
         .map_err(|e| format!("Failed create dest directory '{}': {}", final_mod_dest_path.display(), e))?;
@@ -3601,32 +4562,53 @@ fn import_archive(
     let relative_path_for_db = Path::new(&target_category_slug).join(&target_entity_slug).join(&target_mod_folder_name);
     let relative_path_for_db_str = relative_path_for_db.to_string_lossy().replace("\\", "/");
 
-    let check_existing: Option<i64> = tx.query_row(
-        "SELECT id FROM assets WHERE entity_id = ?1 AND folder_name = ?2",
-        params![target_entity_id, relative_path_for_db_str], |row| row.get(0)
+    let check_existing: Option<(i64, bool)> = tx.query_row(
+        "SELECT id, missing_since IS NOT NULL FROM assets WHERE root_id IS ?1 AND folder_name = ?2",
+        params![target_root_id, relative_path_for_db_str], |row| Ok((row.get(0)?, row.get(1)?))
     ).optional().map_err(|e| format!("DB error check existing import '{}': {}", relative_path_for_db_str, e))?;
 
-    if check_existing.is_some() {
-        fs::remove_dir_all(&final_mod_dest_path).ok();
-        return Err(format!("Database entry already exists for '{}'. Aborting.", relative_path_for_db_str));
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    let new_asset_id = match check_existing {
+        Some((_, false)) => {
+            fs::remove_dir_all(&final_mod_dest_path).ok();
+            return Err(format!("Database entry already exists for '{}'. Aborting.", relative_path_for_db_str));
+        }
+        Some((missing_asset_id, true)) => {
+            // Reinstalling a deleted or vanished mod: take over its row like a rescan would,
+            // keeping favorites, ratings, notes and preset links
+            println!("[import_archive] Reusing missing asset {} at '{}'", missing_asset_id, relative_path_for_db_str);
+            tx.execute(
+                "UPDATE assets SET entity_id = ?1, name = ?2, description = ?3, image_filename = ?4, author = ?5, category_tag = ?6,
+                 missing_since = NULL, updated_at = ?7, last_seen_on_disk_at = ?7 WHERE id = ?8",
+                params![target_entity_id, mod_name.trim(), description, image_filename_for_db, author, category_tag, now, missing_asset_id],
+            ).and_then(|_| tx.execute("UPDATE trash SET asset_id = NULL WHERE asset_id = ?1", params![missing_asset_id]))
+            .map_err(|e| {
+                fs::remove_dir_all(&final_mod_dest_path).ok();
+                format!("Failed to update the reinstalled mod in the database: {}", e)
+            })?;
+            missing_asset_id
+        }
+        None => {
+            println!("[import_archive] Adding asset to DB: entity_id={}, name={}, path={}, image={:?}", target_entity_id, mod_name, relative_path_for_db_str, image_filename_for_db);
+            tx.execute(
+                "INSERT INTO assets (entity_id, name, description, folder_name, image_filename, author, category_tag, root_id, added_at, updated_at, last_seen_on_disk_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?9, ?9)",
+                params![
+                    target_entity_id, mod_name.trim(),
+                    description, relative_path_for_db_str,
+                    image_filename_for_db, author, category_tag, target_root_id, now
+                ]
+            ).map_err(|e| {
+                fs::remove_dir_all(&final_mod_dest_path).ok();
+                format!("Failed add imported mod to database: {}", e)
+            })?;
+            tx.last_insert_rowid()
+        }
+    };
+    println!("[import_archive] Asset stored with ID: {}", new_asset_id);
+    if let Err(e) = refresh_asset_ini_sections(&tx, &final_mod_dest_path, new_asset_id) {
+        eprintln!("[import_archive] Warning: Failed to index INI sections: {}", e);
     }
 
-    println!("[import_archive] Adding asset to DB: entity_id={}, name={}, path={}, image={:?}", target_entity_id, mod_name, relative_path_for_db_str, image_filename_for_db);
-    tx.execute(
-        "INSERT INTO assets (entity_id, name, description, folder_name, image_filename, author, category_tag) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-        params![
-            target_entity_id, mod_name.trim(),
-            description, relative_path_for_db_str,
-            image_filename_for_db, author, category_tag
-        ]
-    ).map_err(|e| {
-        fs::remove_dir_all(&final_mod_dest_path).ok();
-        format!("Failed add imported mod to database: {}", e)
-    })?;
-
-    let new_asset_id = tx.last_insert_rowid();
-    println!("[import_archive] Asset inserted with ID: {}", new_asset_id);
-
     // --- Add to Presets ---
     if let Some(ids) = preset_ids {
         if !ids.is_empty() {
@@ -3648,17 +4630,116 @@ fn import_archive(
         format!("Failed to commit import transaction: {}", e)
     })?;
 
+    // Extracted into the library in link mode; make it active like an in-place import.
+    // Archive roots keep their mods disabled.
+    if activation::root_role(target_root_id) == activation::ROOT_ROLE_LIBRARY {
+        println!("[import_archive] Imported into archive folder, leaving it disabled");
+    } else if let Err(e) = backend.set_enabled(&relative_path_for_db_str, true) {
+        eprintln!("[import_archive] Warning: Imported mod could not be activated: {}", e);
+    } else {
+        persist_materialized_folders(&conn_guard);
+        record_enabled_at(&conn_guard, &[AssetStateChange { asset_id: new_asset_id, is_enabled: true }]);
+    }
+    sync_asset_sidecars(&conn_guard, &[new_asset_id]);
+
    println!("[import_archive] Import successful for '{}'", mod_name);
    Ok(())
 }
 
+// --- Preset Helpers ---
+const PRESET_COLUMNS: &str = "id, name, is_favorite, mode, scope_entity_id, scope_category_id";
+
+fn preset_from_row(row: &rusqlite::Row) -> SqlResult<Preset> {
+    Ok(Preset {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        is_favorite: row.get::<_, i64>(2)? == 1,
+        mode: row.get(3)?,
+        scope_entity_id: row.get(4)?,
+        scope_category_id: row.get(5)?,
+        included_preset_ids: Vec::new(), // Filled by load_preset_layers
+        tag_selectors: Vec::new(),       // Same
+    })
+}
+
+fn fetch_preset_tag_selectors(conn: &Connection, preset_id: i64) -> Result<Vec<PresetTagSelector>, AppError> {
+    let mut stmt = conn.prepare("SELECT tag_id, is_enabled FROM preset_tag_selectors WHERE preset_id = ?1 ORDER BY position ASC")?;
+    let selectors = stmt.query_map(params![preset_id], |row| Ok(PresetTagSelector { tag_id: row.get(0)?, is_enabled: row.get::<_, i64>(1)? == 1 }))?
+        .collect::<SqlResult<Vec<_>>>()?;
+    Ok(selectors)
+}
+
+fn fetch_preset_layer_ids(conn: &Connection, preset_id: i64) -> Result<Vec<i64>, AppError> {
+    let mut stmt = conn.prepare("SELECT included_preset_id FROM preset_layers WHERE preset_id = ?1 ORDER BY position ASC")?;
+    let ids = stmt.query_map(params![preset_id], |row| row.get(0))?
+        .collect::<SqlResult<Vec<i64>>>()?;
+    Ok(ids)
+}
+
+fn load_preset_layers(conn: &Connection, presets: &mut [Preset]) -> Result<(), AppError> {
+    for preset in presets.iter_mut() {
+        preset.included_preset_ids = fetch_preset_layer_ids(conn, preset.id)?;
+        preset.tag_selectors = fetch_preset_tag_selectors(conn, preset.id)?;
+    }
+    Ok(())
+}
+
+fn fetch_preset(conn: &Connection, preset_id: i64) -> Result<Preset, AppError> {
+    let mut preset = conn.query_row(
+        &format!("SELECT {} FROM presets WHERE id = ?1", PRESET_COLUMNS),
+        params![preset_id],
+        preset_from_row,
+    ).optional()?
+     .ok_or_else(|| AppError::NotFound(format!("Preset with ID {} not found.", preset_id)))?;
+    preset.included_preset_ids = fetch_preset_layer_ids(conn, preset_id)?;
+    preset.tag_selectors = fetch_preset_tag_selectors(conn, preset_id)?;
+    Ok(preset)
+}
+
+// Validates mode/scope input from the frontend. A missing mode means overlay (the historical behaviour).
+fn validate_preset_settings(
+    conn: &Connection,
+    mode: Option<String>,
+    scope_entity_id: Option<i64>,
+    scope_category_id: Option<i64>,
+) -> Result<String, String> {
+    let mode = mode.map(|m| m.trim().to_lowercase()).unwrap_or_else(|| PRESET_MODE_OVERLAY.to_string());
+    if mode != PRESET_MODE_STRICT && mode != PRESET_MODE_OVERLAY {
+        return Err(format!("Invalid preset mode '{}'. Expected '{}' or '{}'.", mode, PRESET_MODE_STRICT, PRESET_MODE_OVERLAY));
+    }
+    if scope_entity_id.is_some() && scope_category_id.is_some() {
+        return Err("A preset can be scoped to an entity or a category, not both.".to_string());
+    }
+    if let Some(entity_id) = scope_entity_id {
+        let exists: Option<i64> = conn.query_row("SELECT id FROM entities WHERE id = ?1", params![entity_id], |row| row.get(0))
+            .optional().map_err(|e| format!("DB error checking scope entity: {}", e))?;
+        if exists.is_none() {
+            return Err(format!("Scope entity with ID {} not found.", entity_id));
+        }
+    }
+    if let Some(category_id) = scope_category_id {
+        let exists: Option<i64> = conn.query_row("SELECT id FROM categories WHERE id = ?1", params![category_id], |row| row.get(0))
+            .optional().map_err(|e| format!("DB error checking scope category: {}", e))?;
+        if exists.is_none() {
+            return Err(format!("Scope category with ID {} not found.", category_id));
+        }
+    }
+    Ok(mode)
+}
+
 #[command]
-fn create_preset(name: String, db_state: State<DbState>) -> CmdResult<Preset> {
+fn create_preset(
+    name: String,
+    mode: Option<String>,
+    scope_entity_id: Option<i64>,
+    scope_category_id: Option<i64>,
+    db_state: State<DbState>
+) -> CmdResult<Preset> {
     let name = name.trim();
     if name.is_empty() {
         return Err("Preset name cannot be empty.".to_string());
     }
-    println!("[create_preset] Attempting to create preset: '{}'", name);
+    println!("[create_preset] Attempting to create preset: '{}' (mode: {:?}, entity scope: {:?}, category scope: {:?})", name, mode, scope_entity_id, scope_category_id);
 
     let base_mods_path = get_mods_base_path_from_settings(&db_state)
         .map_err(|e| format!("Cannot create preset: {}", e))?;
@@ -3666,6 +4747,8 @@ fn create_preset(name: String, db_state: State<DbState>) -> CmdResult<Preset> {
     let conn_guard = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
     let mut conn = conn_guard;
 
+    let mode = validate_preset_settings(&conn, mode, scope_entity_id, scope_category_id)?;
+
     // Use a block scope for the transaction
     let preset_id = { // Start block scope for tx
         let tx = conn.transaction().map_err(|e| format!("Failed to start transaction: {}", e))?;
@@ -3683,19 +4766,25 @@ fn create_preset(name: String, db_state: State<DbState>) -> CmdResult<Preset> {
         }
 
         // Insert new preset
-        tx.execute("INSERT INTO presets (name) VALUES (?1)", params![name])
-            .map_err(|e| format!("Failed to insert preset: {}", e))?;
+        tx.execute(
+            "INSERT INTO presets (name, mode, scope_entity_id, scope_category_id) VALUES (?1, ?2, ?3, ?4)",
+            params![name, mode, scope_entity_id, scope_category_id],
+        ).map_err(|e| format!("Failed to insert preset: {}", e))?;
         let new_preset_id = tx.last_insert_rowid();
         println!("[create_preset] Inserted preset with ID: {}", new_preset_id);
 
         // Use another block scope for the statement and iteration
         { // Start block scope for stmt
-            let mut stmt = tx.prepare("SELECT id, folder_name FROM assets")
-                .map_err(|e| format!("Failed to prepare asset fetch: {}", e))?;
-            let asset_iter_result = stmt.query_map([], |row| {
+            // Only snapshot assets inside the preset's scope
+            let mut stmt = tx.prepare(
+                "SELECT a.id, a.folder_name, a.root_id FROM assets a JOIN entities e ON a.entity_id = e.id
+                 WHERE a.missing_since IS NULL AND (?1 IS NULL OR a.entity_id = ?1) AND (?2 IS NULL OR e.category_id = ?2)"
+            ).map_err(|e| format!("Failed to prepare asset fetch: {}", e))?;
+            let asset_iter_result = stmt.query_map(params![scope_entity_id, scope_category_id], |row| {
                 Ok((
                     row.get::<_, i64>(0)?,
                     row.get::<_, String>(1)?.replace("\\", "/"),
+                    row.get::<_, Option<i64>>(2)?,
                 ))
             });
 
@@ -3703,28 +4792,16 @@ fn create_preset(name: String, db_state: State<DbState>) -> CmdResult<Preset> {
                 Ok(asset_iter) => {
                     for asset_result in asset_iter {
                         match asset_result {
-                            Ok((asset_id, clean_relative_path_str)) => {
-                                let clean_relative_path = PathBuf::from(&clean_relative_path_str);
-                                let filename_osstr = clean_relative_path.file_name().unwrap_or_default();
-                                let filename_str = filename_osstr.to_string_lossy();
-                                if filename_str.is_empty() { continue; }
-
-                                let disabled_filename = format!("{}{}", DISABLED_PREFIX, filename_str);
-                                let relative_parent_path = clean_relative_path.parent();
-
-                                let full_path_if_enabled = base_mods_path.join(&clean_relative_path);
-                                let full_path_if_disabled = match relative_parent_path {
-                                    Some(parent) if parent.as_os_str().len() > 0 => base_mods_path.join(parent).join(&disabled_filename),
-                                    _ => base_mods_path.join(&disabled_filename),
+                            Ok((asset_id, clean_relative_path_str, root_id)) => {
+                                let is_currently_enabled = match locate_asset_folder(&base_mods_path, root_id, &clean_relative_path_str) {
+                                    Some((_, true)) => 1,
+                                    Some((_, false)) => 0,
+                                    None => {
+                                        println!("[create_preset] Warning: Asset ID {} folder not found on disk during preset save (path: {}). Skipping.", asset_id, clean_relative_path_str);
+                                        continue;
+                                    }
                                 };
 
-                                let is_currently_enabled = if full_path_if_enabled.is_dir() { 1 }
-                                                            else if full_path_if_disabled.is_dir() { 0 }
-                                                            else {
-                                                                println!("[create_preset] Warning: Asset ID {} folder not found on disk during preset save (path: {}). Skipping.", asset_id, clean_relative_path_str);
-                                                                continue;
-                                                            };
-
                                 tx.execute(
                                     "INSERT INTO preset_assets (preset_id, asset_id, is_enabled) VALUES (?1, ?2, ?3)",
                                     params![new_preset_id, asset_id, is_currently_enabled],
@@ -3738,47 +4815,105 @@ fn create_preset(name: String, db_state: State<DbState>) -> CmdResult<Preset> {
             }
         } // End block scope for stmt - stmt is dropped here, releasing borrow on tx
 
+        // Snapshot mod toggle states (d3dx_user.ini) alongside the folder states
+        if let Err(e) = capture_preset_variables(&tx, new_preset_id, &base_mods_path) {
+            eprintln!("[create_preset] Warning: Failed to save persisted variables: {}", e);
+        }
+        if let Err(e) = capture_preset_ini_states(&tx, new_preset_id, &base_mods_path) {
+            eprintln!("[create_preset] Warning: Failed to save INI states: {}", e);
+        }
+
         // Commit the transaction
         tx.commit().map_err(|e| format!("Failed to commit transaction: {}", e))?;
 
         new_preset_id // Return the ID from the block
     }; // End block scope for tx
+    sync_asset_sidecars(&conn, &preset_member_ids(&conn, preset_id));
 
     println!("[create_preset] Preset '{}' created successfully.", name);
 
-    Ok(Preset { id: preset_id, name: name.to_string(), is_favorite: false })
+    Ok(Preset { id: preset_id, name: name.to_string(), is_favorite: false, mode, scope_entity_id, scope_category_id, included_preset_ids: Vec::new(), tag_selectors: Vec::new() })
+}
+
+#[command]
+fn update_preset_settings(
+    preset_id: i64,
+    mode: String,
+    scope_entity_id: Option<i64>,
+    scope_category_id: Option<i64>,
+    db_state: State<DbState>
+) -> CmdResult<Preset> {
+    println!("[update_preset_settings] Preset ID {}: mode '{}', entity scope {:?}, category scope {:?}", preset_id, mode, scope_entity_id, scope_category_id);
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let mode = validate_preset_settings(&conn, Some(mode), scope_entity_id, scope_category_id)?;
+
+    let changes = conn.execute(
+        "UPDATE presets SET mode = ?1, scope_entity_id = ?2, scope_category_id = ?3 WHERE id = ?4",
+        params![mode, scope_entity_id, scope_category_id, preset_id],
+    ).map_err(|e| format!("Failed to update preset settings: {}", e))?;
+    if changes == 0 {
+        return Err(format!("Preset with ID {} not found.", preset_id));
+    }
+
+    fetch_preset(&conn, preset_id).map_err(|e| e.to_string())
+}
+
+#[command]
+fn set_preset_layers(preset_id: i64, included_preset_ids: Vec<i64>, db_state: State<DbState>) -> CmdResult<Preset> {
+    println!("[set_preset_layers] Setting layers of preset ID {} to {:?}", preset_id, included_preset_ids);
+    let mut conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    fetch_preset(&conn, preset_id).map_err(|e| e.to_string())?;
+
+    let mut seen = HashSet::new();
+    for included_id in &included_preset_ids {
+        if !seen.insert(*included_id) {
+            return Err(format!("Preset ID {} is included more than once.", included_id));
+        }
+        let included = fetch_preset(&conn, *included_id).map_err(|e| e.to_string())?;
+        if preset_layer_reaches(&conn, *included_id, preset_id).map_err(|e| e.to_string())? {
+            return Err(format!("Cannot include '{}': it already includes this preset, which would create a cycle.", included.name));
+        }
+    }
+
+    let tx = conn.transaction().map_err(|e| format!("Failed to start transaction: {}", e))?;
+    tx.execute("DELETE FROM preset_layers WHERE preset_id = ?1", params![preset_id])
+        .map_err(|e| format!("Failed to clear preset layers: {}", e))?;
+    { // Scope for the statement
+        let mut insert_stmt = tx.prepare(
+            "INSERT INTO preset_layers (preset_id, included_preset_id, position) VALUES (?1, ?2, ?3)"
+        ).map_err(|e| format!("Failed to prepare layer insert: {}", e))?;
+        for (position, included_id) in included_preset_ids.iter().enumerate() {
+            insert_stmt.execute(params![preset_id, included_id, position as i64])
+                .map_err(|e| format!("Failed to save layer {}: {}", included_id, e))?;
+        }
+    }
+    tx.commit().map_err(|e| format!("Failed to commit transaction: {}", e))?;
+
+    fetch_preset(&conn, preset_id).map_err(|e| e.to_string())
 }
 
 
 #[command]
 fn get_presets(db_state: State<DbState>) -> CmdResult<Vec<Preset>> {
     let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
-    let mut stmt = conn.prepare("SELECT id, name, is_favorite FROM presets ORDER BY name ASC")
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM presets ORDER BY name ASC", PRESET_COLUMNS))
         .map_err(|e| e.to_string())?;
-    let preset_iter = stmt.query_map([], |row| {
-        Ok(Preset {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            is_favorite: row.get::<_, i64>(2)? == 1,
-        })
-    }).map_err(|e| e.to_string())?;
-    preset_iter.collect::<SqlResult<Vec<Preset>>>().map_err(|e| e.to_string())
+    let preset_iter = stmt.query_map([], preset_from_row).map_err(|e| e.to_string())?;
+    let mut presets = preset_iter.collect::<SqlResult<Vec<Preset>>>().map_err(|e| e.to_string())?;
+    load_preset_layers(&conn, &mut presets).map_err(|e| e.to_string())?;
+    Ok(presets)
 }
 
 #[command]
 fn get_favorite_presets(db_state: State<DbState>) -> CmdResult<Vec<Preset>> {
     let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
     let mut stmt = conn.prepare(
-        "SELECT id, name, is_favorite FROM presets WHERE is_favorite = 1 ORDER BY name ASC LIMIT 3"
+        &format!("SELECT {} FROM presets WHERE is_favorite = 1 ORDER BY name ASC LIMIT 3", PRESET_COLUMNS)
     ).map_err(|e| e.to_string())?;
-    let preset_iter = stmt.query_map([], |row| {
-        Ok(Preset {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            is_favorite: row.get::<_, i64>(2)? == 1,
-        })
-    }).map_err(|e| e.to_string())?;
-    preset_iter.collect::<SqlResult<Vec<Preset>>>().map_err(|e| e.to_string())
+    let preset_iter = stmt.query_map([], preset_from_row).map_err(|e| e.to_string())?;
+    let mut presets = preset_iter.collect::<SqlResult<Vec<Preset>>>().map_err(|e| e.to_string())?;
+    load_preset_layers(&conn, &mut presets).map_err(|e| e.to_string())?;
+    Ok(presets)
 }
 
 #[command]
@@ -3792,42 +4927,68 @@ async fn apply_preset(preset_id: i64, db_state: State<'_, DbState>, app_handle:
     let base_mods_path = get_mods_base_path_from_settings(&db_state)
         .map_err(|e| format!("Cannot apply preset: {}", e))?;
 
-    // --- Fetch preset assets ---
-    let preset_assets_to_apply = { // Use block scope for connection lock
+    // --- Fetch preset assets (resolved for mode and scope) ---
+    let (preset, preset_assets_to_apply) = { // Use block scope for connection lock
         let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
-        let mut stmt = conn.prepare(
-            "SELECT pa.asset_id, pa.is_enabled, a.folder_name, a.name
-             FROM preset_assets pa
-             JOIN assets a ON pa.asset_id = a.id
-             WHERE pa.preset_id = ?1"
-        ).map_err(|e| format!("Failed to prepare fetch for preset assets: {}", e))?;
-
-        let preset_assets_iter_result = stmt.query_map(params![preset_id], |row| {
-            Ok((
-                row.get::<_, i64>(0)?,                   // asset_id
-                row.get::<_, i64>(1)? == 1,              // desired_is_enabled (bool)
-                row.get::<_, String>(2)?.replace("\\", "/"), // clean_relative_path
-                row.get::<_, String>(3)?,               // asset_name
-            ))
-        });
-
-        match preset_assets_iter_result {
-             Ok(iter) => iter.collect::<SqlResult<Vec<(i64, bool, String, String)>>>() // Include name
-                              .map_err(|e| format!("Failed to collect preset assets: {}", e))?,
-             Err(e) => return Err(format!("Error preparing preset asset iterator: {}", e)),
-        }
+        let preset = fetch_preset(&conn, preset_id).map_err(|e| e.to_string())?;
+        let states = resolve_preset_target_states(&conn, &preset)
+            .map_err(|e| format!("Failed to fetch preset assets: {}", e))?;
+        (preset, states)
     }; // Connection lock released here
 
     let total_assets = preset_assets_to_apply.len();
     println!("[apply_preset] Found {} assets in preset.", total_assets);
 
+    let (mut errors, asset_changes) = apply_asset_states(&app_handle, &base_mods_path, preset_assets_to_apply);
+
+    // --- Restore per-INI states and persisted mod variables stored with the preset ---
+    {
+        let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+        let mut ini_changes = Vec::new();
+        match restore_preset_ini_states(&conn, &preset, &base_mods_path, &mut ini_changes, &mut errors) {
+            Ok(count) => println!("[apply_preset] Toggled {} INI files.", count),
+            Err(e) => errors.push(format!("Failed to restore INI states: {}", e)),
+        }
+        match restore_preset_variables(&conn, &preset, &base_mods_path) {
+            Ok(count) => println!("[apply_preset] Restored {} persisted variables.", count),
+            Err(e) => errors.push(format!("Failed to restore persisted variables: {}", e)),
+        }
+        // Persisted variables are not journaled; undo only restores folder and INI states
+        record_enabled_at(&conn, &asset_changes);
+        journal_operation(&conn, &format!("Applied preset '{}'", preset.name), &JournalOp::SetEnabled { assets: asset_changes, inis: ini_changes });
+    }
+
+    println!("[apply_preset] Finished applying preset ID {}. Errors: {}", preset_id, errors.len());
+
+    if errors.is_empty() {
+        // --- Emit COMPLETE event ---
+        let summary = format!("Successfully applied preset ({} mods processed).", total_assets);
+        app_handle.emit_all(PRESET_APPLY_COMPLETE_EVENT, &summary).ok();
+        Ok(())
+    } else {
+        // --- Emit ERROR event ---
+        let combined_errors = errors.join("\n");
+        let error_summary = format!("Preset application completed with {} error(s).", errors.len());
+        // You might want to send the full errors separately or just the summary
+        app_handle_clone.emit_all(PRESET_APPLY_ERROR_EVENT, &error_summary).ok();
+        Err(format!("{}\nDetails:\n{}", error_summary, combined_errors)) // Return error details too
+    }
+}
+
+// Shared filesystem pass behind apply_preset and shuffle_assets.
+// Emits the preset://apply_start/progress events and returns the per-asset errors and the applied changes.
+fn apply_asset_states(app_handle: &AppHandle, base_mods_path: &Path, states: Vec<PresetAssetState>) -> (Vec<String>, Vec<AssetStateChange>) {
+    let total_assets = states.len();
+
     // --- Emit START event ---
     app_handle.emit_all(PRESET_APPLY_START_EVENT, total_assets).ok();
 
     let mut processed_count = 0;
     let mut errors = Vec::new();
+    let mut changes = Vec::new();
 
-    for (asset_id, desired_is_enabled, clean_relative_path_str, asset_name) in preset_assets_to_apply {
+    for state in states {
+        let PresetAssetState { asset_id, is_enabled: desired_is_enabled, clean_relative_path: clean_relative_path_str, name: asset_name, implied, root_id, .. } = state;
         processed_count += 1;
 
         // --- Emit PROGRESS event ---
@@ -3838,63 +4999,32 @@ async fn apply_preset(preset_id: i64, db_state: State<'_, DbState>, app_handle:
             current_asset_id: Some(asset_id),
             message: progress_message.clone(),
         }).ok();
-        println!("[apply_preset] {}", progress_message); // Also log to console
+        println!("[apply_asset_states] {}", progress_message); // Also log to console
 
         // --- Filesystem logic ---
-        let clean_relative_path = PathBuf::from(&clean_relative_path_str);
-        let filename_osstr = clean_relative_path.file_name().unwrap_or_default();
-        let filename_str = filename_osstr.to_string_lossy();
-        if filename_str.is_empty() {
-            let err_msg = format!("Skipping asset ID {}: Invalid folder name '{}'.", asset_id, clean_relative_path_str);
-            println!("[apply_preset] {}", err_msg);
-            errors.push(err_msg);
-            continue;
-        }
-
-        let enabled_filename = filename_str.to_string();
-        let disabled_filename = format!("{}{}", DISABLED_PREFIX, filename_str);
-        let relative_parent_path = clean_relative_path.parent();
-
-        let construct_full_path = |name: &str| -> PathBuf {
-            match relative_parent_path {
-                Some(parent) if parent.as_os_str().len() > 0 => base_mods_path.join(parent).join(name),
-                _ => base_mods_path.join(name),
+        let (backend, current_is_enabled) = match locate_asset_backend(base_mods_path, root_id, &clean_relative_path_str) {
+            Some(found) => found,
+            None if implied => {
+                // Unlisted asset of a strict preset is already gone, nothing to disable
+                println!("[apply_asset_states] Unlisted asset '{}' (ID {}) not found on disk. Nothing to disable.", asset_name, asset_id);
+                continue;
+            }
+            None => {
+                let err_msg = format!("Skipping asset '{}' (ID {}): Folder not found on disk (path: '{}').", asset_name, asset_id, clean_relative_path_str);
+                println!("[apply_asset_states] {}", err_msg);
+                errors.push(err_msg);
+                continue;
             }
         };
 
-        let full_path_if_enabled = construct_full_path(&enabled_filename);
-        let full_path_if_disabled = construct_full_path(&disabled_filename);
-
-        let current_path_on_disk: Option<PathBuf>;
-        let current_is_enabled: bool;
-
-        if full_path_if_enabled.is_dir() {
-            current_path_on_disk = Some(full_path_if_enabled);
-            current_is_enabled = true;
-        } else if full_path_if_disabled.is_dir() {
-            current_path_on_disk = Some(full_path_if_disabled);
-            current_is_enabled = false;
-        } else {
-            let err_msg = format!("Skipping asset '{}' (ID {}): Folder not found on disk (path: '{}').", asset_name, asset_id, clean_relative_path_str);
-            println!("[apply_preset] {}", err_msg);
-            errors.push(err_msg);
-            continue;
-        }
-
         if current_is_enabled != desired_is_enabled {
-            let target_path = if desired_is_enabled {
-                construct_full_path(&enabled_filename)
-            } else {
-                construct_full_path(&disabled_filename)
-            };
-            let source_path = current_path_on_disk.unwrap();
-            println!("[apply_preset] Renaming '{}' -> '{}' (Desired Enabled: {})", source_path.display(), target_path.display(), desired_is_enabled);
-            match fs::rename(&source_path, &target_path) {
-                Ok(_) => { /* Success */ }
+            println!("[apply_asset_states] Setting '{}' enabled = {} ({} mode)", clean_relative_path_str, desired_is_enabled, backend.mode());
+            match backend.set_enabled(&clean_relative_path_str, desired_is_enabled) {
+                Ok(()) => changes.push(AssetStateChange { asset_id, is_enabled: desired_is_enabled }),
                 Err(e) => {
-                     let err_msg = format!("Failed to rename asset '{}' (ID {}): {}", asset_name, asset_id, e);
-                     println!("[apply_preset] Error: {}", err_msg);
-                     errors.push(err_msg);
+                    let err_msg = format!("Failed to toggle asset '{}' (ID {}): {}", asset_name, asset_id, e);
+                    println!("[apply_asset_states] Error: {}", err_msg);
+                    errors.push(err_msg);
                 }
             }
         }
@@ -3902,487 +5032,4121 @@ async fn apply_preset(preset_id: i64, db_state: State<'_, DbState>, app_handle:
         // tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
     } // End loop
 
-    println!("[apply_preset] Finished applying preset ID {}. Errors: {}", preset_id, errors.len());
+    (errors, changes)
+}
 
-    if errors.is_empty() {
-        // --- Emit COMPLETE event ---
-        let summary = format!("Successfully applied preset ({} mods processed).", total_assets);
-        app_handle.emit_all(PRESET_APPLY_COMPLETE_EVENT, &summary).ok();
-        Ok(())
-    } else {
-        // --- Emit ERROR event ---
-        let combined_errors = errors.join("\n");
-        let error_summary = format!("Preset application completed with {} error(s).", errors.len());
-        // You might want to send the full errors separately or just the summary
-        app_handle_clone.emit_all(PRESET_APPLY_ERROR_EVENT, &error_summary).ok();
-        Err(format!("{}\nDetails:\n{}", error_summary, combined_errors)) // Return error details too
+// Helper to read the stored asset states of a preset (Internal error type)
+fn fetch_preset_asset_states(conn: &Connection, preset_id: i64) -> Result<Vec<PresetAssetState>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT pa.asset_id, pa.is_enabled, a.folder_name, a.name, e.slug, a.root_id
+         FROM preset_assets pa
+         JOIN assets a ON pa.asset_id = a.id
+         JOIN entities e ON a.entity_id = e.id
+         WHERE pa.preset_id = ?1 AND a.missing_since IS NULL"
+    )?;
+    let state_iter = stmt.query_map(params![preset_id], |row| {
+        Ok(PresetAssetState {
+            asset_id: row.get(0)?,
+            is_enabled: row.get::<_, i64>(1)? == 1,
+            clean_relative_path: row.get::<_, String>(2)?.replace("\\", "/"),
+            name: row.get(3)?,
+            entity_slug: row.get(4)?,
+            implied: false,
+            root_id: row.get(5)?,
+        })
+    })?;
+    Ok(state_iter.collect::<SqlResult<Vec<PresetAssetState>>>()?)
+}
+
+// Resolves the states a single preset applies on its own, honouring its scope and mode.
+// Listed assets outside the scope are ignored; strict presets additionally disable
+// every in-scope asset they don't list (returned with `implied = true`).
+fn resolve_own_preset_states(conn: &Connection, preset: &Preset) -> Result<Vec<PresetAssetState>, AppError> {
+    let mut states = {
+        let mut stmt = conn.prepare(
+            "SELECT pa.asset_id, pa.is_enabled, a.folder_name, a.name, e.slug, a.root_id
+             FROM preset_assets pa
+             JOIN assets a ON pa.asset_id = a.id
+             JOIN entities e ON a.entity_id = e.id
+             WHERE pa.preset_id = ?1 AND a.missing_since IS NULL
+               AND (?2 IS NULL OR a.entity_id = ?2) AND (?3 IS NULL OR e.category_id = ?3)"
+        )?;
+        let state_iter = stmt.query_map(params![preset.id, preset.scope_entity_id, preset.scope_category_id], |row| {
+            Ok(PresetAssetState {
+                asset_id: row.get(0)?,
+                is_enabled: row.get::<_, i64>(1)? == 1,
+                clean_relative_path: row.get::<_, String>(2)?.replace("\\", "/"),
+                name: row.get(3)?,
+                entity_slug: row.get(4)?,
+                implied: false,
+                root_id: row.get(5)?,
+            })
+        })?;
+        state_iter.collect::<SqlResult<Vec<PresetAssetState>>>()?
+    };
+
+    // Tag selectors cover the in-scope tagged assets the preset doesn't list itself
+    if !preset.tag_selectors.is_empty() {
+        let listed: HashSet<i64> = states.iter().map(|s| s.asset_id).collect();
+        let mut stmt = conn.prepare(
+            "SELECT a.id, a.folder_name, a.name, e.slug, a.root_id
+             FROM asset_tags ast
+             JOIN assets a ON ast.asset_id = a.id
+             JOIN entities e ON a.entity_id = e.id
+             WHERE ast.tag_id = ?1 AND a.missing_since IS NULL
+               AND (?2 IS NULL OR a.entity_id = ?2) AND (?3 IS NULL OR e.category_id = ?3)"
+        )?;
+        let mut selected: Vec<PresetAssetState> = Vec::new();
+        let mut index_by_asset: HashMap<i64, usize> = HashMap::new();
+        for selector in &preset.tag_selectors {
+            let tagged = stmt.query_map(params![selector.tag_id, preset.scope_entity_id, preset.scope_category_id], |row| {
+                Ok(PresetAssetState {
+                    asset_id: row.get(0)?,
+                    is_enabled: selector.is_enabled,
+                    clean_relative_path: row.get::<_, String>(1)?.replace("\\", "/"),
+                    name: row.get(2)?,
+                    entity_slug: row.get(3)?,
+                    implied: false,
+                    root_id: row.get(4)?,
+                })
+            })?.collect::<SqlResult<Vec<PresetAssetState>>>()?;
+            for state in tagged.into_iter().filter(|s| !listed.contains(&s.asset_id)) {
+                match index_by_asset.get(&state.asset_id) {
+                    Some(&index) => selected[index] = state, // Later selectors win
+                    None => {
+                        index_by_asset.insert(state.asset_id, selected.len());
+                        selected.push(state);
+                    }
+                }
+            }
+        }
+        println!("[resolve_own_preset_states] Preset {}: {} assets selected by tag.", preset.id, selected.len());
+        states.extend(selected);
+    }
+
+    if preset.mode == PRESET_MODE_STRICT {
+        let mut stmt = conn.prepare(
+            "SELECT a.id, a.folder_name, a.name, e.slug, a.root_id
+             FROM assets a
+             JOIN entities e ON a.entity_id = e.id
+             WHERE a.missing_since IS NULL AND (?2 IS NULL OR a.entity_id = ?2) AND (?3 IS NULL OR e.category_id = ?3)
+               AND a.id NOT IN (SELECT asset_id FROM preset_assets WHERE preset_id = ?1)"
+        )?;
+        let implied_iter = stmt.query_map(params![preset.id, preset.scope_entity_id, preset.scope_category_id], |row| {
+            Ok(PresetAssetState {
+                asset_id: row.get(0)?,
+                is_enabled: false,
+                clean_relative_path: row.get::<_, String>(1)?.replace("\\", "/"),
+                name: row.get(2)?,
+                entity_slug: row.get(3)?,
+                implied: true,
+                root_id: row.get(4)?,
+            })
+        })?;
+        let covered: HashSet<i64> = states.iter().map(|s| s.asset_id).collect();
+        let implied_states: Vec<PresetAssetState> = implied_iter.collect::<SqlResult<Vec<PresetAssetState>>>()?
+            .into_iter().filter(|s| !covered.contains(&s.asset_id)).collect();
+        println!("[resolve_own_preset_states] Strict preset {}: {} unlisted in-scope assets will be disabled.", preset.id, implied_states.len());
+        states.extend(implied_states);
     }
+
+    Ok(states)
 }
 
+// Resolves the effective states of a preset including its layers.
+// Layers are applied in order and the preset's own assets come last; a later layer wins
+// over an earlier one, including the implied disables of a strict layer.
+fn resolve_preset_target_states(conn: &Connection, preset: &Preset) -> Result<Vec<PresetAssetState>, AppError> {
+    let mut visiting = Vec::new();
+    let mut merged: Vec<PresetAssetState> = Vec::new();
+    let mut index_by_asset: HashMap<i64, usize> = HashMap::new();
+    collect_layered_preset_states(conn, preset, &mut visiting, &mut merged, &mut index_by_asset)?;
+    Ok(merged)
+}
 
-#[command]
-fn toggle_preset_favorite(preset_id: i64, is_favorite: bool, db_state: State<DbState>) -> CmdResult<()> {
-    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
-    let fav_value = if is_favorite { 1 } else { 0 };
-    conn.execute(
-        "UPDATE presets SET is_favorite = ?1 WHERE id = ?2",
-        params![fav_value, preset_id],
-    )
-    .map_err(|e| format!("Failed to update favorite status: {}", e))?;
+fn collect_layered_preset_states(
+    conn: &Connection,
+    preset: &Preset,
+    visiting: &mut Vec<i64>,
+    merged: &mut Vec<PresetAssetState>,
+    index_by_asset: &mut HashMap<i64, usize>,
+) -> Result<(), AppError> {
+    if visiting.contains(&preset.id) {
+        return Err(AppError::Config(format!("Preset layers form a cycle (preset ID {} includes itself).", preset.id)));
+    }
+    visiting.push(preset.id);
+
+    for layer_id in &preset.included_preset_ids {
+        let layer = fetch_preset(conn, *layer_id)?;
+        collect_layered_preset_states(conn, &layer, visiting, merged, index_by_asset)?;
+    }
+
+    for state in resolve_own_preset_states(conn, preset)? {
+        match index_by_asset.get(&state.asset_id) {
+            Some(&existing_index) => merged[existing_index] = state,
+            None => {
+                index_by_asset.insert(state.asset_id, merged.len());
+                merged.push(state);
+            }
+        }
+    }
+
+    visiting.pop();
     Ok(())
 }
 
-#[command]
-fn delete_preset(preset_id: i64, db_state: State<DbState>) -> CmdResult<()> {
-    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
-    // Foreign key cascade should delete from preset_assets automatically
-    let changes = conn.execute("DELETE FROM presets WHERE id = ?1", params![preset_id])
-                      .map_err(|e| format!("Failed to delete preset: {}", e))?;
-    if changes == 0 {
-        Err(format!("Preset with ID {} not found.", preset_id))
-    } else {
-        Ok(())
+// Returns true if `candidate_id` is `preset_id` or (transitively) includes it
+fn preset_layer_reaches(conn: &Connection, candidate_id: i64, preset_id: i64) -> Result<bool, AppError> {
+    let mut stack = vec![candidate_id];
+    let mut seen = HashSet::new();
+    while let Some(current) = stack.pop() {
+        if current == preset_id {
+            return Ok(true);
+        }
+        if seen.insert(current) {
+            stack.extend(fetch_preset_layer_ids(conn, current)?);
+        }
     }
+    Ok(false)
 }
 
-// --- Command to get Dashboard Stats ---
 #[command]
-fn get_dashboard_stats(db_state: State<DbState>) -> CmdResult<DashboardStats> {
-    let base_mods_path = match get_mods_base_path_from_settings(&db_state) {
-        Ok(p) => p,
-        Err(_) => {
-             // If base path isn't set, return default zeroed stats
-            return Ok(DashboardStats {
-                total_mods: 0,
-                enabled_mods: 0,
-                disabled_mods: 0,
-                uncategorized_mods: 0,
-                category_counts: HashMap::new(),
-            });
-        }
-    };
+fn preview_preset_apply(preset_id: i64, compare_preset_id: Option<i64>, db_state: State<DbState>) -> CmdResult<PresetApplyPreview> {
+    println!("[preview_preset_apply] Previewing preset ID: {} (compare to: {:?})", preset_id, compare_preset_id);
 
     let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
 
-    // 1. Total Mods
-    let total_mods = conn.query_row("SELECT COUNT(*) FROM assets", [], |row| row.get::<_, i64>(0))
-                         .map_err(|e| format!("Failed to get total mod count: {}", e))?;
+    let preset = fetch_preset(&conn, preset_id).map_err(|e| e.to_string())?;
+    let target_states = resolve_preset_target_states(&conn, &preset)
+        .map_err(|e| format!("Failed to fetch preset assets: {}", e))?;
+    let target_ids: HashSet<i64> = target_states.iter().map(|s| s.asset_id).collect();
 
-    // 2. Uncategorized Mods
-    let uncategorized_mods = conn.query_row(
-        "SELECT COUNT(a.id) FROM assets a JOIN entities e ON a.entity_id = e.id WHERE e.slug LIKE '%-other'",
-        [],
-        |row| row.get::<_, i64>(0)
-    ).map_err(|e| format!("Failed to get uncategorized mod count: {}", e))?;
+    let to_entry = |state: &PresetAssetState| PresetPreviewEntry {
+        asset_id: state.asset_id,
+        name: state.name.clone(),
+        entity_slug: state.entity_slug.clone(),
+        folder_name: state.clean_relative_path.clone(),
+    };
 
-    // 3. Category Counts
-    let mut category_counts = HashMap::new();
-    let mut cat_stmt = conn.prepare(
-        "SELECT c.name, COUNT(a.id)
-         FROM categories c
-         JOIN entities e ON c.id = e.category_id
-         JOIN assets a ON e.id = a.entity_id
-         GROUP BY c.name
-         HAVING COUNT(a.id) > 0" // Only include categories with mods
-    ).map_err(|e| format!("Failed to prepare category count query: {}", e))?;
+    let mut preview = PresetApplyPreview {
+        preset_id,
+        compared_to_preset_id: compare_preset_id,
+        to_enable: Vec::new(),
+        to_disable: Vec::new(),
+        unchanged_count: 0,
+        missing: Vec::new(),
+        not_covered: Vec::new(),
+    };
 
-    let cat_rows = cat_stmt.query_map([], |row| {
-        Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
-    }).map_err(|e| format!("Failed to execute category count query: {}", e))?;
+    // --- Build the baseline state (other preset or disk) ---
+    let baseline: HashMap<i64, bool> = if let Some(other_id) = compare_preset_id {
+        let other_preset = fetch_preset(&conn, other_id).map_err(|e| e.to_string())?;
+        let other_states = resolve_preset_target_states(&conn, &other_preset)
+            .map_err(|e| format!("Failed to fetch assets of preset {}: {}", other_id, e))?;
+        for other in other_states.iter().filter(|s| !target_ids.contains(&s.asset_id)) {
+            preview.not_covered.push(to_entry(other));
+        }
+        other_states.into_iter().map(|s| (s.asset_id, s.is_enabled)).collect()
+    } else {
+        let base_mods_path = PathBuf::from(
+            get_setting_value(&conn, SETTINGS_KEY_MODS_FOLDER)
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| "Mods folder path not set".to_string())?
+        );
 
-    for row_result in cat_rows {
-        match row_result {
-            Ok((name, count)) => { category_counts.insert(name, count); }
-            Err(e) => { eprintln!("[get_dashboard_stats] Error processing category count row: {}", e); }
+        // Current state of every asset in the library, read from disk
+        let mut library_states = HashMap::new();
+        let mut stmt = conn.prepare(
+            "SELECT a.id, a.name, a.folder_name, e.slug, a.root_id FROM assets a JOIN entities e ON a.entity_id = e.id WHERE a.missing_since IS NULL"
+        ).map_err(|e| format!("Failed to prepare asset fetch: {}", e))?;
+        let rows = stmt.query_map([], |row| Ok(PresetAssetState {
+            asset_id: row.get(0)?,
+            is_enabled: false,
+            name: row.get(1)?,
+            clean_relative_path: row.get::<_, String>(2)?.replace("\\", "/"),
+            entity_slug: row.get(3)?,
+            implied: false,
+            root_id: row.get(4)?,
+        })).map_err(|e| format!("Failed to query assets: {}", e))?;
+
+        for row_result in rows {
+            let mut state = match row_result {
+                Ok(s) => s,
+                Err(e) => { eprintln!("[preview_preset_apply] Error reading asset row: {}", e); continue; }
+            };
+            let current = get_current_asset_enabled_state(&conn, state.asset_id, &base_mods_path)
+                .map_err(|e| format!("Failed to read the state of asset {}: {}", state.asset_id, e))?;
+            if let Some(is_enabled) = current {
+                state.is_enabled = is_enabled;
+                if !target_ids.contains(&state.asset_id) {
+                    preview.not_covered.push(to_entry(&state));
+                }
+                library_states.insert(state.asset_id, is_enabled);
+            }
+        }
+        library_states
+    };
+
+    // --- Diff the preset against the baseline ---
+    for state in &target_states {
+        match baseline.get(&state.asset_id) {
+            Some(&current) if current == state.is_enabled => preview.unchanged_count += 1,
+            Some(_) if state.is_enabled => preview.to_enable.push(to_entry(state)),
+            Some(_) => preview.to_disable.push(to_entry(state)),
+            None if state.implied => {} // Unlisted by a strict preset and absent from the baseline, nothing would change
+            None => preview.missing.push(to_entry(state)),
         }
     }
 
-    // 4. Enabled/Disabled Count (Disk Check)
-    let mut enabled_mods = 0;
-    let mut disabled_mods = 0;
-    let mut disk_check_errors = 0;
+    println!("[preview_preset_apply] Enable: {}, Disable: {}, Unchanged: {}, Missing: {}, Not covered: {}",
+        preview.to_enable.len(), preview.to_disable.len(), preview.unchanged_count, preview.missing.len(), preview.not_covered.len());
+    Ok(preview)
+}
 
-    // Fetch folder names for checking
-    let mut asset_folders_stmt = conn.prepare("SELECT folder_name FROM assets")
-        .map_err(|e| format!("Failed to prepare asset folder fetch: {}", e))?;
-    let asset_folder_rows = asset_folders_stmt.query_map([], |row| row.get::<_, String>(0))
-        .map_err(|e| format!("Failed to query asset folders: {}", e))?;
+// --- Preset Export / Import ---
 
-    for folder_result in asset_folder_rows {
-        match folder_result {
-            Ok(clean_relative_path_str) => {
-                 let clean_relative_path = PathBuf::from(clean_relative_path_str.replace("\\", "/"));
-                 let filename_osstr = clean_relative_path.file_name().unwrap_or_default();
-                 let filename_str = filename_osstr.to_string_lossy();
-                 if filename_str.is_empty() { continue; }
-
-                 let disabled_filename = format!("{}{}", DISABLED_PREFIX, filename_str);
-                 let relative_parent_path = clean_relative_path.parent();
-
-                 let full_path_if_enabled = base_mods_path.join(&clean_relative_path);
-                 let full_path_if_disabled = match relative_parent_path {
-                    Some(parent) if parent.as_os_str().len() > 0 => base_mods_path.join(parent).join(&disabled_filename),
-                    _ => base_mods_path.join(&disabled_filename),
-                 };
+fn read_portable_preset(file_path: &Path) -> Result<PortablePreset, AppError> {
+    let content = fs::read_to_string(file_path)?;
+    let is_toml = file_path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+    let preset: PortablePreset = if is_toml {
+        toml::from_str(&content).map_err(|e| AppError::Config(format!("Invalid preset file: {}", e)))?
+    } else {
+        serde_json::from_str(&content)?
+    };
+    if preset.format_version > PORTABLE_PRESET_FORMAT_VERSION {
+        return Err(AppError::Config(format!(
+            "Preset file format version {} is newer than supported version {}. Please update GMM.",
+            preset.format_version, PORTABLE_PRESET_FORMAT_VERSION
+        )));
+    }
+    Ok(preset)
+}
 
-                 if full_path_if_enabled.is_dir() {
-                     enabled_mods += 1;
-                 } else if full_path_if_disabled.is_dir() {
-                     disabled_mods += 1;
-                 } else {
-                     // Folder not found in either state - might have been deleted since last scan
-                     // We don't count it as enabled or disabled.
-                     disk_check_errors += 1;
-                 }
+// (asset_id, asset_name, match_method) for a resolved portable preset entry
+type PortableAssetMatch = (i64, String, &'static str);
+
+// (asset_id, asset_name, clean_relative_path, entity_slug, root_id) of a local asset a portable preset can match
+type LocalPresetAsset = (i64, String, String, String, Option<i64>);
+
+fn fetch_local_preset_assets(conn: &Connection) -> Result<Vec<LocalPresetAsset>, AppError> {
+    let mut stmt = conn.prepare("SELECT a.id, a.name, a.folder_name, e.slug, a.root_id FROM assets a JOIN entities e ON a.entity_id = e.id WHERE a.missing_since IS NULL")?;
+    let local_assets = stmt.query_map([], |row| Ok((
+        row.get::<_, i64>(0)?,
+        row.get::<_, String>(1)?,
+        row.get::<_, String>(2)?.replace("\\", "/"),
+        row.get::<_, String>(3)?,
+        row.get::<_, Option<i64>>(4)?,
+    )))?.collect::<SqlResult<Vec<LocalPresetAsset>>>()?;
+    Ok(local_assets)
+}
+
+// Resolves entries of a portable preset against the local library, in priority order:
+// exact folder path, content fingerprint, then same entity + same mod folder name.
+// Returns one match per entry, None if nothing matched. Hashes mod folders, so call it without the DB lock.
+fn match_portable_preset_assets(
+    local_assets: &[LocalPresetAsset],
+    base_mods_path: &Path,
+    entries: &[PortablePresetAsset],
+) -> Vec<Option<PortableAssetMatch>> {
+    let mut results: Vec<Option<PortableAssetMatch>> = vec![None; entries.len()];
+    let mut used_ids = HashSet::new();
+
+    // Pass 1: Exact folder path
+    let by_path: HashMap<String, usize> = local_assets.iter().enumerate().map(|(i, a)| (a.2.to_lowercase(), i)).collect();
+    for (index, entry) in entries.iter().enumerate() {
+        if let Some(&local_index) = by_path.get(&entry.folder_path.replace("\\", "/").to_lowercase()) {
+            let (id, name, ..) = &local_assets[local_index];
+            if used_ids.insert(*id) {
+                results[index] = Some((*id, name.clone(), "path"));
             }
-            Err(e) => { eprintln!("[get_dashboard_stats] Error fetching asset folder row: {}", e); }
         }
     }
 
-    Ok(DashboardStats {
-        total_mods,
-        enabled_mods,
-        disabled_mods,
-        uncategorized_mods,
-        category_counts,
-    })
-}
+    // Pass 2: Content fingerprint (only computed when something is still unmatched)
+    let needs_fingerprints = entries.iter().zip(results.iter()).any(|(e, r)| r.is_none() && e.fingerprint.is_some());
+    if needs_fingerprints {
+        println!("[match_portable_preset_assets] Computing fingerprints for unmatched local assets...");
+        let mut by_fingerprint: HashMap<String, usize> = HashMap::new();
+        for (local_index, (id, _, path, _, root_id)) in local_assets.iter().enumerate() {
+            if used_ids.contains(id) { continue; }
+            if let Some((folder, _)) = locate_asset_folder(base_mods_path, *root_id, path) {
+                if let Some(fingerprint) = compute_folder_fingerprint(&folder) {
+                    by_fingerprint.entry(fingerprint).or_insert(local_index);
+                }
+            }
+        }
+        for (index, entry) in entries.iter().enumerate() {
+            if results[index].is_some() { continue; }
+            let local_index = match entry.fingerprint.as_ref().and_then(|fp| by_fingerprint.get(fp)) {
+                Some(&i) => i,
+                None => continue,
+            };
+            let (id, name, ..) = &local_assets[local_index];
+            if used_ids.insert(*id) {
+                results[index] = Some((*id, name.clone(), "fingerprint"));
+            }
+        }
+    }
 
+    // Pass 3: Same entity + same mod folder name
+    for (index, entry) in entries.iter().enumerate() {
+        if results[index].is_some() { continue; }
+        let entry_folder = entry.folder_path.replace("\\", "/").rsplit('/').next().unwrap_or("").to_lowercase();
+        if entry_folder.is_empty() { continue; }
+        let found = local_assets.iter().find(|(id, _, path, slug, _)| {
+            !used_ids.contains(id)
+                && *slug == entry.entity_slug
+                && path.rsplit('/').next().unwrap_or("").to_lowercase() == entry_folder
+        });
+        if let Some((id, name, ..)) = found {
+            used_ids.insert(*id);
+            results[index] = Some((*id, name.clone(), "folder_name"));
+        }
+    }
 
-// --- Command to get App Version ---
-#[command]
-fn get_app_version() -> String {
-    // Read from environment variable set by build script/Cargo
-    env!("CARGO_PKG_VERSION").to_string()
+    results
 }
 
-#[command]
-fn get_entities_by_category_with_counts(category_slug: String, db_state: State<DbState>) -> CmdResult<Vec<EntityWithCounts>> {
-    println!("[get_entities_with_counts] Fetching for category: {}", category_slug);
-
-    let base_mods_path = match get_mods_base_path_from_settings(&db_state) {
-        Ok(p) => p,
-        Err(_) => {
-            println!("[get_entities_with_counts] Mods folder not set. Returning empty list.");
-            return Ok(Vec::new());
+// Entries resolved to an asset that another entry already uses. preset_assets holds one row
+// per asset, so importing them would silently keep only the last entry's state.
+fn find_duplicate_preset_targets(resolved: &[Option<i64>]) -> Vec<PresetImportDuplicate> {
+    let mut entries_by_asset: HashMap<i64, Vec<usize>> = HashMap::new();
+    for (entry_index, asset_id) in resolved.iter().enumerate() {
+        if let Some(asset_id) = asset_id {
+            entries_by_asset.entry(*asset_id).or_default().push(entry_index);
         }
-    };
+    }
+    let mut duplicates: Vec<PresetImportDuplicate> = entries_by_asset.into_iter()
+        .filter(|(_, entry_indices)| entry_indices.len() > 1)
+        .map(|(asset_id, entry_indices)| PresetImportDuplicate { asset_id, entry_indices })
+        .collect();
+    duplicates.sort_by_key(|d| d.entry_indices[0]);
+    duplicates
+}
 
-    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+// User remaps take precedence over automatic matches
+fn apply_preset_import_remaps(resolved: &mut [Option<i64>], remap: &[PresetImportRemap]) {
+    for entry_remap in remap {
+        match resolved.get_mut(entry_remap.entry_index) {
+            Some(slot) => *slot = entry_remap.asset_id,
+            None => eprintln!("[import_preset] Warning: Ignoring remap for out-of-range entry {}", entry_remap.entry_index),
+        }
+    }
+}
 
-    // 1. Get Category ID
-    let category_id: i64 = conn.query_row(
-        "SELECT id FROM categories WHERE slug = ?1",
-        params![category_slug],
-        |row| row.get(0),
-    ).map_err(|e| match e {
-        rusqlite::Error::QueryReturnedNoRows => format!("Category '{}' not found", category_slug),
-        _ => format!("DB Error getting category ID: {}", e),
-    })?;
+// Scope ids of a portable preset, stored by slug in the file. A slug that doesn't exist locally is an
+// error: dropping it would turn a scoped (possibly strict) preset into a library-wide one.
+fn resolve_portable_preset_scope(conn: &Connection, portable: &PortablePreset) -> CmdResult<(Option<i64>, Option<i64>)> {
+    let scope_entity_id: Option<i64> = match &portable.scope_entity_slug {
+        Some(slug) => Some(conn.query_row("SELECT id FROM entities WHERE slug = ?1", params![slug], |row| row.get(0))
+            .optional().map_err(|e| format!("DB error resolving scope entity: {}", e))?
+            .ok_or_else(|| format!("The preset is limited to entity '{}', which doesn't exist in this library.", slug))?),
+        None => None,
+    };
+    let scope_category_id: Option<i64> = match (&portable.scope_category_slug, scope_entity_id) {
+        (Some(slug), None) => Some(conn.query_row("SELECT id FROM categories WHERE slug = ?1", params![slug], |row| row.get(0))
+            .optional().map_err(|e| format!("DB error resolving scope category: {}", e))?
+            .ok_or_else(|| format!("The preset is limited to category '{}', which doesn't exist in this library.", slug))?),
+        _ => None,
+    };
+    Ok((scope_entity_id, scope_category_id))
+}
 
-    // 2. Get Entities for the Category
-    let mut entity_stmt = conn.prepare(
-         "SELECT e.id, e.category_id, e.name, e.slug, e.details, e.base_image
-          FROM entities e
-          WHERE e.category_id = ?1
-          ORDER BY CASE WHEN e.slug LIKE '%-other' THEN 0 ELSE 1 END ASC, e.name ASC"
-     ).map_err(|e| format!("Failed to prepare entity query: {}", e))?;
+// Presets are keyed by game-specific entity slugs; files without a game are accepted
+fn check_preset_game(conn: &Connection, file_game: Option<&str>) -> Result<Option<String>, AppError> {
+    let active_game = get_setting_value(conn, DB_INTERNAL_GAME_SLUG_KEY)?;
+    Ok(match (file_game, active_game.as_deref()) {
+        (Some(file_game), Some(active)) if !file_game.eq_ignore_ascii_case(active) => Some(format!(
+            "This preset was exported for '{}' but the active game is '{}'.", file_game, active
+        )),
+        _ => None,
+    })
+}
 
-    let entity_rows_iter = entity_stmt.query_map(params![category_id], |row| {
-        Ok((
-            row.get::<_, i64>(0)?,
-            row.get::<_, i64>(1)?,
-            row.get::<_, String>(2)?,
-            row.get::<_, String>(3)?,
-            row.get::<_, Option<String>>(4)?,
-            row.get::<_, Option<String>>(5)?,
-        ))
-    }).map_err(|e| format!("Failed to query entities: {}", e))?;
+// Appends " (Imported)", " (Imported 2)", ... until the name is free
+fn unique_preset_name(conn: &Connection, desired: &str) -> Result<String, AppError> {
+    let name_taken = |candidate: &str| -> Result<bool, AppError> {
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM presets WHERE LOWER(name) = LOWER(?1)", params![candidate], |row| row.get(0))?;
+        Ok(count > 0)
+    };
+    if !name_taken(desired)? {
+        return Ok(desired.to_string());
+    }
+    let mut attempt = 1;
+    loop {
+        let candidate = if attempt == 1 { format!("{} (Imported)", desired) } else { format!("{} (Imported {})", desired, attempt) };
+        if !name_taken(&candidate)? {
+            return Ok(candidate);
+        }
+        attempt += 1;
+    }
+}
 
-    let mut results: Vec<EntityWithCounts> = Vec::new();
+#[command]
+async fn select_preset_export_file(default_name: Option<String>) -> CmdResult<Option<PathBuf>> {
+    let file_name = format!("{}.json", default_name.unwrap_or_else(|| "preset".to_string()));
+    let result = dialog::blocking::FileDialogBuilder::new()
+        .set_title("Export Preset")
+        .set_file_name(&file_name)
+        .add_filter("JSON", &["json"])
+        .add_filter("TOML", &["toml"])
+        .save_file();
+    Ok(result)
+}
 
-    // *** FIX: Apply .map_err() to the prepare call ***
-    let mut asset_folder_stmt = conn.prepare("SELECT folder_name FROM assets WHERE entity_id = ?1")
-                                     .map_err(|e| format!("Failed to prepare asset folder query: {}", e))?; // Prepare asset query once
+#[command]
+async fn select_preset_import_file() -> CmdResult<Option<PathBuf>> {
+    let result = dialog::blocking::FileDialogBuilder::new()
+        .set_title("Import Preset")
+        .add_filter("Preset Files", &["json", "toml"])
+        .add_filter("All Files", &["*"])
+        .pick_file();
+    Ok(result)
+}
 
-    for entity_result in entity_rows_iter {
-        match entity_result {
-            Ok((id, cat_id, name, slug, details, base_image)) => {
-                // 3. For each entity, get its assets and check disk status
-                let mut total_mods_for_entity = 0;
-                let mut enabled_mods_for_entity = 0;
+#[command]
+fn export_preset(preset_id: i64, file_path: String, include_personal: Option<bool>, db_state: State<DbState>) -> CmdResult<usize> {
+    let include_personal = include_personal.unwrap_or(false);
+    println!("[export_preset] Exporting preset ID {} to '{}' (personal data: {})", preset_id, file_path, include_personal);
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
 
-                // Map potential errors when querying assets for *this specific* entity
-                let asset_folder_rows_result = asset_folder_stmt.query_map(params![id], |row| row.get::<_, String>(0));
+    let (preset, game, scope_entity_slug, scope_category_slug, tag_selectors, states) = {
+        let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+        let preset = fetch_preset(&conn, preset_id).map_err(|e| e.to_string())?;
+        let game = get_setting_value(&conn, DB_INTERNAL_GAME_SLUG_KEY).map_err(|e| e.to_string())?;
+        let scope_entity_slug: Option<String> = match preset.scope_entity_id {
+            Some(id) => conn.query_row("SELECT slug FROM entities WHERE id = ?1", params![id], |row| row.get(0))
+                .optional().map_err(|e| format!("DB error fetching scope entity: {}", e))?,
+            None => None,
+        };
+        let scope_category_slug: Option<String> = match preset.scope_category_id {
+            Some(id) => conn.query_row("SELECT slug FROM categories WHERE id = ?1", params![id], |row| row.get(0))
+                .optional().map_err(|e| format!("DB error fetching scope category: {}", e))?,
+            None => None,
+        };
+        let states = fetch_preset_asset_states(&conn, preset_id).map_err(|e| format!("Failed to fetch preset assets: {}", e))?;
+        // Favorites, ratings and notes stay private unless asked for
+        let states: Vec<(PresetAssetState, AssetPersonalData)> = states.into_iter().map(|state| {
+            let personal = if include_personal { fetch_asset_personal_data(&conn, state.asset_id).unwrap_or_default() } else { AssetPersonalData::default() };
+            (state, personal)
+        }).collect();
+        let tag_selectors: Vec<PortableTagSelector> = preset.tag_selectors.iter()
+            .filter_map(|selector| fetch_tag(&conn, selector.tag_id).ok().map(|tag| PortableTagSelector { tag_name: tag.name, is_enabled: selector.is_enabled }))
+            .collect();
+        (preset, game, scope_entity_slug, scope_category_slug, tag_selectors, states)
+    }; // Lock released before hashing mod folders
+
+    let assets: Vec<PortablePresetAsset> = states.into_iter().map(|(state, personal)| {
+        let fingerprint = locate_asset_folder(&base_mods_path, state.root_id, &state.clean_relative_path)
+            .and_then(|(folder, _)| compute_folder_fingerprint(&folder));
+        PortablePresetAsset {
+            entity_slug: state.entity_slug,
+            folder_path: state.clean_relative_path,
+            fingerprint,
+            name: state.name,
+            is_enabled: state.is_enabled,
+            is_favorite: personal.is_favorite,
+            rating: personal.rating,
+            notes: personal.notes,
+        }
+    }).collect();
+    let asset_count = assets.len();
+
+    let portable = PortablePreset {
+        format_version: PORTABLE_PRESET_FORMAT_VERSION,
+        name: preset.name,
+        game,
+        mode: Some(preset.mode),
+        scope_entity_slug,
+        scope_category_slug,
+        tag_selectors,
+        assets,
+    };
+    let target_path = PathBuf::from(&file_path);
+    let content = if target_path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("toml")) {
+        toml::to_string_pretty(&portable).map_err(|e| format!("Failed to serialize preset as TOML: {}", e))?
+    } else {
+        serde_json::to_string_pretty(&portable).map_err(|e| format!("Failed to serialize preset as JSON: {}", e))?
+    };
+    fs::write(&target_path, content).map_err(|e| format!("Failed to write preset file '{}': {}", target_path.display(), e))?;
 
-                match asset_folder_rows_result {
-                     Ok(rows) => {
-                        for folder_result in rows {
-                            match folder_result {
-                                Ok(clean_relative_path_str) => {
-                                    total_mods_for_entity += 1;
+    println!("[export_preset] Exported {} assets to '{}'", asset_count, target_path.display());
+    Ok(asset_count)
+}
 
-                                    let clean_relative_path = PathBuf::from(clean_relative_path_str.replace("\\", "/"));
-                                    let filename_osstr = clean_relative_path.file_name().unwrap_or_default();
-                                    let filename_str = filename_osstr.to_string_lossy();
-                                    if filename_str.is_empty() { continue; }
+#[command]
+// `remap` takes the user's pending remaps into account for the duplicate check
+fn analyze_preset_import(file_path: String, remap: Option<Vec<PresetImportRemap>>, db_state: State<DbState>) -> CmdResult<PresetImportAnalysis> {
+    println!("[analyze_preset_import] Analyzing '{}'", file_path);
+    let portable = read_portable_preset(Path::new(&file_path)).map_err(|e| e.to_string())?;
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
 
-                                    // Check only enabled state path
-                                    let full_path_if_enabled = base_mods_path.join(&clean_relative_path);
-                                    if full_path_if_enabled.is_dir() {
-                                        enabled_mods_for_entity += 1;
-                                    }
-                                }
-                                Err(e) => eprintln!("[get_entities_with_counts] Error fetching asset folder row for entity {}: {}", id, e),
-                            }
-                        }
-                    }
-                    // Log the error but don't stop the whole process for one entity's assets failing
-                    Err(e) => eprintln!("[get_entities_with_counts] Error querying asset folders for entity {}: {}", id, e),
-                }
+    let local_assets = {
+        let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+        fetch_local_preset_assets(&conn).map_err(|e| format!("Failed to fetch local assets: {}", e))?
+    }; // Lock released before hashing mod folders
+    let matches = match_portable_preset_assets(&local_assets, &base_mods_path, &portable.assets);
+    let mut resolved: Vec<Option<i64>> = matches.iter().map(|m| m.as_ref().map(|(id, _, _)| *id)).collect();
+    apply_preset_import_remaps(&mut resolved, &remap.unwrap_or_default());
+    let duplicates = find_duplicate_preset_targets(&resolved);
 
-                results.push(EntityWithCounts {
-                    id,
-                    category_id: cat_id,
-                    name,
-                    slug,
-                    details,
-                    base_image,
-                    total_mods: total_mods_for_entity,
-                    enabled_mods: enabled_mods_for_entity,
-                });
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let game_warning = check_preset_game(&conn, portable.game.as_deref()).map_err(|e| e.to_string())?;
+    let scope_error = resolve_portable_preset_scope(&conn, &portable).err();
+    let name_conflict = unique_preset_name(&conn, &portable.name).map_err(|e| e.to_string())? != portable.name;
+
+    let mut matched = Vec::new();
+    let mut unmatched = Vec::new();
+    let mut candidate_stmt = conn.prepare(
+        "SELECT a.id, a.name, a.folder_name, e.slug FROM assets a JOIN entities e ON a.entity_id = e.id WHERE e.slug = ?1 AND a.missing_since IS NULL ORDER BY a.name"
+    ).map_err(|e| format!("Failed to prepare candidate query: {}", e))?;
+
+    for (entry_index, (entry, match_result)) in portable.assets.iter().zip(matches).enumerate() {
+        match match_result {
+            Some((asset_id, asset_name, method)) => matched.push(PresetImportMatch {
+                entry_index,
+                asset_id,
+                asset_name,
+                match_method: method.to_string(),
+                is_enabled: entry.is_enabled,
+            }),
+            None => {
+                let candidates = candidate_stmt.query_map(params![entry.entity_slug], |row| Ok(PresetPreviewEntry {
+                    asset_id: row.get(0)?,
+                    name: row.get(1)?,
+                    folder_name: row.get::<_, String>(2)?.replace("\\", "/"),
+                    entity_slug: row.get(3)?,
+                })).and_then(|rows| rows.collect::<SqlResult<Vec<_>>>())
+                   .map_err(|e| format!("Failed to fetch remap candidates: {}", e))?;
+                unmatched.push(PresetImportUnmatched { entry_index, entry: entry.clone(), candidates });
             }
-            Err(e) => eprintln!("[get_entities_with_counts] Error processing entity row: {}", e),
         }
     }
 
-    println!("[get_entities_with_counts] Found {} entities with counts for category '{}'", results.len(), category_slug);
-    Ok(results)
+    println!("[analyze_preset_import] '{}': {} matched, {} unmatched.", portable.name, matched.len(), unmatched.len());
+    Ok(PresetImportAnalysis {
+        file_path,
+        preset_name: portable.name,
+        game: portable.game,
+        game_warning,
+        scope_error,
+        name_conflict,
+        total_assets: portable.assets.len(),
+        matched,
+        unmatched,
+        duplicates,
+    })
 }
 
 #[command]
-fn overwrite_preset(preset_id: i64, db_state: State<DbState>) -> CmdResult<()> {
-    println!("[overwrite_preset] Attempting to overwrite preset ID: {}", preset_id);
+fn import_preset(
+    file_path: String,
+    name: Option<String>,
+    remap: Option<Vec<PresetImportRemap>>,
+    import_personal: Option<bool>, // Fill favorites, ratings and notes from the file into assets without their own
+    db_state: State<DbState>
+) -> CmdResult<Preset> {
+    let import_personal = import_personal.unwrap_or(false);
+    println!("[import_preset] Importing '{}' (name override: {:?}, remaps: {}, personal data: {})", file_path, name, remap.as_ref().map_or(0, |r| r.len()), import_personal);
+    let portable = read_portable_preset(Path::new(&file_path)).map_err(|e| e.to_string())?;
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
 
-    let base_mods_path = get_mods_base_path_from_settings(&db_state)
-        .map_err(|e| format!("Cannot overwrite preset (failed to get mods path): {}", e))?;
+    let local_assets = {
+        let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+        if let Some(warning) = check_preset_game(&conn, portable.game.as_deref()).map_err(|e| e.to_string())? {
+            return Err(warning);
+        }
+        fetch_local_preset_assets(&conn).map_err(|e| format!("Failed to fetch local assets: {}", e))?
+    }; // Lock released before hashing mod folders
+    let mut resolved: Vec<Option<i64>> = match_portable_preset_assets(&local_assets, &base_mods_path, &portable.assets)
+        .into_iter()
+        .map(|m| m.map(|(id, _, _)| id))
+        .collect();
 
-    let conn_guard = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
-    let mut conn = conn_guard; // Get mutable access to the MutexGuard content
+    apply_preset_import_remaps(&mut resolved, &remap.unwrap_or_default());
+    let duplicates = find_duplicate_preset_targets(&resolved);
+    if !duplicates.is_empty() {
+        let details: Vec<String> = duplicates.iter()
+            .map(|d| format!("asset {} <- entries {}", d.asset_id, d.entry_indices.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(", ")))
+            .collect();
+        return Err(format!("Several preset entries point to the same mod; remap or skip all but one: {}", details.join("; ")));
+    }
 
-    // Use a transaction for atomicity
-    let tx = conn.transaction().map_err(|e| format!("Failed to start transaction: {}", e))?;
+    let mut conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
 
-    // 1. Delete existing asset states for this preset
-    println!("[overwrite_preset] Deleting old asset states for preset {}", preset_id);
-    let delete_count = tx.execute("DELETE FROM preset_assets WHERE preset_id = ?1", params![preset_id])
-        .map_err(|e| format!("Failed to delete old preset asset states: {}", e))?;
-    println!("[overwrite_preset] Deleted {} old entries.", delete_count);
+    let desired_name = name.map(|n| n.trim().to_string()).filter(|n| !n.is_empty()).unwrap_or_else(|| portable.name.clone());
+    let final_name = unique_preset_name(&conn, &desired_name).map_err(|e| e.to_string())?;
 
-    // 2. Fetch all current assets from the main assets table
-    let mut assets_to_save = Vec::<(i64, String)>::new(); // (asset_id, clean_relative_path)
-    { // Scope for the statement
-        let mut stmt = tx.prepare("SELECT id, folder_name FROM assets")
-           .map_err(|e| format!("Failed to prepare asset fetch statement: {}", e))?;
-        let asset_iter = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))
-                             .map_err(|e| format!("Failed to create asset query iterator: {}", e))?;
+    let (scope_entity_id, scope_category_id) = resolve_portable_preset_scope(&conn, &portable)?;
+    let mode = validate_preset_settings(&conn, portable.mode.clone(), scope_entity_id, scope_category_id)?;
 
-        for row_result in asset_iter {
-            match row_result {
-                Ok((asset_id, folder_name)) => {
-                    assets_to_save.push((asset_id, folder_name.replace("\\", "/")));
+    let tx = conn.transaction().map_err(|e| format!("Failed to start transaction: {}", e))?;
+    tx.execute(
+        "INSERT INTO presets (name, mode, scope_entity_id, scope_category_id) VALUES (?1, ?2, ?3, ?4)",
+        params![final_name, mode, scope_entity_id, scope_category_id],
+    ).map_err(|e| format!("Failed to insert preset: {}", e))?;
+    let preset_id = tx.last_insert_rowid();
+
+    let mut imported_count = 0;
+    let mut skipped_count = 0;
+    { // Scope for the statement
+        let mut insert_stmt = tx.prepare(
+            "INSERT OR REPLACE INTO preset_assets (preset_id, asset_id, is_enabled) VALUES (?1, ?2, ?3)"
+        ).map_err(|e| format!("Failed to prepare preset asset insert: {}", e))?;
+        // Local favorites, ratings and notes always win over the file's
+        let mut personal_stmt = tx.prepare(
+            "UPDATE assets SET is_favorite = MAX(is_favorite, ?1), rating = COALESCE(rating, ?2), notes = COALESCE(notes, ?3) WHERE id = ?4"
+        ).map_err(|e| format!("Failed to prepare personal data update: {}", e))?;
+        for (entry, asset_id) in portable.assets.iter().zip(resolved) {
+            match asset_id {
+                Some(id) => {
+                    insert_stmt.execute(params![preset_id, id, if entry.is_enabled { 1 } else { 0 }])
+                        .map_err(|e| format!("Failed to save state for asset {}: {}", id, e))?;
+                    if import_personal {
+                        let rating = entry.rating.filter(|r| (1..=5).contains(r));
+                        personal_stmt.execute(params![entry.is_favorite as i64, rating, entry.notes, id])
+                            .map_err(|e| format!("Failed to save personal data for asset {}: {}", id, e))?;
+                    }
+                    imported_count += 1;
                 }
-                Err(e) => {
-                    // Log error for the specific row but continue fetching others
-                    eprintln!("[overwrite_preset] Error fetching asset row from DB: {}", e);
-                    // Optionally rollback transaction and return error
-                    // tx.rollback().ok(); // Attempt rollback // Don't rollback here, let the error propagate
-                    // return Err(format!("Error fetching asset row from DB: {}", e)); // Let caller handle rollback on error
+                None => {
+                    println!("[import_preset] Skipping unmatched entry '{}' ({})", entry.name, entry.folder_path);
+                    skipped_count += 1;
                 }
             }
         }
     }
-    println!("[overwrite_preset] Fetched {} assets to check for saving.", assets_to_save.len());
+    for (position, selector) in portable.tag_selectors.iter().enumerate() {
+        let tag_id = ensure_tag(&tx, &selector.tag_name).map_err(|e| format!("Failed to create tag '{}': {}", selector.tag_name, e))?;
+        tx.execute(
+            "INSERT OR IGNORE INTO preset_tag_selectors (preset_id, tag_id, is_enabled, position) VALUES (?1, ?2, ?3, ?4)",
+            params![preset_id, tag_id, selector.is_enabled as i64, position as i64],
+        ).map_err(|e| format!("Failed to save selector for tag '{}': {}", selector.tag_name, e))?;
+    }
+    tx.commit().map_err(|e| format!("Failed to commit transaction: {}", e))?;
+    sync_asset_sidecars(&conn, &preset_member_ids(&conn, preset_id));
 
+    println!("[import_preset] Imported preset '{}' (ID {}). {} assets linked, {} skipped.", final_name, preset_id, imported_count, skipped_count);
+    fetch_preset(&conn, preset_id).map_err(|e| e.to_string())
+}
 
-    // --- FIX: Add block scope for insert_stmt ---
-    let mut saved_count = 0;
-    let mut not_found_count = 0;
-    { // Start scope for insert_stmt
-        // 3. Iterate through fetched assets, check disk state, and insert into preset_assets
-        let mut insert_stmt = tx.prepare(
-            "INSERT INTO preset_assets (preset_id, asset_id, is_enabled) VALUES (?1, ?2, ?3)"
-        ).map_err(|e| format!("Failed to prepare insert statement for preset assets: {}", e))?;
 
+// --- Shuffle ---
 
-        for (asset_id, clean_relative_path_str) in assets_to_save {
-            let clean_relative_path = PathBuf::from(&clean_relative_path_str);
-            let filename_osstr = clean_relative_path.file_name().unwrap_or_default();
-            let filename_str = filename_osstr.to_string_lossy();
-            if filename_str.is_empty() { continue; }
+// Small xorshift64* generator; picking skins doesn't need a crypto RNG or an extra crate
+struct ShuffleRng(u64);
 
-            // Check enabled state on disk
-            let full_path_if_enabled = base_mods_path.join(&clean_relative_path);
-            let is_currently_enabled_on_disk = if full_path_if_enabled.is_dir() {
-                1 // Enabled
-            } else {
-                // Check disabled state only to confirm it exists somewhere, otherwise skip saving
-                let disabled_filename = format!("{}{}", DISABLED_PREFIX, filename_str);
-                let relative_parent_path = clean_relative_path.parent();
-                let full_path_if_disabled = match relative_parent_path {
-                    Some(parent) if parent.as_os_str().len() > 0 => base_mods_path.join(parent).join(&disabled_filename),
-                    _ => base_mods_path.join(&disabled_filename),
-                };
-                if full_path_if_disabled.is_dir() {
-                    0 // Disabled
-                } else {
-                    // Folder not found in either state - skip saving its state for this preset
+impl ShuffleRng {
+    fn from_time() -> Self {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0x9E37_79B9_7F4A_7C15);
+        ShuffleRng(seed | 1) // State must never be zero
+    }
+
+    fn next_index(&mut self, len: usize) -> usize {
+        let mut x = self.0;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.0 = x;
+        (x.wrapping_mul(0x2545_F491_4F6C_DD1D) % len as u64) as usize
+    }
+}
+
+// Picks one asset per entity from the filtered pool. Returns the states to apply: the pick enabled
+// and every other asset of that entity disabled, inside the pool or not (favorites/preset filters
+// only narrow what can be picked). Entities with fewer than two candidates are left alone.
+// The picks are recorded by record_shuffle_picks once they have been applied.
+fn pick_shuffle_states(
+    conn: &Connection,
+    base_mods_path: &Path,
+    options: &ShuffleOptions,
+) -> Result<(Vec<PresetAssetState>, Vec<ShufflePick>), AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT a.id, a.folder_name, a.name, e.id, e.slug, a.root_id
+         FROM assets a
+         JOIN entities e ON a.entity_id = e.id
+         WHERE a.missing_since IS NULL AND e.slug NOT LIKE ?1
+           AND (?2 IS NULL OR e.category_id = ?2)
+           AND (?3 IS NULL OR a.id IN (SELECT asset_id FROM preset_assets WHERE preset_id = ?3))
+           AND (?4 = 0 OR a.is_favorite = 1 OR a.id IN (SELECT pa.asset_id FROM preset_assets pa JOIN presets p ON pa.preset_id = p.id WHERE p.is_favorite = 1))
+         ORDER BY e.id, a.id"
+    )?;
+    let rows = stmt.query_map(
+        params![format!("%{}", OTHER_ENTITY_SUFFIX), options.category_id, options.preset_id, options.favorites_only as i64],
+        |row| Ok((
+            row.get::<_, i64>(3)?,
+            PresetAssetState {
+                asset_id: row.get(0)?,
+                is_enabled: false,
+                clean_relative_path: row.get::<_, String>(1)?.replace("\\", "/"),
+                name: row.get(2)?,
+                entity_slug: row.get(4)?,
+                implied: false,
+                root_id: row.get(5)?,
+            },
+        )),
+    )?;
+
+    // Group candidates per entity, keeping only folders that exist on disk
+    let mut pools: Vec<(i64, Vec<PresetAssetState>)> = Vec::new();
+    for row_result in rows {
+        let (entity_id, state) = row_result?;
+        if locate_asset_folder(base_mods_path, state.root_id, &state.clean_relative_path).is_none() {
+            continue;
+        }
+        match pools.last_mut() {
+            Some((last_entity_id, pool)) if *last_entity_id == entity_id => pool.push(state),
+            _ => pools.push((entity_id, vec![state])),
+        }
+    }
+
+    let mut rng = ShuffleRng::from_time();
+    let mut states = Vec::new();
+    let mut picks = Vec::new();
+
+    for (entity_id, mut pool) in pools {
+        if pool.len() < 2 {
+            continue;
+        }
+
+        // Avoid recent picks, but never so many that nothing is left to choose from
+        let avoid_count = SHUFFLE_AVOID_RECENT.min(pool.len() - 1);
+        let mut recent_stmt = conn.prepare_cached("SELECT asset_id FROM shuffle_history WHERE entity_id = ?1 ORDER BY id DESC LIMIT ?2")?;
+        let recent: HashSet<i64> = recent_stmt.query_map(params![entity_id, avoid_count as i64], |row| row.get(0))?
+            .collect::<SqlResult<HashSet<i64>>>()?;
+        let eligible: Vec<usize> = (0..pool.len()).filter(|i| !recent.contains(&pool[*i].asset_id)).collect();
+        let picked_index = if eligible.is_empty() {
+            rng.next_index(pool.len())
+        } else {
+            eligible[rng.next_index(eligible.len())]
+        };
+
+        for (index, state) in pool.iter_mut().enumerate() {
+            state.is_enabled = index == picked_index;
+        }
+        let picked = &pool[picked_index];
+        picks.push(ShufflePick { entity_id, entity_slug: picked.entity_slug.clone(), asset_id: picked.asset_id, asset_name: picked.name.clone() });
+
+        // Assets of the entity outside the pool would otherwise stay enabled next to the pick
+        let pool_ids: HashSet<i64> = pool.iter().map(|s| s.asset_id).collect();
+        let mut others_stmt = conn.prepare_cached(
+            "SELECT a.id, a.folder_name, a.name, e.slug, a.root_id FROM assets a JOIN entities e ON a.entity_id = e.id
+             WHERE a.entity_id = ?1 AND a.missing_since IS NULL"
+        )?;
+        let others = others_stmt.query_map(params![entity_id], |row| Ok(PresetAssetState {
+            asset_id: row.get(0)?,
+            is_enabled: false,
+            clean_relative_path: row.get::<_, String>(1)?.replace("\\", "/"),
+            name: row.get(2)?,
+            entity_slug: row.get(3)?,
+            implied: true, // Nothing to disable if the folder is gone
+            root_id: row.get(4)?,
+        }))?.collect::<SqlResult<Vec<PresetAssetState>>>()?;
+
+        states.extend(pool);
+        states.extend(others.into_iter().filter(|s| !pool_ids.contains(&s.asset_id)));
+    }
+
+    Ok((states, picks))
+}
+
+// Remembers applied picks so the next shuffles avoid them, keeping the newest per entity
+fn record_shuffle_picks(conn: &Connection, picks: &[ShufflePick]) -> Result<(), AppError> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    for pick in picks {
+        conn.execute(
+            "INSERT INTO shuffle_history (entity_id, asset_id, picked_at) VALUES (?1, ?2, ?3)",
+            params![pick.entity_id, pick.asset_id, now],
+        )?;
+        conn.execute(
+            "DELETE FROM shuffle_history WHERE entity_id = ?1 AND id NOT IN (SELECT id FROM shuffle_history WHERE entity_id = ?1 ORDER BY id DESC LIMIT ?2)",
+            params![pick.entity_id, SHUFFLE_HISTORY_KEEP as i64],
+        )?;
+    }
+    Ok(())
+}
+
+fn read_shuffle_options(conn: &Connection) -> Result<ShuffleOptions, AppError> {
+    match get_setting_value(conn, SETTINGS_KEY_SHUFFLE_OPTIONS)? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(ShuffleOptions::default()),
+    }
+}
+
+// Shared by the shuffle_assets command and launch_executable
+fn run_shuffle(db_state: &DbState, app_handle: &AppHandle, options: &ShuffleOptions) -> Result<Vec<ShufflePick>, String> {
+    println!("[run_shuffle] Shuffling with options: {:?}", options);
+    let base_mods_path = get_mods_base_path_from_settings(db_state)
+        .map_err(|e| format!("Cannot shuffle: {}", e))?;
+
+    let (states, picks) = {
+        let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+        pick_shuffle_states(&conn, &base_mods_path, options)
+            .map_err(|e| format!("Failed to pick shuffle assets: {}", e))?
+    }; // Lock released before touching the filesystem
+
+    let total_assets = states.len();
+    let (errors, changes) = apply_asset_states(app_handle, &base_mods_path, states);
+    if let Ok(conn) = db_state.0.lock() {
+        record_enabled_at(&conn, &changes);
+        journal_operation(&conn, &format!("Shuffled {} entities", picks.len()), &JournalOp::SetEnabled { assets: changes, inis: Vec::new() });
+        // A failed shuffle may not have enabled its picks, so they don't count as recent
+        if errors.is_empty() {
+            if let Err(e) = record_shuffle_picks(&conn, &picks) {
+                eprintln!("[run_shuffle] Failed to record shuffle history: {}", e);
+            }
+        }
+    }
+    println!("[run_shuffle] Picked {} assets ({} mods processed). Errors: {}", picks.len(), total_assets, errors.len());
+
+    if errors.is_empty() {
+        let summary = format!("Shuffled {} entities ({} mods processed).", picks.len(), total_assets);
+        app_handle.emit_all(PRESET_APPLY_COMPLETE_EVENT, &summary).ok();
+        Ok(picks)
+    } else {
+        let error_summary = format!("Shuffle completed with {} error(s).", errors.len());
+        app_handle.emit_all(PRESET_APPLY_ERROR_EVENT, &error_summary).ok();
+        Err(format!("{}\nDetails:\n{}", error_summary, errors.join("\n")))
+    }
+}
+
+#[command]
+async fn shuffle_assets(options: Option<ShuffleOptions>, db_state: State<'_, DbState>, app_handle: AppHandle) -> CmdResult<Vec<ShufflePick>> {
+    let options = match options {
+        Some(o) => o,
+        None => {
+            let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+            read_shuffle_options(&conn).map_err(|e| e.to_string())?
+        }
+    };
+    run_shuffle(&db_state, &app_handle, &options)
+}
+
+#[command]
+fn get_shuffle_options(db_state: State<DbState>) -> CmdResult<ShuffleOptions> {
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    read_shuffle_options(&conn).map_err(|e| e.to_string())
+}
+
+#[command]
+fn save_shuffle_options(options: ShuffleOptions, db_state: State<DbState>) -> CmdResult<()> {
+    let json = serde_json::to_string(&options).map_err(|e| e.to_string())?;
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    conn.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+        params![SETTINGS_KEY_SHUFFLE_OPTIONS, json],
+    ).map_err(|e| e.to_string())?;
+    println!("[save_shuffle_options] Saved shuffle options: {}", json);
+    Ok(())
+}
+
+#[command]
+fn toggle_preset_favorite(preset_id: i64, is_favorite: bool, db_state: State<DbState>) -> CmdResult<()> {
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let fav_value = if is_favorite { 1 } else { 0 };
+    conn.execute(
+        "UPDATE presets SET is_favorite = ?1 WHERE id = ?2",
+        params![fav_value, preset_id],
+    )
+    .map_err(|e| format!("Failed to update favorite status: {}", e))?;
+    Ok(())
+}
+
+#[command]
+fn delete_preset(preset_id: i64, db_state: State<DbState>) -> CmdResult<()> {
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let member_ids = preset_member_ids(&conn, preset_id); // Gone after the cascade
+    // Foreign key cascade should delete from preset_assets automatically
+    let changes = conn.execute("DELETE FROM presets WHERE id = ?1", params![preset_id])
+                      .map_err(|e| format!("Failed to delete preset: {}", e))?;
+    if changes == 0 {
+        Err(format!("Preset with ID {} not found.", preset_id))
+    } else {
+        sync_asset_sidecars(&conn, &member_ids);
+        Ok(())
+    }
+}
+
+// --- Command to get Dashboard Stats ---
+#[command]
+fn get_dashboard_stats(db_state: State<DbState>) -> CmdResult<DashboardStats> {
+    let base_mods_path = match get_mods_base_path_from_settings(&db_state) {
+        Ok(p) => p,
+        Err(_) => {
+             // If base path isn't set, return default zeroed stats
+            return Ok(DashboardStats {
+                total_mods: 0,
+                enabled_mods: 0,
+                disabled_mods: 0,
+                uncategorized_mods: 0,
+                category_counts: HashMap::new(),
+                recently_added_mods: 0,
+                recently_used_mods: 0,
+            });
+        }
+    };
+
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+
+    // 1. Total Mods
+    let total_mods = conn.query_row("SELECT COUNT(*) FROM assets WHERE missing_since IS NULL", [], |row| row.get::<_, i64>(0))
+                         .map_err(|e| format!("Failed to get total mod count: {}", e))?;
+
+    // 2. Uncategorized Mods
+    let uncategorized_mods = conn.query_row(
+        "SELECT COUNT(a.id) FROM assets a JOIN entities e ON a.entity_id = e.id WHERE e.slug LIKE '%-other' AND a.missing_since IS NULL",
+        [],
+        |row| row.get::<_, i64>(0)
+    ).map_err(|e| format!("Failed to get uncategorized mod count: {}", e))?;
+
+    // 3. Category Counts
+    let mut category_counts = HashMap::new();
+    let mut cat_stmt = conn.prepare(
+        "SELECT c.name, COUNT(a.id)
+         FROM categories c
+         JOIN entities e ON c.id = e.category_id
+         JOIN assets a ON e.id = a.entity_id AND a.missing_since IS NULL
+         GROUP BY c.name
+         HAVING COUNT(a.id) > 0" // Only include categories with mods
+    ).map_err(|e| format!("Failed to prepare category count query: {}", e))?;
+
+    let cat_rows = cat_stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+    }).map_err(|e| format!("Failed to execute category count query: {}", e))?;
+
+    for row_result in cat_rows {
+        match row_result {
+            Ok((name, count)) => { category_counts.insert(name, count); }
+            Err(e) => { eprintln!("[get_dashboard_stats] Error processing category count row: {}", e); }
+        }
+    }
+
+    // 4. Recently added / used
+    let (recently_added_mods, recently_used_mods) = conn.query_row(
+        "SELECT COALESCE(SUM(added_at >= ?1), 0), COALESCE(SUM(last_enabled_at >= ?1), 0) FROM assets WHERE missing_since IS NULL",
+        params![recent_cutoff(DEFAULT_RECENT_DAYS)],
+        |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+    ).map_err(|e| format!("Failed to get recent mod counts: {}", e))?;
+
+    // 5. Enabled/Disabled Count (Disk Check)
+    let mut enabled_mods = 0;
+    let mut disabled_mods = 0;
+    let mut disk_check_errors = 0;
+
+    // Fetch folder names for checking
+    let mut asset_folders_stmt = conn.prepare("SELECT folder_name, root_id FROM assets WHERE missing_since IS NULL")
+        .map_err(|e| format!("Failed to prepare asset folder fetch: {}", e))?;
+    let asset_folder_rows = asset_folders_stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<i64>>(1)?)))
+        .map_err(|e| format!("Failed to query asset folders: {}", e))?;
+
+    for folder_result in asset_folder_rows {
+        match folder_result {
+            Ok((clean_relative_path_str, root_id)) => {
+                 match locate_asset_folder(&base_mods_path, root_id, &clean_relative_path_str.replace("\\", "/")) {
+                     Some((_, true)) => enabled_mods += 1,
+                     Some((_, false)) => disabled_mods += 1,
+                     // Folder not found in either state - might have been deleted since last scan
+                     // We don't count it as enabled or disabled.
+                     None => disk_check_errors += 1,
+                 }
+            }
+            Err(e) => { eprintln!("[get_dashboard_stats] Error fetching asset folder row: {}", e); }
+        }
+    }
+
+    Ok(DashboardStats {
+        total_mods,
+        enabled_mods,
+        disabled_mods,
+        uncategorized_mods,
+        category_counts,
+        recently_added_mods,
+        recently_used_mods,
+    })
+}
+
+// Unix time `days` days ago
+fn recent_cutoff(days: i64) -> i64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    now - days.max(0) * 24 * 60 * 60
+}
+
+// Mods added in the last `days` days (default DEFAULT_RECENT_DAYS), newest first
+#[command]
+fn get_recently_added_assets(days: Option<i64>, limit: Option<i64>, db_state: State<DbState>) -> CmdResult<Vec<Asset>> {
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM assets WHERE missing_since IS NULL AND added_at >= ?1 ORDER BY added_at DESC, id DESC LIMIT ?2", ASSET_COLUMNS
+    )).map_err(|e| format!("Failed to prepare recent assets query: {}", e))?;
+    let assets = stmt.query_map(params![recent_cutoff(days.unwrap_or(DEFAULT_RECENT_DAYS)), limit.unwrap_or(-1)], asset_from_row)
+        .and_then(|rows| rows.collect::<SqlResult<Vec<Asset>>>())
+        .map_err(|e| format!("Failed to fetch recent assets: {}", e))?;
+    Ok(assets.into_iter().filter_map(|asset| resolve_asset_on_disk(&base_mods_path, asset)).collect())
+}
+
+// Mods enabled in the last `days` days (default DEFAULT_RECENT_DAYS), most recently enabled first
+#[command]
+fn get_recently_used_assets(days: Option<i64>, limit: Option<i64>, db_state: State<DbState>) -> CmdResult<Vec<Asset>> {
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM assets WHERE missing_since IS NULL AND last_enabled_at >= ?1 ORDER BY last_enabled_at DESC, id DESC LIMIT ?2", ASSET_COLUMNS
+    )).map_err(|e| format!("Failed to prepare recently used query: {}", e))?;
+    let assets = stmt.query_map(params![recent_cutoff(days.unwrap_or(DEFAULT_RECENT_DAYS)), limit.unwrap_or(-1)], asset_from_row)
+        .and_then(|rows| rows.collect::<SqlResult<Vec<Asset>>>())
+        .map_err(|e| format!("Failed to fetch recently used assets: {}", e))?;
+    Ok(assets.into_iter().filter_map(|asset| resolve_asset_on_disk(&base_mods_path, asset)).collect())
+}
+
+
+// --- Command to get App Version ---
+#[command]
+fn get_app_version() -> String {
+    // Read from environment variable set by build script/Cargo
+    env!("CARGO_PKG_VERSION").to_string()
+}
+
+#[command]
+fn get_entities_by_category_with_counts(category_slug: String, db_state: State<DbState>) -> CmdResult<Vec<EntityWithCounts>> {
+    println!("[get_entities_with_counts] Fetching for category: {}", category_slug);
+
+    let base_mods_path = match get_mods_base_path_from_settings(&db_state) {
+        Ok(p) => p,
+        Err(_) => {
+            println!("[get_entities_with_counts] Mods folder not set. Returning empty list.");
+            return Ok(Vec::new());
+        }
+    };
+
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+
+    // 1. Get Category ID
+    let category_id: i64 = conn.query_row(
+        "SELECT id FROM categories WHERE slug = ?1",
+        params![category_slug],
+        |row| row.get(0),
+    ).map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => format!("Category '{}' not found", category_slug),
+        _ => format!("DB Error getting category ID: {}", e),
+    })?;
+
+    // 2. Get Entities for the Category
+    let mut entity_stmt = conn.prepare(
+         "SELECT e.id, e.category_id, e.name, e.slug, e.details, e.base_image
+          FROM entities e
+          WHERE e.category_id = ?1
+          ORDER BY CASE WHEN e.slug LIKE '%-other' THEN 0 ELSE 1 END ASC, e.name ASC"
+     ).map_err(|e| format!("Failed to prepare entity query: {}", e))?;
+
+    let entity_rows_iter = entity_stmt.query_map(params![category_id], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, i64>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, Option<String>>(4)?,
+            row.get::<_, Option<String>>(5)?,
+        ))
+    }).map_err(|e| format!("Failed to query entities: {}", e))?;
+
+    let mut results: Vec<EntityWithCounts> = Vec::new();
+
+    // *** FIX: Apply .map_err() to the prepare call ***
+    let mut asset_folder_stmt = conn.prepare("SELECT folder_name, root_id FROM assets WHERE entity_id = ?1 AND missing_since IS NULL")
+                                     .map_err(|e| format!("Failed to prepare asset folder query: {}", e))?; // Prepare asset query once
+
+    for entity_result in entity_rows_iter {
+        match entity_result {
+            Ok((id, cat_id, name, slug, details, base_image)) => {
+                // 3. For each entity, get its assets and check disk status
+                let mut total_mods_for_entity = 0;
+                let mut enabled_mods_for_entity = 0;
+
+                // Map potential errors when querying assets for *this specific* entity
+                let asset_folder_rows_result = asset_folder_stmt.query_map(params![id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<i64>>(1)?)));
+
+                match asset_folder_rows_result {
+                     Ok(rows) => {
+                        for folder_result in rows {
+                            match folder_result {
+                                Ok((clean_relative_path_str, root_id)) => {
+                                    total_mods_for_entity += 1;
+
+                                    // Enabled state as the mod's root and activation mode see it
+                                    if let Some((_, true)) = locate_asset_folder(&base_mods_path, root_id, &clean_relative_path_str.replace("\\", "/")) {
+                                        enabled_mods_for_entity += 1;
+                                    }
+                                }
+                                Err(e) => eprintln!("[get_entities_with_counts] Error fetching asset folder row for entity {}: {}", id, e),
+                            }
+                        }
+                    }
+                    // Log the error but don't stop the whole process for one entity's assets failing
+                    Err(e) => eprintln!("[get_entities_with_counts] Error querying asset folders for entity {}: {}", id, e),
+                }
+
+                results.push(EntityWithCounts {
+                    id,
+                    category_id: cat_id,
+                    name,
+                    slug,
+                    details,
+                    base_image,
+                    total_mods: total_mods_for_entity,
+                    enabled_mods: enabled_mods_for_entity,
+                });
+            }
+            Err(e) => eprintln!("[get_entities_with_counts] Error processing entity row: {}", e),
+        }
+    }
+
+    println!("[get_entities_with_counts] Found {} entities with counts for category '{}'", results.len(), category_slug);
+    Ok(results)
+}
+
+#[command]
+fn overwrite_preset(preset_id: i64, db_state: State<DbState>) -> CmdResult<()> {
+    println!("[overwrite_preset] Attempting to overwrite preset ID: {}", preset_id);
+
+    let base_mods_path = get_mods_base_path_from_settings(&db_state)
+        .map_err(|e| format!("Cannot overwrite preset (failed to get mods path): {}", e))?;
+
+    let conn_guard = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let mut conn = conn_guard; // Get mutable access to the MutexGuard content
+
+    let preset = fetch_preset(&conn, preset_id).map_err(|e| e.to_string())?;
+    let mut sidecar_asset_ids = preset_member_ids(&conn, preset_id); // Dropped members need their sidecar rewritten too
+
+    // Use a transaction for atomicity
+    let tx = conn.transaction().map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+    // 1. Delete existing asset states for this preset
+    println!("[overwrite_preset] Deleting old asset states for preset {}", preset_id);
+    let delete_count = tx.execute("DELETE FROM preset_assets WHERE preset_id = ?1", params![preset_id])
+        .map_err(|e| format!("Failed to delete old preset asset states: {}", e))?;
+    println!("[overwrite_preset] Deleted {} old entries.", delete_count);
+
+    // 2. Fetch all current assets within the preset's scope from the main assets table
+    let mut assets_to_save = Vec::<(i64, String, Option<i64>)>::new(); // (asset_id, clean_relative_path, root_id)
+    { // Scope for the statement
+        let mut stmt = tx.prepare(
+            "SELECT a.id, a.folder_name, a.root_id FROM assets a JOIN entities e ON a.entity_id = e.id
+             WHERE a.missing_since IS NULL AND (?1 IS NULL OR a.entity_id = ?1) AND (?2 IS NULL OR e.category_id = ?2)"
+        ).map_err(|e| format!("Failed to prepare asset fetch statement: {}", e))?;
+        let asset_iter = stmt.query_map(params![preset.scope_entity_id, preset.scope_category_id], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<i64>>(2)?)))
+                             .map_err(|e| format!("Failed to create asset query iterator: {}", e))?;
+
+        for row_result in asset_iter {
+            match row_result {
+                Ok((asset_id, folder_name, root_id)) => {
+                    assets_to_save.push((asset_id, folder_name.replace("\\", "/"), root_id));
+                }
+                Err(e) => {
+                    // Log error for the specific row but continue fetching others
+                    eprintln!("[overwrite_preset] Error fetching asset row from DB: {}", e);
+                    // Optionally rollback transaction and return error
+                    // tx.rollback().ok(); // Attempt rollback // Don't rollback here, let the error propagate
+                    // return Err(format!("Error fetching asset row from DB: {}", e)); // Let caller handle rollback on error
+                }
+            }
+        }
+    }
+    println!("[overwrite_preset] Fetched {} assets to check for saving.", assets_to_save.len());
+
+
+    // --- FIX: Add block scope for insert_stmt ---
+    let mut saved_count = 0;
+    let mut not_found_count = 0;
+    { // Start scope for insert_stmt
+        // 3. Iterate through fetched assets, check disk state, and insert into preset_assets
+        let mut insert_stmt = tx.prepare(
+            "INSERT INTO preset_assets (preset_id, asset_id, is_enabled) VALUES (?1, ?2, ?3)"
+        ).map_err(|e| format!("Failed to prepare insert statement for preset assets: {}", e))?;
+
+
+        for (asset_id, clean_relative_path_str, root_id) in assets_to_save {
+            // Check enabled state on disk
+            let is_currently_enabled_on_disk = match locate_asset_folder(&base_mods_path, root_id, &clean_relative_path_str) {
+                Some((_, true)) => 1, // Enabled
+                Some((_, false)) => 0, // Disabled
+                None => {
+                    // Folder not found in either state - skip saving its state for this preset
                     println!("[overwrite_preset] Warning: Asset ID {} folder not found on disk during preset save (path: {}). Skipping.", asset_id, clean_relative_path_str);
                     not_found_count += 1;
                     continue; // Skip to next asset
                 }
-            };
+            };
+
+            // Insert the current state into the preset
+            insert_stmt.execute(params![preset_id, asset_id, is_currently_enabled_on_disk])
+                .map_err(|e| format!("Failed to save state for asset {}: {}", asset_id, e))?;
+            saved_count += 1;
+        }
+    } // --- End scope for insert_stmt --- `insert_stmt` is dropped here, releasing the borrow on `tx`
+
+    // Snapshot mod toggle states (d3dx_user.ini) alongside the folder states
+    if let Err(e) = capture_preset_variables(&tx, preset_id, &base_mods_path) {
+        eprintln!("[overwrite_preset] Warning: Failed to save persisted variables: {}", e);
+    }
+    if let Err(e) = capture_preset_ini_states(&tx, preset_id, &base_mods_path) {
+        eprintln!("[overwrite_preset] Warning: Failed to save INI states: {}", e);
+    }
+
+    // 4. Commit the transaction (Now safe as insert_stmt is out of scope)
+    tx.commit().map_err(|e| format!("Failed to commit transaction: {}", e))?;
+    sidecar_asset_ids.extend(preset_member_ids(&conn, preset_id));
+    sidecar_asset_ids.sort_unstable();
+    sidecar_asset_ids.dedup();
+    sync_asset_sidecars(&conn, &sidecar_asset_ids);
+
+    println!("[overwrite_preset] Preset ID {} overwritten successfully. Saved state for {} assets (skipped {} not found).", preset_id, saved_count, not_found_count);
+    Ok(())
+}
+
+// Parses the [Key...] sections of one INI file. `ini_file` is its path relative to the mod folder.
+fn read_ini_keybinds(ini_path: &Path, ini_file: &str) -> io::Result<Vec<KeybindInfo>> {
+    let doc = IniDocument::load(ini_path)?;
+    let mut keybinds = Vec::new();
+
+    let persistent_vars: HashSet<String> = doc.constants().into_iter()
+        .filter(|decl| decl.is_persist)
+        .map(|decl| decl.name.to_lowercase())
+        .collect();
+
+    for key_section in doc.key_sections() {
+        let keys: Vec<String> = key_section.keys.into_iter().map(|(_, v)| v).filter(|v| !v.is_empty()).collect();
+        if keys.is_empty() {
+            continue;
+        }
+        let label = doc.sections[key_section.section_index].leading_comments.iter()
+            .filter(|c| !c.is_empty())
+            .cloned()
+            .collect::<Vec<String>>()
+            .join(" ");
+        keybinds.push(KeybindInfo {
+            title: key_section.name,
+            key: keys.join(", "),
+            keys,
+            back: key_section.back.into_iter().map(|(_, v)| v).filter(|v| !v.is_empty()).collect(),
+            bind_type: key_section.bind_type,
+            condition: key_section.condition,
+            variables: key_section.variables.into_iter().map(|var| KeybindVariable {
+                persist: persistent_vars.contains(&var.name.to_lowercase()),
+                name: var.name,
+                values: var.values,
+            }).collect(),
+            label: if label.is_empty() { None } else { Some(label) },
+            ini_file: ini_file.to_string(),
+        });
+    }
+
+    Ok(keybinds)
+}
+
+#[command]
+fn get_ini_keybinds(asset_id: i64, db_state: State<DbState>) -> Result<Vec<KeybindInfo>, String> { // CmdResult is Result<T, String>
+    println!("[get_ini_keybinds] COMMAND START for asset ID: {}", asset_id);
+
+    let result: Result<Vec<KeybindInfo>, String> = (|| {
+        println!("[get_ini_keybinds] Attempting to acquire DB lock...");
+        let conn_guard = db_state.0.lock().map_err(|_| {
+            eprintln!("[get_ini_keybinds] ERROR: DB lock poisoned!");
+            "DB lock poisoned".to_string()
+        })?;
+        println!("[get_ini_keybinds] DB lock acquired.");
+        let conn = &*conn_guard; // Dereference the guard to get the connection
+
+        println!("[get_ini_keybinds] Attempting to get base mods path setting directly...");
+        // --- FIX: Provide arguments to query_row ---
+        let mods_folder_path_str_opt: Option<String> = conn.query_row(
+            "SELECT value FROM settings WHERE key = ?1", // SQL query
+            params![SETTINGS_KEY_MODS_FOLDER],          // Parameters
+            |row| row.get(0),                           // Mapping closure
+        ).optional().map_err(|e| format!("DB error fetching mods folder setting: {}", e))?;
+        // --- End Fix ---
+
+        let base_mods_path = mods_folder_path_str_opt
+            .map(PathBuf::from)
+            .ok_or_else(|| {
+                eprintln!("[get_ini_keybinds] ERROR: Mods folder path not set in settings.");
+                "Mods folder path not set".to_string()
+            })?;
+        println!("[get_ini_keybinds] Base mods path obtained directly: {}", base_mods_path.display());
+
+
+        println!("[get_ini_keybinds] Calling find_asset_ini_paths...");
+        let ini_paths = find_asset_ini_paths(conn, asset_id, &base_mods_path)
+            .map_err(|e| {
+                eprintln!("[get_ini_keybinds] ERROR from find_asset_ini_paths: {}", e);
+                format!("Error finding INI paths: {}", e)
+            })?;
+        println!("[get_ini_keybinds] find_asset_ini_paths returned {} paths.", ini_paths.len());
+
+        // --- Release the lock explicitly before file I/O ---
+        drop(conn_guard);
+        println!("[get_ini_keybinds] DB lock released before file parsing.");
+
+        if ini_paths.is_empty() {
+             println!("[get_ini_keybinds] No INI files found for asset ID {}", asset_id);
+             return Ok(Vec::new()); // Return empty early if no INIs exist
+        }
+
+        let mut found_keybinds: Vec<KeybindInfo> = Vec::new();
+
+        for (ini_file, ini_path) in ini_paths {
+            // 3DMigoto skips files prefixed with DISABLED, so their binds are inactive
+            if is_disabled_ini(&ini_file) {
+                println!("[get_ini_keybinds] Skipping disabled INI: {}", ini_path.display());
+                continue;
+            }
+            println!("[get_ini_keybinds] Parsing INI at: {}", ini_path.display());
+
+            match read_ini_keybinds(&ini_path, &ini_file) {
+                Ok(keybinds) => {
+                    println!("[get_ini_keybinds] Found {} keybinds in {}.", keybinds.len(), ini_path.display());
+                    found_keybinds.extend(keybinds);
+                }
+                Err(e) => {
+                    // Skip to the next file if this one can't be read
+                    eprintln!("[get_ini_keybinds] ERROR: Failed to read INI file {}: {}. Skipping.", ini_path.display(), e);
+                }
+            }
+        } // --- End loop through INI paths ---
+
+        println!("[get_ini_keybinds] Found {} keybinds across all INI files for asset ID {}", found_keybinds.len(), asset_id);
+        Ok(found_keybinds)
+
+    })(); // Execute the closure
+
+    println!("[get_ini_keybinds] COMMAND END for asset ID: {}", asset_id);
+    result // Return the result of the closure (Result<Vec<KeybindInfo>, String>)
+}
+
+const INI_BACKUP_EXTENSION: &str = "bak"; // mod.ini -> mod.ini.bak, holds the file as it was before the first edit
+
+// Finds the INI of an asset that contains `section`. With `ini_file` (path relative to the mod folder)
+// given, only that file is considered.
+fn resolve_keybind_ini(
+    db_state: &DbState,
+    asset_id: i64,
+    section: &str,
+    ini_file: Option<&str>,
+) -> Result<(PathBuf, IniDocument), String> {
+    let base_mods_path = get_mods_base_path_from_settings(db_state).map_err(|e| e.to_string())?;
+    let ini_paths = {
+        let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+        find_asset_ini_paths(&conn, asset_id, &base_mods_path).map_err(|e| format!("Error finding INI paths: {}", e))?
+    };
+
+    let wanted = ini_file.map(|f| f.replace("\\", "/"));
+    let mut matches = Vec::new();
+    for (relative_path, ini_path) in ini_paths {
+        if let Some(wanted) = &wanted {
+            if !relative_path.eq_ignore_ascii_case(wanted) { continue; }
+        }
+        let doc = IniDocument::load(&ini_path).map_err(|e| format!("Failed to read INI file {}: {}", ini_path.display(), e))?;
+        if doc.section_index(section).is_some() {
+            matches.push((ini_path, doc));
+        }
+    }
+
+    match matches.len() {
+        0 => Err(format!("Section [{}] not found in the INI files of asset {}.", section, asset_id)),
+        1 => Ok(matches.remove(0)),
+        _ => Err(format!("Section [{}] exists in {} INI files of asset {}. Please specify which file to edit.", section, matches.len(), asset_id)),
+    }
+}
+
+fn ini_backup_path(ini_path: &Path) -> PathBuf {
+    let mut backup_name = ini_path.as_os_str().to_owned();
+    backup_name.push(format!(".{}", INI_BACKUP_EXTENSION));
+    PathBuf::from(backup_name)
+}
+
+// Line index of the n-th `key =` / `back =` line in a section
+fn find_keybind_line(doc: &IniDocument, section: &str, key: &str, occurrence: usize) -> Option<usize> {
+    let section_index = doc.section_index(section)?;
+    doc.assignments(section_index)
+        .filter(|(_, _, k, v)| k.eq_ignore_ascii_case(key) && v.is_some())
+        .nth(occurrence)
+        .map(|(line_index, _, _, _)| line_index)
+}
+
+fn validate_keybind_target(key: &str) -> Result<(), String> {
+    if key.eq_ignore_ascii_case("key") || key.eq_ignore_ascii_case("back") {
+        Ok(())
+    } else {
+        Err(format!("Only 'key' and 'back' bindings can be edited (got '{}').", key))
+    }
+}
+
+// Writes one binding into the INI, keeping a backup of the untouched file on first edit
+fn write_keybind_value(ini_path: &Path, mut doc: IniDocument, section: &str, key: &str, occurrence: usize, new_value: &str) -> Result<(), String> {
+    if doc.lossy {
+        return Err(format!("INI file {} is not valid UTF-8 and can't be edited safely.", ini_path.display()));
+    }
+    let line_index = find_keybind_line(&doc, section, key, occurrence)
+        .ok_or_else(|| format!("No '{}' binding #{} in section [{}].", key, occurrence + 1, section))?;
+
+    let backup_path = ini_backup_path(ini_path);
+    if !backup_path.exists() {
+        fs::copy(ini_path, &backup_path).map_err(|e| format!("Failed to create backup {}: {}", backup_path.display(), e))?;
+        println!("[write_keybind_value] Created backup {}", backup_path.display());
+    }
+
+    doc.set_assignment_value(line_index, new_value);
+    fs::write(ini_path, doc.to_string()).map_err(|e| format!("Failed to write INI file {}: {}", ini_path.display(), e))?;
+    println!("[write_keybind_value] [{}] {} = {} written to {}", section, key, new_value, ini_path.display());
+    Ok(())
+}
+
+#[command]
+fn set_ini_keybind(
+    asset_id: i64,
+    section: String,
+    key: String,
+    new_value: String,
+    ini_file: Option<String>,
+    occurrence: Option<usize>, // Which `key =` line when a section binds several (default first)
+    db_state: State<DbState>
+) -> CmdResult<()> {
+    println!("[set_ini_keybind] Asset {}: [{}] {} -> '{}' (file: {:?})", asset_id, section, key, new_value, ini_file);
+    validate_keybind_target(&key)?;
+    let new_value = new_value.trim();
+    if new_value.is_empty() {
+        return Err("Keybind cannot be empty.".to_string());
+    }
+    if new_value.contains(['\n', '\r', ';', '[', ']']) {
+        return Err(format!("Invalid keybind '{}'.", new_value));
+    }
+
+    let (ini_path, doc) = resolve_keybind_ini(&db_state, asset_id, &section, ini_file.as_deref())?;
+    write_keybind_value(&ini_path, doc, &section, &key, occurrence.unwrap_or(0), new_value)?;
+    refresh_keybind_asset(&db_state, asset_id)
+}
+
+#[command]
+fn reset_ini_keybind(
+    asset_id: i64,
+    section: String,
+    key: String,
+    ini_file: Option<String>,
+    occurrence: Option<usize>,
+    db_state: State<DbState>
+) -> CmdResult<String> {
+    println!("[reset_ini_keybind] Asset {}: resetting [{}] {} (file: {:?})", asset_id, section, key, ini_file);
+    validate_keybind_target(&key)?;
+    let occurrence = occurrence.unwrap_or(0);
+
+    let (ini_path, doc) = resolve_keybind_ini(&db_state, asset_id, &section, ini_file.as_deref())?;
+    let backup_path = ini_backup_path(&ini_path);
+    if !backup_path.exists() {
+        return Err(format!("No backup found for {}. The binding was never changed from GMM.", ini_path.display()));
+    }
+
+    let original_doc = IniDocument::load(&backup_path).map_err(|e| format!("Failed to read backup {}: {}", backup_path.display(), e))?;
+    let original_value = find_keybind_line(&original_doc, &section, &key, occurrence)
+        .and_then(|line_index| match &original_doc.lines[line_index].kind {
+            migoto_ini::IniLineKind::Assignment { value: Some(v), .. } => Some(v.clone()),
+            _ => None,
+        })
+        .ok_or_else(|| format!("Original '{}' binding of [{}] not found in backup.", key, section))?;
+
+    write_keybind_value(&ini_path, doc, &section, &key, occurrence, &original_value)?;
+    refresh_keybind_asset(&db_state, asset_id)?;
+    Ok(original_value)
+}
+
+// Keybind edits land in the mod's source folder; pushes them to a copied link-mode tree
+fn refresh_keybind_asset(db_state: &DbState, asset_id: i64) -> CmdResult<()> {
+    let base_mods_path = get_mods_base_path_from_settings(db_state).map_err(|e| e.to_string())?;
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    refresh_edited_asset(&conn, &base_mods_path, asset_id)
+        .map_err(|e| format!("Keybind saved, but the active copy of the mod could not be updated: {}", e))
+}
+
+#[command]
+fn get_keybind_collisions(include_reserved: Option<bool>, db_state: State<DbState>, app_handle: AppHandle) -> CmdResult<KeybindCollisionReport> {
+    let include_reserved = include_reserved.unwrap_or(true);
+    println!("[get_keybind_collisions] Building collision report (reserved keys: {})", include_reserved);
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+
+    let (assets, game_slug) = {
+        let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+        let mut stmt = conn.prepare("SELECT a.id, a.name, a.folder_name, e.slug, a.root_id FROM assets a JOIN entities e ON a.entity_id = e.id WHERE a.missing_since IS NULL")
+            .map_err(|e| format!("Failed to prepare asset fetch: {}", e))?;
+        let assets = stmt.query_map([], |row| Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?.replace("\\", "/"),
+            row.get::<_, String>(3)?,
+            row.get::<_, Option<i64>>(4)?,
+        ))).and_then(|rows| rows.collect::<SqlResult<Vec<_>>>())
+           .map_err(|e| format!("Failed to fetch assets: {}", e))?;
+        let game_slug = get_setting_value(&conn, DB_INTERNAL_GAME_SLUG_KEY).map_err(|e| e.to_string())?;
+        (assets, game_slug)
+    }; // Lock released before reading INI files
+
+    // --- Collect bindings of every enabled asset ---
+    let mut scanned_assets = 0;
+    let mut bindings: Vec<(migoto_ini::KeyExpression, KeybindBinding)> = Vec::new();
+    for (asset_id, asset_name, clean_relative_path, entity_slug, root_id) in assets {
+        let mod_folder = match locate_asset_folder(&base_mods_path, root_id, &clean_relative_path) {
+            Some((folder, true)) => folder,
+            _ => continue, // Disabled or missing mods can't collide
+        };
+        scanned_assets += 1;
+        for (ini_file, ini_path) in list_ini_files_recursive(&mod_folder) {
+            if is_disabled_ini(&ini_file) { continue; }
+            let keybinds = match read_ini_keybinds(&ini_path, &ini_file) {
+                Ok(k) => k,
+                Err(e) => { eprintln!("[get_keybind_collisions] Failed to read {}: {}", ini_path.display(), e); continue; }
+            };
+            for keybind in keybinds {
+                for raw_key in keybind.keys {
+                    if let Some(expression) = migoto_ini::KeyExpression::parse(&raw_key) {
+                        bindings.push((expression.clone(), KeybindBinding {
+                            asset_id,
+                            asset_name: asset_name.clone(),
+                            entity_slug: entity_slug.clone(),
+                            section: keybind.title.clone(),
+                            ini_file: ini_file.clone(),
+                            raw_key,
+                            normalized_key: expression.to_string(),
+                        }));
+                    }
+                }
+            }
+        }
+    }
+    let total_bindings = bindings.len();
+
+    let reserved: Vec<(migoto_ini::KeyExpression, String)> = match (include_reserved, game_slug) {
+        (true, Some(slug)) => load_game_definitions(&app_handle, &slug).reserved_keys.into_iter()
+            .filter_map(|r| migoto_ini::KeyExpression::parse(&r.key).map(|expr| (expr, r.action)))
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    // --- Group by main key, keep bindings that overlap another mod or a reserved key ---
+    let mut by_main_key: HashMap<String, Vec<usize>> = HashMap::new();
+    for (index, (expression, _)) in bindings.iter().enumerate() {
+        by_main_key.entry(expression.main_key.clone()).or_default().push(index);
+    }
+
+    let mut collisions = Vec::new();
+    for (main_key, indices) in by_main_key {
+        let mut involved = Vec::new();
+        let mut reserved_actions = Vec::new();
+        for &i in &indices {
+            let (expression, binding) = &bindings[i];
+            let clashes_with_mod = indices.iter().any(|&j| {
+                bindings[j].1.asset_id != binding.asset_id && expression.overlaps(&bindings[j].0)
+            });
+            let mut clashes_with_game = false;
+            for (reserved_expression, action) in &reserved {
+                if expression.overlaps(reserved_expression) {
+                    clashes_with_game = true;
+                    if !reserved_actions.contains(action) {
+                        reserved_actions.push(action.clone());
+                    }
+                }
+            }
+            if clashes_with_mod || clashes_with_game {
+                involved.push(binding.clone());
+            }
+        }
+        if !involved.is_empty() {
+            involved.sort_by_key(|b| b.asset_name.to_lowercase());
+            collisions.push(KeybindCollision { key: main_key, bindings: involved, reserved_actions });
+        }
+    }
+    collisions.sort_by(|a, b| b.bindings.len().cmp(&a.bindings.len()).then_with(|| a.key.cmp(&b.key)));
+
+    println!("[get_keybind_collisions] Scanned {} enabled assets, {} bindings, {} colliding keys.", scanned_assets, total_bindings, collisions.len());
+    Ok(KeybindCollisionReport { scanned_assets, total_bindings, collisions })
+}
+
+// --- Persisted Variables (d3dx_user.ini) ---
+
+fn d3dx_user_ini_path(base_mods_path: &Path) -> Result<PathBuf, AppError> {
+    base_mods_path.parent()
+        .map(|parent| parent.join(D3DX_USER_INI))
+        .ok_or_else(|| AppError::Config(format!("Mods folder {} has no parent folder.", base_mods_path.display())))
+}
+
+// Prefix 3DMigoto uses for the persisted variables of one INI: `$\namespace` for
+// namespaced INIs, otherwise `$\<mods folder>\<mod path>\<INI path in the mod>`. Always lowercase.
+fn persisted_variable_prefix(base_mods_path: &Path, clean_relative_path: &str, ini_file: &str, doc: &IniDocument) -> String {
+    match doc.namespace() {
+        Some(namespace) => format!("$\\{}", namespace.replace('/', "\\")).to_lowercase(),
+        None => {
+            let mods_dir = base_mods_path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| "mods".to_string());
+            format!("$\\{}\\{}\\{}", mods_dir, clean_relative_path.replace('/', "\\"), ini_file.replace('/', "\\")).to_lowercase()
+        }
+    }
+}
+
+// Lowercase key -> (line index, value) for every `$\...` entry in d3dx_user.ini
+fn read_d3dx_user_values(doc: &IniDocument) -> HashMap<String, (usize, String)> {
+    let mut values = HashMap::new();
+    for (line_index, line) in doc.lines.iter().enumerate() {
+        if let migoto_ini::IniLineKind::Assignment { key, value: Some(value), .. } = &line.kind {
+            if key.starts_with("$\\") {
+                values.insert(key.to_lowercase(), (line_index, value.clone()));
+            }
+        }
+    }
+    values
+}
+
+fn load_d3dx_user_ini(base_mods_path: &Path) -> Result<(PathBuf, IniDocument), AppError> {
+    let path = d3dx_user_ini_path(base_mods_path)?;
+    let doc = if path.is_file() { IniDocument::load(&path)? } else { IniDocument::default() };
+    Ok((path, doc))
+}
+
+// Sets (Some) or removes (None) entries in d3dx_user.ini. New entries go into [Constants].
+// 3DMigoto rewrites this file while the game runs, so changes only stick with the game closed.
+fn write_d3dx_user_values(path: &Path, mut doc: IniDocument, updates: &[(String, Option<String>)]) -> Result<usize, AppError> {
+    if doc.lossy {
+        return Err(AppError::ModOperation(format!("{} is not valid UTF-8 and can't be edited safely.", path.display())));
+    }
+    let mut changed = 0;
+    for (key, new_value) in updates {
+        let existing = read_d3dx_user_values(&doc).get(&key.to_lowercase()).cloned();
+        match (existing, new_value) {
+            (Some((line_index, current)), Some(value)) => {
+                if current != *value {
+                    doc.set_assignment_value(line_index, value);
+                    changed += 1;
+                }
+            }
+            (Some((line_index, _)), None) => {
+                doc.splice_lines(line_index..line_index + 1, &[]);
+                changed += 1;
+            }
+            (None, Some(value)) => {
+                let insert_at = match doc.section_index("Constants") {
+                    Some(section_index) => {
+                        // After the last non-blank line, so the blank line before the next section stays
+                        let body = doc.sections[section_index].body.clone();
+                        body.clone().rev()
+                            .find(|&i| doc.lines[i].kind != migoto_ini::IniLineKind::Blank)
+                            .map_or(body.start, |i| i + 1)
+                    }
+                    None => {
+                        let end = doc.lines.len();
+                        doc.splice_lines(end..end, &["[Constants]".to_string()]);
+                        doc.lines.len()
+                    }
+                };
+                doc.splice_lines(insert_at..insert_at, &[format!("{} = {}", key, value)]);
+                changed += 1;
+            }
+            (None, None) => {}
+        }
+    }
+    if changed > 0 {
+        fs::write(path, doc.to_string())?;
+        println!("[write_d3dx_user_values] Updated {} entries in {}", changed, path.display());
+    }
+    Ok(changed)
+}
+
+// Persisted variables declared by the (enabled) INIs of one mod folder, with their current values
+fn collect_persisted_variables(
+    base_mods_path: &Path,
+    root_id: Option<i64>,
+    clean_relative_path: &str,
+    user_values: &HashMap<String, (usize, String)>,
+) -> Vec<PersistedVariable> {
+    let mod_folder = match locate_asset_folder(base_mods_path, root_id, clean_relative_path) {
+        Some((folder, _)) => folder,
+        None => return Vec::new(),
+    };
+    let mut variables = Vec::new();
+    for (ini_file, ini_path) in list_ini_files_recursive(&mod_folder) {
+        if is_disabled_ini(&ini_file) { continue; }
+        let doc = match IniDocument::load(&ini_path) {
+            Ok(d) => d,
+            Err(e) => { eprintln!("[collect_persisted_variables] Failed to read {}: {}", ini_path.display(), e); continue; }
+        };
+        let prefix = persisted_variable_prefix(base_mods_path, clean_relative_path, &ini_file, &doc);
+        for declaration in doc.constants().into_iter().filter(|d| d.is_persist) {
+            let user_ini_key = format!("{}\\{}", prefix, declaration.name.trim_start_matches('$').to_lowercase());
+            variables.push(PersistedVariable {
+                ini_file: ini_file.clone(),
+                value: user_values.get(&user_ini_key).map(|(_, v)| v.clone()),
+                name: declaration.name,
+                default_value: declaration.initial_value,
+                user_ini_key,
+            });
+        }
+    }
+    variables
+}
+
+// Stores the current persisted values of the preset's enabled assets (Internal error type)
+fn capture_preset_variables(conn: &Connection, preset_id: i64, base_mods_path: &Path) -> Result<usize, AppError> {
+    let (_, user_doc) = load_d3dx_user_ini(base_mods_path)?;
+    let user_values = read_d3dx_user_values(&user_doc);
+    conn.execute("DELETE FROM preset_asset_variables WHERE preset_id = ?1", params![preset_id])?;
+    if user_values.is_empty() {
+        return Ok(0);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT pa.asset_id, a.folder_name, a.root_id FROM preset_assets pa JOIN assets a ON pa.asset_id = a.id
+         WHERE pa.preset_id = ?1 AND pa.is_enabled = 1"
+    )?;
+    let assets = stmt.query_map(params![preset_id], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?.replace("\\", "/"), row.get::<_, Option<i64>>(2)?)))?
+        .collect::<SqlResult<Vec<(i64, String, Option<i64>)>>>()?;
+
+    let mut saved = 0;
+    for (asset_id, clean_relative_path, root_id) in assets {
+        for variable in collect_persisted_variables(base_mods_path, root_id, &clean_relative_path, &user_values) {
+            if let Some(value) = variable.value {
+                conn.execute(
+                    "INSERT OR REPLACE INTO preset_asset_variables (preset_id, asset_id, ini_file, variable, value) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![preset_id, asset_id, variable.ini_file, variable.name, value],
+                )?;
+                saved += 1;
+            }
+        }
+    }
+    println!("[capture_preset_variables] Saved {} persisted variables for preset {}", saved, preset_id);
+    Ok(saved)
+}
+
+// Writes the stored variables of a preset (and its layers, in order) back into d3dx_user.ini
+fn restore_preset_variables(conn: &Connection, preset: &Preset, base_mods_path: &Path) -> Result<usize, AppError> {
+    let mut preset_order = Vec::new();
+    collect_preset_layer_order(conn, preset, &mut preset_order)?;
+
+    // (asset_id, ini_file, variable, value), later presets override earlier ones
+    let mut stored: Vec<(i64, String, String, String)> = Vec::new();
+    for preset_id in preset_order {
+        let mut stmt = conn.prepare("SELECT asset_id, ini_file, variable, value FROM preset_asset_variables WHERE preset_id = ?1")?;
+        let rows = stmt.query_map(params![preset_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+            .collect::<SqlResult<Vec<(i64, String, String, String)>>>()?;
+        stored.extend(rows);
+    }
+    if stored.is_empty() {
+        return Ok(0);
+    }
+
+    let (user_path, user_doc) = load_d3dx_user_ini(base_mods_path)?;
+    let mut updates: Vec<(String, Option<String>)> = Vec::new();
+    let mut prefix_cache: HashMap<(i64, String), Option<String>> = HashMap::new();
+    for (asset_id, ini_file, variable, value) in stored {
+        let prefix = prefix_cache.entry((asset_id, ini_file.clone())).or_insert_with(|| {
+            let location = get_asset_location_info(conn, asset_id).ok()?;
+            let (folder, _) = locate_asset_folder(base_mods_path, location.root_id, &location.clean_relative_path)?;
+            let doc = IniDocument::load(&folder.join(&ini_file)).ok()?;
+            Some(persisted_variable_prefix(base_mods_path, &location.clean_relative_path, &ini_file, &doc))
+        });
+        if let Some(prefix) = prefix {
+            let key = format!("{}\\{}", prefix, variable.trim_start_matches('$').to_lowercase());
+            updates.retain(|(k, _)| *k != key);
+            updates.push((key, Some(value)));
+        }
+    }
+    write_d3dx_user_values(&user_path, user_doc, &updates)
+}
+
+// Preset ids in apply order: layers first (recursively), then the preset itself
+fn collect_preset_layer_order(conn: &Connection, preset: &Preset, order: &mut Vec<i64>) -> Result<(), AppError> {
+    if order.contains(&preset.id) {
+        return Ok(()); // Already applied as an earlier layer (or a cycle)
+    }
+    for layer_id in &preset.included_preset_ids {
+        let layer = fetch_preset(conn, *layer_id)?;
+        collect_preset_layer_order(conn, &layer, order)?;
+    }
+    order.push(preset.id);
+    Ok(())
+}
+
+// --- Activation Mode ---
+
+// Moves one mod from one backend's storage to the other's, keeping its enabled state (Internal error type)
+// `to_role` is the role of the target's mod root (ROOT_ROLE_ACTIVE for the primary Mods folder).
+fn migrate_asset_activation(from: &dyn activation::ActivationBackend, to: &dyn activation::ActivationBackend, to_role: &str, clean_relative_path: &str) -> Result<bool, AppError> {
+    let (_, was_enabled) = from.locate(clean_relative_path)
+        .ok_or_else(|| AppError::NotFound(format!("Mod folder '{}' not found.", clean_relative_path)))?;
+    from.set_enabled(clean_relative_path, false)?;
+    let (source_path, _) = from.locate(clean_relative_path)
+        .ok_or_else(|| AppError::NotFound(format!("Mod folder '{}' disappeared while deactivating.", clean_relative_path)))?;
+    let target_path = to.inactive_path(clean_relative_path)
+        .ok_or_else(|| AppError::ModOperation(format!("Invalid folder name '{}'", clean_relative_path)))?;
+    activation::move_folder(&source_path, &target_path)?;
+    // Archive roots never hold active mods; the mod simply stays disabled there
+    if was_enabled && to_role != activation::ROOT_ROLE_LIBRARY {
+        to.set_enabled(clean_relative_path, true)?;
+    }
+    Ok(was_enabled)
+}
+
+#[command]
+fn get_activation_settings() -> CmdResult<ActivationSettings> {
+    Ok(activation::active_settings())
+}
+
+// Switches how mods are activated for the current game, moving every known mod folder accordingly.
+// Returns the number of migrated mods.
+#[command]
+fn set_activation_mode(mode: String, library_path: Option<String>, db_state: State<DbState>, app_handle: AppHandle) -> CmdResult<usize> {
+    println!("[set_activation_mode] Switching to '{}' (library: {:?})", mode, library_path);
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+    let current = activation::active_settings();
+
+    let new_settings = match mode.as_str() {
+        activation::ACTIVATION_MODE_RENAME => ActivationSettings { mode: mode.clone(), library_path: None, ..current.clone() },
+        activation::ACTIVATION_MODE_LINK => {
+            let library = PathBuf::from(library_path.as_deref().map(str::trim).filter(|p| !p.is_empty())
+                .ok_or_else(|| "A library folder is required for the link mode.".to_string())?);
+            if !library.is_absolute() {
+                return Err("The library folder must be an absolute path.".to_string());
+            }
+            if library.starts_with(&base_mods_path) || base_mods_path.starts_with(&library) {
+                return Err("The library folder must not be inside the Mods folder (or contain it).".to_string());
+            }
+            fs::create_dir_all(&library).map_err(|e| format!("Failed to create library folder '{}': {}", library.display(), e))?;
+            ActivationSettings { mode: mode.clone(), library_path: Some(library), ..current.clone() }
+        }
+        _ => return Err(format!("Unknown activation mode '{}'.", mode)),
+    };
+    if current.mode == new_settings.mode {
+        if current.library_path == new_settings.library_path {
+            return Ok(0);
+        }
+        return Err("Switch back to the rename mode before choosing a different library folder.".to_string());
+    }
+
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    backup_before_operation(&conn, &app_handle, "activation_mode");
+    // Only the primary Mods folder follows the activation mode
+    let asset_paths: Vec<String> = conn.prepare("SELECT folder_name FROM assets WHERE root_id IS NULL")
+        .and_then(|mut stmt| stmt.query_map([], |row| row.get::<_, String>(0))?.collect::<SqlResult<Vec<String>>>())
+        .map_err(|e| format!("Failed to fetch assets: {}", e))?
+        .into_iter().map(|p| p.replace("\\", "/")).collect();
+
+    let from = activation::backend_with(&base_mods_path, &current);
+    let to = activation::backend_with(&base_mods_path, &new_settings);
+    let mut migrated: Vec<&str> = Vec::new();
+    for clean_relative_path in &asset_paths {
+        if from.locate(clean_relative_path).is_none() {
+            println!("[set_activation_mode] '{}' not found on disk, skipping.", clean_relative_path);
+            continue;
+        }
+        if let Err(e) = migrate_asset_activation(from.as_ref(), to.as_ref(), activation::ROOT_ROLE_ACTIVE, clean_relative_path) {
+            // Put already migrated mods back so the old mode keeps working
+            eprintln!("[set_activation_mode] Failed on '{}': {}. Rolling back {} mods.", clean_relative_path, e, migrated.len());
+            for done in migrated.iter().rev() {
+                if let Err(rollback_error) = migrate_asset_activation(to.as_ref(), from.as_ref(), activation::ROOT_ROLE_ACTIVE, done) {
+                    eprintln!("[set_activation_mode] Rollback failed for '{}': {}", done, rollback_error);
+                }
+            }
+            persist_materialized_folders(&conn);
+            return Err(format!("Failed to move '{}': {}. No changes were kept.", clean_relative_path, e));
+        }
+        migrated.push(clean_relative_path);
+    }
+    persist_materialized_folders(&conn);
+
+    let library_setting = new_settings.library_path.as_ref().map(|p| p.to_string_lossy().to_string()).unwrap_or_default();
+    conn.execute("INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)", params![SETTINGS_KEY_ACTIVATION_MODE, new_settings.mode])
+        .and_then(|_| conn.execute("INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)", params![SETTINGS_KEY_MOD_LIBRARY, library_setting]))
+        .map_err(|e| format!("Mods were moved but the setting could not be saved: {}", e))?;
+    activation::set_active_settings(new_settings);
+
+    println!("[set_activation_mode] Migrated {} mods.", migrated.len());
+    Ok(migrated.len())
+}
+
+// Changes how disabled mods are marked in the rename mode, converting every disabled mod folder.
+// Markers stock 3DMigoto would still load are refused, except suffixes, which come with a warning.
+#[command]
+fn set_disabled_marker(marker: DisabledMarker, db_state: State<DbState>, app_handle: AppHandle) -> CmdResult<DisabledMarkerChange> {
+    println!("[set_disabled_marker] Switching to {:?}", marker);
+    marker.validate()?;
+    let warning = marker.loader_warning();
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+    let current = activation::active_settings();
+    if current.disabled_marker == marker {
+        return Ok(DisabledMarkerChange { converted: 0, warning });
+    }
+    let new_settings = ActivationSettings { disabled_marker: marker.clone(), ..current.clone() };
+
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    backup_before_operation(&conn, &app_handle, "disabled_marker");
+    let roots = activation::mod_roots();
+    // Old and new backend for a mod's folder; None when the marker doesn't apply there
+    let backends_for = |root_id: Option<i64>| -> Option<(Box<dyn activation::ActivationBackend>, Box<dyn activation::ActivationBackend>)> {
+        match root_id {
+            None if current.mode == activation::ACTIVATION_MODE_RENAME => Some((
+                activation::backend_with(&base_mods_path, &current),
+                activation::backend_with(&base_mods_path, &new_settings),
+            )),
+            None => None,
+            Some(id) => roots.iter()
+                .find(|r| r.id == id && r.role == activation::ROOT_ROLE_ACTIVE)
+                .map(|root| (activation::backend_for_root(root, &current), activation::backend_for_root(root, &new_settings))),
+        }
+    };
+
+    let assets: Vec<(String, Option<i64>)> = conn.prepare("SELECT folder_name, root_id FROM assets")
+        .and_then(|mut stmt| stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<i64>>(1)?)))?.collect::<SqlResult<Vec<_>>>())
+        .map_err(|e| format!("Failed to fetch assets: {}", e))?
+        .into_iter().map(|(p, root_id)| (p.replace("\\", "/"), root_id)).collect();
+
+    let mut converted: Vec<(String, Option<i64>)> = Vec::new();
+    for (clean_relative_path, root_id) in assets {
+        let Some((from, to)) = backends_for(root_id) else { continue };
+        // Enabled folders carry no marker, only disabled ones move
+        if !matches!(from.locate(&clean_relative_path), Some((_, false))) {
+            continue;
+        }
+        if let Err(e) = migrate_asset_activation(from.as_ref(), to.as_ref(), activation::ROOT_ROLE_ACTIVE, &clean_relative_path) {
+            eprintln!("[set_disabled_marker] Failed on '{}': {}. Rolling back {} mods.", clean_relative_path, e, converted.len());
+            for (done, done_root_id) in converted.iter().rev() {
+                let Some((done_from, done_to)) = backends_for(*done_root_id) else { continue };
+                if let Err(rollback_error) = migrate_asset_activation(done_to.as_ref(), done_from.as_ref(), activation::ROOT_ROLE_ACTIVE, done) {
+                    eprintln!("[set_disabled_marker] Rollback failed for '{}': {}", done, rollback_error);
+                }
+            }
+            return Err(format!("Failed to rename '{}': {}. No changes were kept.", clean_relative_path, e));
+        }
+        converted.push((clean_relative_path, root_id));
+    }
+
+    let json = serde_json::to_string(&marker).map_err(|e| e.to_string())?;
+    conn.execute("INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)", params![SETTINGS_KEY_DISABLED_MARKER, json])
+        .map_err(|e| format!("Folders were renamed but the setting could not be saved: {}", e))?;
+    activation::set_active_settings(new_settings);
+
+    println!("[set_disabled_marker] Converted {} disabled mods.", converted.len());
+    Ok(DisabledMarkerChange { converted: converted.len(), warning })
+}
+
+// --- Mods Folder Relocation ---
+
+// Stores the Mods folder path after its contents were moved there (Internal error type)
+fn save_relocated_mods_path(conn: &Connection, old_mods_path: &Path, new_mods_path: &Path) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+        params![SETTINGS_KEY_MODS_FOLDER, new_mods_path.to_string_lossy()],
+    )?;
+    if let Err(e) = rebase_materialized_folders(conn, old_mods_path, new_mods_path) {
+        eprintln!("[relocate_mods_library] Failed to update link-mode records: {}", e);
+    }
+    Ok(())
+}
+
+// Moves (or copies, across drives) the primary Mods folder to `new_path` and updates the setting.
+// Assets store paths relative to the Mods folder, so ids, preset links and metadata stay as they are.
+#[command]
+async fn relocate_mods_library(new_path: String, db_state: State<'_, DbState>, app_handle: AppHandle) -> CmdResult<String> {
+    println!("[relocate_mods_library] Relocating Mods folder to '{}'", new_path);
+    let old_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+    let new_path = PathBuf::from(new_path.trim());
+    if !new_path.is_absolute() {
+        return Err("The new location must be an absolute path.".to_string());
+    }
+    if new_path == old_path {
+        return Ok("The Mods folder is already there.".to_string());
+    }
+    if !old_path.is_dir() {
+        return Err(format!("Current Mods folder not found: {}", old_path.display()));
+    }
+    let overlaps = |other: &Path| new_path.starts_with(other) || other.starts_with(&new_path);
+    if overlaps(&old_path) {
+        return Err("The new location must not be inside the current Mods folder (or contain it).".to_string());
+    }
+    let roots = activation::mod_roots();
+    if activation::active_settings().library_path.as_deref().is_some_and(overlaps) || roots.iter().any(|r| overlaps(&r.path)) {
+        return Err("The new location overlaps the library folder or another mod folder.".to_string());
+    }
+    if new_path.exists() && fs::read_dir(&new_path).map(|mut entries| entries.next().is_some()).unwrap_or(true) {
+        return Err(format!("'{}' must be an empty folder.", new_path.display()));
+    }
+
+    if let Ok(conn) = db_state.0.lock() {
+        backup_before_operation(&conn, &app_handle, "relocate_library");
+    }
+    let db = db_state.0.clone();
+    let app_handle_clone = app_handle.clone();
+    let relocate_task = async_runtime::spawn_blocking(move || -> CmdResult<String> {
+        let (total_files, total_bytes) = activation::folder_stats(&old_path)
+            .map_err(|e| format!("Failed to read '{}': {}", old_path.display(), e))?;
+        app_handle_clone.emit_all(RELOCATE_START_EVENT, total_files).ok();
+
+        if new_path.exists() {
+            fs::remove_dir(&new_path).map_err(|e| format!("Failed to prepare '{}': {}", new_path.display(), e))?;
+        }
+        if let Some(parent) = new_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create '{}': {}", parent.display(), e))?;
+        }
+
+        // Same drive: a single rename, under the DB lock so no toggle or scan sees a half-moved folder
+        {
+            let conn = db.lock().map_err(|_| "DB lock poisoned".to_string())?;
+            match fs::rename(&old_path, &new_path) {
+                Ok(()) => {
+                    if let Err(e) = save_relocated_mods_path(&conn, &old_path, &new_path) {
+                        // Keep the old location authoritative
+                        if let Err(rollback_error) = fs::rename(&new_path, &old_path) {
+                            eprintln!("[relocate_mods_library] Failed to move the folder back: {}", rollback_error);
+                        }
+                        return Err(format!("Failed to save the new Mods folder path: {}", e));
+                    }
+                    return Ok(format!("Moved {} files to '{}'.", total_files, new_path.display()));
+                }
+                Err(e) if activation::is_cross_device(&e) => {}
+                Err(e) => return Err(format!("Failed to move the Mods folder: {}. The original folder was left untouched.", e)),
+            }
+        }
+
+        // Different drive: copy and verify without blocking the app, then switch over under the lock
+        println!("[relocate_mods_library] Different drive, copying {} files ({} bytes)", total_files, total_bytes);
+        let mut processed = 0;
+        let copy_result = activation::copy_folder(&old_path, &new_path, &mut |relative| {
+            processed += 1;
+            if processed % 50 == 0 || processed == total_files {
+                app_handle_clone.emit_all(RELOCATE_PROGRESS_EVENT, ScanProgress {
+                    processed,
+                    total: total_files,
+                    current_path: Some(relative.to_string_lossy().to_string()),
+                    message: format!("Copying files ({}/{})", processed, total_files),
+                }).ok();
+            }
+        });
+        let discard_copy = |reason: String| {
+            fs::remove_dir_all(&new_path).ok();
+            Err(format!("Failed to copy the Mods folder: {}. The original folder was left untouched.", reason))
+        };
+        match copy_result.and_then(|_| activation::compare_folders(&old_path, &new_path, true)) {
+            Ok(None) => {}
+            Ok(Some(difference)) => return discard_copy(format!("verification failed, {}", difference)),
+            Err(e) => return discard_copy(e.to_string()),
+        }
+
+        {
+            let conn = db.lock().map_err(|_| "DB lock poisoned".to_string())?;
+            // Mods toggled or deleted during the copy changed the original after it was copied
+            match activation::compare_folders(&old_path, &new_path, false) {
+                Ok(None) => {}
+                Ok(Some(difference)) => return discard_copy(format!("the folder changed while it was copied ({}), please try again", difference)),
+                Err(e) => return discard_copy(e.to_string()),
+            }
+            if let Err(e) = save_relocated_mods_path(&conn, &old_path, &new_path) {
+                fs::remove_dir_all(&new_path).ok();
+                return Err(format!("Failed to save the new Mods folder path: {}", e));
+            }
+        }
+
+        let mut summary = format!("Moved {} files to '{}'.", total_files, new_path.display());
+        if let Err(e) = fs::remove_dir_all(&old_path) {
+            eprintln!("[relocate_mods_library] Failed to remove old folder: {}", e);
+            summary.push_str(&format!(" The old folder '{}' could not be removed completely: {}", old_path.display(), e));
+        }
+        Ok(summary)
+    });
+
+    let result = relocate_task.await.map_err(|e| format!("Relocation task failed: {}", e)).and_then(|r| r);
+    match &result {
+        Ok(summary) => {
+            println!("[relocate_mods_library] {}", summary);
+            app_handle.emit_all(RELOCATE_COMPLETE_EVENT, summary).ok();
+        }
+        Err(e) => {
+            eprintln!("[relocate_mods_library] {}", e);
+            app_handle.emit_all(RELOCATE_ERROR_EVENT, e).ok();
+        }
+    }
+    result
+}
+
+// --- Library Export / Import ---
+// One zip per game: every mod folder under mods/, a snapshot of the database and a manifest
+// with a checksum per file. Importing restores it into a fresh Mods folder on another PC.
+
+#[derive(Serialize, Deserialize, Debug)]
+struct LibraryManifest {
+    format_version: u32,
+    game_slug: String,
+    app_version: String,
+    created_at: i64,
+    enabled_only: bool,
+    assets: Vec<LibraryAssetEntry>,
+    files: Vec<LibraryFileEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct LibraryAssetEntry {
+    asset_id: i64,
+    folder_name: String, // Clean relative path, also the folder below mods/
+    is_enabled: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct LibraryFileEntry {
+    path: String,     // Path inside the archive
+    size: u64,
+    checksum: String, // FNV-1a 64 of the content, hex
+}
+
+// Copies everything from `reader` to `writer`. Returns the byte count and checksum of the data.
+fn copy_with_checksum(reader: &mut dyn Read, writer: &mut dyn Write) -> io::Result<(u64, String)> {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut total: u64 = 0;
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        fnv1a_update(&mut hash, &buffer[..read]);
+        writer.write_all(&buffer[..read])?;
+        total += read as u64;
+    }
+    Ok((total, format!("{:016x}", hash)))
+}
+
+// Joins a path read from an archive below `base`. Absolute paths and `..` are refused.
+fn safe_archive_join(base: &Path, relative: &str) -> Option<PathBuf> {
+    let relative = Path::new(relative);
+    let is_plain = relative.components().all(|c| matches!(c, std::path::Component::Normal(_)));
+    (is_plain && relative.components().next().is_some()).then(|| base.join(relative))
+}
+
+fn emit_library_progress(app_handle: &AppHandle, processed: usize, total: usize, current_path: &str, verb: &str) {
+    if processed % 50 == 0 || processed == total {
+        app_handle.emit_all(LIBRARY_TRANSFER_PROGRESS_EVENT, ScanProgress {
+            processed,
+            total,
+            current_path: Some(current_path.to_string()),
+            message: format!("{} files ({}/{})", verb, processed, total),
+        }).ok();
+    }
+}
+
+// What a library export writes, gathered under the DB lock so folders and snapshot match
+struct LibraryExportPlan {
+    game_slug: String,
+    assets: Vec<LibraryAssetEntry>,
+    sources: Vec<(String, PathBuf)>, // (path inside the archive, file on disk), the DB snapshot last
+    snapshot_path: PathBuf,
+}
+
+fn plan_library_export(conn: &Connection, base_mods_path: &Path, temp_dir: &Path, enabled_only: bool) -> Result<LibraryExportPlan, AppError> {
+    let game_slug = get_setting_value(conn, DB_INTERNAL_GAME_SLUG_KEY)?.unwrap_or_else(|| DEFAULT_GAME_SLUG.to_string());
+
+    // 1. Pick the mod folders and list their files
+    let rows: Vec<(i64, String, Option<i64>)> = conn.prepare("SELECT id, folder_name, root_id FROM assets WHERE missing_since IS NULL ORDER BY id")?
+        .query_map([], |row| Ok((row.get(0)?, row.get::<_, String>(1)?.replace("\\", "/"), row.get(2)?)))?
+        .collect::<SqlResult<_>>()?;
+    let mut assets = Vec::new();
+    let mut sources: Vec<(String, PathBuf)> = Vec::new(); // (path inside the archive, file on disk)
+    let mut exported_folders = HashSet::new();
+    for (asset_id, folder_name, root_id) in rows {
+        let Some((folder_path, is_enabled)) = locate_asset_folder(base_mods_path, root_id, &folder_name) else {
+            println!("[export_library] Skipping asset {}: folder '{}' not found on disk.", asset_id, folder_name);
+            continue;
+        };
+        if enabled_only && !is_enabled { continue; }
+        // Every root is extracted into one Mods folder, so a second mod with the same path can't come along
+        if !exported_folders.insert(folder_name.clone()) {
+            println!("[export_library] Skipping asset {}: '{}' is already exported from another mod folder.", asset_id, folder_name);
+            continue;
+        }
+        for entry in WalkDir::new(&folder_path).min_depth(1).into_iter().filter_map(|e| e.ok()) {
+            if !entry.file_type().is_file() { continue; }
+            let Ok(relative) = entry.path().strip_prefix(&folder_path) else { continue };
+            let archive_path = format!("{}{}/{}", LIBRARY_MODS_PREFIX, folder_name, relative.to_string_lossy().replace("\\", "/"));
+            sources.push((archive_path, entry.into_path()));
+        }
+        assets.push(LibraryAssetEntry { asset_id, folder_name, is_enabled });
+    }
+
+    // 2. Consistent copy of the database
+    let snapshot_path = backup_connection(conn, temp_dir, "library_export")?;
+    sources.push((LIBRARY_DB_ENTRY.to_string(), snapshot_path.clone()));
+    Ok(LibraryExportPlan { game_slug, assets, sources, snapshot_path })
+}
+
+// Compresses a planned export. Runs without the DB lock; removes the snapshot when done.
+fn write_library_archive(plan: LibraryExportPlan, app_handle: &AppHandle, output_path: &Path, enabled_only: bool) -> Result<String, AppError> {
+    let LibraryExportPlan { game_slug, assets, sources, snapshot_path } = plan;
+    let total = sources.len();
+    app_handle.emit_all(LIBRARY_TRANSFER_START_EVENT, total).ok();
+
+    // 3. Write the archive, manifest last since it carries the checksums
+    let write_result = (|| -> Result<Vec<LibraryFileEntry>, AppError> {
+        let mut zip = zip::ZipWriter::new(fs::File::create(output_path)?);
+        let mut files = Vec::with_capacity(total);
+        for (index, (archive_path, disk_path)) in sources.iter().enumerate() {
+            let mut source = fs::File::open(disk_path)?;
+            let large = source.metadata()?.len() >= u32::MAX as u64;
+            zip.start_file(archive_path.as_str(), zip::write::FileOptions::default().large_file(large))?;
+            let (size, checksum) = copy_with_checksum(&mut source, &mut zip)?;
+            files.push(LibraryFileEntry { path: archive_path.clone(), size, checksum });
+            emit_library_progress(app_handle, index + 1, total, archive_path, "Exporting");
+        }
+        let manifest = LibraryManifest {
+            format_version: LIBRARY_FORMAT_VERSION,
+            game_slug: game_slug.clone(),
+            app_version: get_app_version(),
+            created_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0),
+            enabled_only,
+            assets,
+            files,
+        };
+        zip.start_file(LIBRARY_MANIFEST_ENTRY, zip::write::FileOptions::default())?;
+        zip.write_all(serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+        zip.finish()?;
+        Ok(manifest.files)
+    })();
+    fs::remove_file(&snapshot_path).ok();
+    let files = write_result?;
+
+    let total_bytes: u64 = files.iter().map(|f| f.size).sum();
+    Ok(format!("Exported {} files ({} bytes) of '{}' to '{}'.", files.len(), total_bytes, game_slug, output_path.display()))
+}
+
+// Extracts and verifies every file of a library archive. Mods go into `mods_path`, the
+// database snapshot into `temp_dir`. Nothing in the app is changed yet; on error the
+// caller removes both folders.
+fn extract_library_archive(
+    app_handle: &AppHandle,
+    archive_path: &Path,
+    mods_path: &Path,
+    temp_dir: &Path,
+    expected_game_slug: &str,
+) -> Result<(LibraryManifest, PathBuf), AppError> {
+    let mut archive = ZipArchive::new(fs::File::open(archive_path)?)?;
+    let manifest: LibraryManifest = match archive.by_name(LIBRARY_MANIFEST_ENTRY) {
+        Ok(entry) => serde_json::from_reader(entry)?,
+        Err(ZipError::FileNotFound) => return Err(AppError::Config("Not a library export: the manifest is missing.".to_string())),
+        Err(e) => return Err(e.into()),
+    };
+    if manifest.format_version > LIBRARY_FORMAT_VERSION {
+        return Err(AppError::Config(format!("This export was made by a newer version ({}). Update the app first.", manifest.app_version)));
+    }
+    if manifest.game_slug != expected_game_slug {
+        return Err(AppError::Config(format!("This export belongs to '{}'. Switch to that game before importing it.", manifest.game_slug)));
+    }
+
+    fs::create_dir_all(mods_path)?;
+    fs::create_dir_all(temp_dir)?;
+    let now_millis = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+    let snapshot_path = temp_dir.join(format!("{}_library_import.sqlite", now_millis));
+    let total = manifest.files.len();
+    app_handle.emit_all(LIBRARY_TRANSFER_START_EVENT, total).ok();
+
+    for (index, file) in manifest.files.iter().enumerate() {
+        let target = if file.path == LIBRARY_DB_ENTRY {
+            snapshot_path.clone()
+        } else {
+            file.path.strip_prefix(LIBRARY_MODS_PREFIX)
+                .and_then(|relative| safe_archive_join(mods_path, relative))
+                .ok_or_else(|| AppError::ModOperation(format!("Unexpected path '{}' in the archive.", file.path)))?
+        };
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut entry = archive.by_name(&file.path)?;
+        let (size, checksum) = copy_with_checksum(&mut entry, &mut fs::File::create(&target)?)?;
+        if size != file.size || checksum != file.checksum {
+            return Err(AppError::ModOperation(format!("Checksum mismatch for '{}'. The archive is damaged.", file.path)));
+        }
+        emit_library_progress(app_handle, index + 1, total, &file.path, "Importing");
+    }
+
+    if !snapshot_path.is_file() {
+        return Err(AppError::ModOperation("The archive contains no database.".to_string()));
+    }
+    if let Some(problem) = check_database_integrity(&snapshot_path)? {
+        return Err(AppError::ModOperation(format!("The exported database is damaged: {}", problem)));
+    }
+    Ok((manifest, snapshot_path))
+}
+
+// Replaces the active database with the exported one and points it at the new Mods folder.
+// Machine-specific state (extra roots, link library, trash, undo history) is dropped and
+// activation falls back to in-place renaming.
+fn apply_library_snapshot(conn: &mut Connection, manifest: &LibraryManifest, snapshot_path: &Path, mods_path: &Path) -> Result<usize, AppError> {
+    {
+        let source = Connection::open_with_flags(snapshot_path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+        let backup = rusqlite::backup::Backup::new(&source, conn)?;
+        backup.run_to_completion(BACKUP_PAGES_PER_STEP, std::time::Duration::from_millis(5), None)?;
+    }
+    conn.execute("PRAGMA foreign_keys = ON;", [])?;
+    run_schema_migrations(conn)?;
+
+    let exported_ids: HashSet<i64> = manifest.assets.iter().map(|a| a.asset_id).collect();
+    let tx = conn.transaction()?;
+    let stored_ids: Vec<i64> = tx.prepare("SELECT id FROM assets")?.query_map([], |row| row.get(0))?.collect::<SqlResult<_>>()?;
+    for asset_id in stored_ids.into_iter().filter(|id| !exported_ids.contains(id)) {
+        tx.execute("DELETE FROM assets WHERE id = ?1", params![asset_id])?;
+    }
+    tx.execute_batch(
+        "UPDATE assets SET root_id = NULL, missing_since = NULL;
+         DELETE FROM mod_roots;
+         DELETE FROM trash;
+         DELETE FROM operation_journal;"
+    )?;
+    tx.execute("DELETE FROM settings WHERE key IN (?1, ?2)", params![SETTINGS_KEY_ACTIVATION_MODE, SETTINGS_KEY_MOD_LIBRARY])?;
+    tx.execute("INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)", params![SETTINGS_KEY_MODS_FOLDER, mods_path.to_string_lossy()])?;
+    tx.commit()?;
+    activation::set_active_settings(load_activation_settings(conn)?);
+    activation::set_mod_roots(load_mod_roots(conn)?);
+    activation::set_materialized_folders(load_materialized_folders(conn)?);
+
+    // Folders were extracted in their enabled form
+    let mut failed = 0;
+    for asset in manifest.assets.iter().filter(|a| !a.is_enabled) {
+        if let Err(e) = set_asset_enabled(conn, mods_path, asset.asset_id, false) {
+            eprintln!("[import_library] Failed to disable '{}': {}", asset.folder_name, e);
+            failed += 1;
+        }
+    }
+    Ok(failed)
+}
+
+#[command]
+async fn select_library_export_file(default_name: Option<String>) -> CmdResult<Option<PathBuf>> {
+    let file_name = format!("{}.zip", default_name.unwrap_or_else(|| "gmm-library".to_string()));
+    let result = dialog::blocking::FileDialogBuilder::new()
+        .set_title("Export Library")
+        .set_file_name(&file_name)
+        .add_filter("Zip Archive", &["zip"])
+        .save_file();
+    Ok(result)
+}
+
+#[command]
+async fn select_library_import_file() -> CmdResult<Option<PathBuf>> {
+    let result = dialog::blocking::FileDialogBuilder::new()
+        .set_title("Import Library")
+        .add_filter("Zip Archive", &["zip"])
+        .pick_file();
+    Ok(result)
+}
+
+// Exports the active game's library to a zip. With `enabled_only`, disabled mods are left out.
+#[command]
+async fn export_library(file_path: String, enabled_only: bool, db_state: State<'_, DbState>, app_handle: AppHandle) -> CmdResult<String> {
+    println!("[export_library] Exporting to '{}' (enabled only: {})", file_path, enabled_only);
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+    let output_path = PathBuf::from(file_path.trim());
+    if !output_path.is_absolute() {
+        return Err("The export file must be an absolute path.".to_string());
+    }
+    if output_path.starts_with(&base_mods_path) {
+        return Err("The export file must not be inside the Mods folder.".to_string());
+    }
+    let temp_dir = get_app_data_dir(&app_handle).map_err(|e| e.to_string())?.join(LIBRARY_TEMP_DIR_NAME);
+
+    let db = db_state.0.clone();
+    let app_handle_clone = app_handle.clone();
+    let export_task = async_runtime::spawn_blocking(move || -> CmdResult<String> {
+        // Only held while listing the folders and snapshotting the database, not while compressing
+        let plan = {
+            let conn = db.lock().map_err(|_| "DB lock poisoned".to_string())?;
+            plan_library_export(&conn, &base_mods_path, &temp_dir, enabled_only).map_err(|e| format!("Export failed: {}", e))?
+        };
+        // Written next to the target first so a failed export never leaves a truncated zip behind
+        let mut partial_path = output_path.clone().into_os_string();
+        partial_path.push(".partial");
+        let partial_path = PathBuf::from(partial_path);
+        let result = write_library_archive(plan, &app_handle_clone, &partial_path, enabled_only)
+            .and_then(|summary| fs::rename(&partial_path, &output_path).map(|_| summary).map_err(AppError::from));
+        if result.is_err() {
+            fs::remove_file(&partial_path).ok();
+        }
+        result.map_err(|e| format!("Export failed: {}", e))
+    });
+
+    let result = export_task.await.map_err(|e| format!("Export task failed: {}", e)).and_then(|r| r);
+    match &result {
+        Ok(summary) => {
+            println!("[export_library] {}", summary);
+            app_handle.emit_all(LIBRARY_TRANSFER_COMPLETE_EVENT, summary).ok();
+        }
+        Err(e) => {
+            eprintln!("[export_library] {}", e);
+            app_handle.emit_all(LIBRARY_TRANSFER_ERROR_EVENT, e).ok();
+        }
+    }
+    result
+}
+
+// Restores a library export into `mods_path`, which must be empty, replacing the active
+// game's database. The previous database is backed up first.
+#[command]
+async fn import_library(file_path: String, mods_path: String, db_state: State<'_, DbState>, app_handle: AppHandle) -> CmdResult<String> {
+    println!("[import_library] Importing '{}' into '{}'", file_path, mods_path);
+    let archive_path = PathBuf::from(file_path.trim());
+    let mods_path = PathBuf::from(mods_path.trim());
+    if !archive_path.is_file() {
+        return Err(format!("Export file not found: {}", archive_path.display()));
+    }
+    if !mods_path.is_absolute() {
+        return Err("The Mods folder must be an absolute path.".to_string());
+    }
+    if mods_path.exists() && fs::read_dir(&mods_path).map(|mut entries| entries.next().is_some()).unwrap_or(true) {
+        return Err(format!("'{}' must be an empty folder.", mods_path.display()));
+    }
+    let temp_dir = get_app_data_dir(&app_handle).map_err(|e| e.to_string())?.join(LIBRARY_TEMP_DIR_NAME);
+
+    let db = db_state.0.clone();
+    let app_handle_clone = app_handle.clone();
+    let import_task = async_runtime::spawn_blocking(move || -> CmdResult<String> {
+        let game_slug = {
+            let conn = db.lock().map_err(|_| "DB lock poisoned".to_string())?;
+            get_setting_value(&conn, DB_INTERNAL_GAME_SLUG_KEY).map_err(|e| e.to_string())?
+                .unwrap_or_else(|| DEFAULT_GAME_SLUG.to_string())
+        };
+
+        // Extraction only writes into the new Mods folder and the temp dir, so the database stays usable meanwhile
+        let (manifest, snapshot_path) = match extract_library_archive(&app_handle_clone, &archive_path, &mods_path, &temp_dir, &game_slug) {
+            Ok(extracted) => extracted,
+            Err(e) => {
+                fs::remove_dir_all(&mods_path).ok();
+                fs::remove_dir_all(&temp_dir).ok();
+                return Err(format!("Import failed: {}. Nothing was changed.", e));
+            }
+        };
+
+        let mut conn = match db.lock() {
+            Ok(conn) => conn,
+            Err(_) => {
+                fs::remove_dir_all(&mods_path).ok();
+                fs::remove_file(&snapshot_path).ok();
+                return Err("DB lock poisoned".to_string());
+            }
+        };
+        backup_before_operation(&conn, &app_handle_clone, "library_import");
+        let result = apply_library_snapshot(&mut conn, &manifest, &snapshot_path, &mods_path);
+        fs::remove_file(&snapshot_path).ok();
+        let failed = result.map_err(|e| format!("Import failed while updating the database: {}. Restore the 'library_import' backup to undo it.", e))?;
+
+        let mut summary = format!("Imported {} mods ({} files) into '{}'.", manifest.assets.len(), manifest.files.len(), mods_path.display());
+        if failed > 0 {
+            summary.push_str(&format!(" {} mods could not be disabled again.", failed));
+        }
+        Ok(summary)
+    });
+
+    let result = import_task.await.map_err(|e| format!("Import task failed: {}", e)).and_then(|r| r);
+    match &result {
+        Ok(summary) => {
+            println!("[import_library] {}", summary);
+            app_handle.emit_all(LIBRARY_TRANSFER_COMPLETE_EVENT, summary).ok();
+        }
+        Err(e) => {
+            eprintln!("[import_library] {}", e);
+            app_handle.emit_all(LIBRARY_TRANSFER_ERROR_EVENT, e).ok();
+        }
+    }
+    result
+}
+
+// --- Mod Roots ---
+
+fn validate_root_role(role: &str) -> CmdResult<()> {
+    match role {
+        activation::ROOT_ROLE_ACTIVE | activation::ROOT_ROLE_LIBRARY | activation::ROOT_ROLE_READONLY => Ok(()),
+        _ => Err(format!("Unknown folder role '{}'.", role)),
+    }
+}
+
+fn refresh_mod_roots(conn: &Connection) -> CmdResult<Vec<ModRoot>> {
+    let roots = load_mod_roots(conn).map_err(|e| format!("Failed to load mod folders: {}", e))?;
+    activation::set_mod_roots(roots.clone());
+    Ok(roots)
+}
+
+#[command]
+fn get_mod_roots(db_state: State<DbState>) -> CmdResult<Vec<ModRoot>> {
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    load_mod_roots(&conn).map_err(|e| e.to_string())
+}
+
+#[command]
+fn add_mod_root(path: String, role: String, label: Option<String>, db_state: State<DbState>) -> CmdResult<ModRoot> {
+    println!("[add_mod_root] Adding '{}' as {}", path, role);
+    validate_root_role(&role)?;
+    let root_path = PathBuf::from(path.trim());
+    if !root_path.is_absolute() {
+        return Err("The folder must be an absolute path.".to_string());
+    }
+    if !root_path.is_dir() {
+        return Err(format!("Folder not found: {}", root_path.display()));
+    }
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+    let library_path = activation::active_settings().library_path;
+    let existing = activation::mod_roots();
+    let overlaps = |other: &Path| root_path.starts_with(other) || other.starts_with(&root_path);
+    if overlaps(&base_mods_path) || library_path.as_deref().is_some_and(overlaps) {
+        return Err("The folder must not be inside the Mods or library folder (or contain them).".to_string());
+    }
+    if let Some(other) = existing.iter().find(|r| overlaps(&r.path)) {
+        return Err(format!("The folder overlaps the registered folder '{}'.", other.path.display()));
+    }
+
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let label = label.map(|l| l.trim().to_string()).filter(|l| !l.is_empty());
+    conn.execute(
+        "INSERT INTO mod_roots (path, role, label) VALUES (?1, ?2, ?3)",
+        params![root_path.to_string_lossy(), role, label],
+    ).map_err(|e| format!("Failed to add mod folder: {}", e))?;
+    let root_id = conn.last_insert_rowid();
+    refresh_mod_roots(&conn)?.into_iter().find(|r| r.id == root_id)
+        .ok_or_else(|| "Mod folder disappeared after adding it.".to_string())
+}
+
+#[command]
+fn update_mod_root(root_id: i64, role: String, label: Option<String>, db_state: State<DbState>) -> CmdResult<ModRoot> {
+    println!("[update_mod_root] Root {} -> {}", root_id, role);
+    validate_root_role(&role)?;
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let label = label.map(|l| l.trim().to_string()).filter(|l| !l.is_empty());
+    let changed = conn.execute(
+        "UPDATE mod_roots SET role = ?1, label = ?2 WHERE id = ?3",
+        params![role, label, root_id],
+    ).map_err(|e| format!("Failed to update mod folder: {}", e))?;
+    if changed == 0 {
+        return Err(format!("Mod folder with ID {} not found.", root_id));
+    }
+    refresh_mod_roots(&conn)?.into_iter().find(|r| r.id == root_id)
+        .ok_or_else(|| format!("Mod folder with ID {} not found.", root_id))
+}
+
+#[command]
+fn remove_mod_root(root_id: i64, db_state: State<DbState>) -> CmdResult<()> {
+    println!("[remove_mod_root] Removing root {}", root_id);
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let asset_count: i64 = conn.query_row("SELECT COUNT(*) FROM assets WHERE root_id = ?1 AND missing_since IS NULL", params![root_id], |row| row.get(0))
+        .map_err(|e| format!("Failed to count mods in folder: {}", e))?;
+    if asset_count > 0 {
+        return Err(format!("{} mods still live in this folder. Move or delete them first.", asset_count));
+    }
+    let changed = conn.execute("DELETE FROM mod_roots WHERE id = ?1", params![root_id])
+        .map_err(|e| format!("Failed to remove mod folder: {}", e))?;
+    if changed == 0 {
+        return Err(format!("Mod folder with ID {} not found.", root_id));
+    }
+    refresh_mod_roots(&conn)?;
+    Ok(())
+}
+
+// Moves a mod between the primary Mods folder (None) and the extra roots, keeping its ID,
+// presets and enabled state where the target allows it.
+#[command]
+fn move_asset_to_root(asset_id: i64, target_root_id: Option<i64>, db_state: State<DbState>) -> CmdResult<()> {
+    println!("[move_asset_to_root] Moving asset {} to root {:?}", asset_id, target_root_id);
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let location = get_asset_location_info(&conn, asset_id).map_err(|e| e.to_string())?;
+    let clean_relative_path = location.clean_relative_path;
+    let from_root_id = location.root_id;
+    if from_root_id == target_root_id {
+        return Ok(());
+    }
+    let from = activation::backend_for_asset(&base_mods_path, from_root_id)
+        .filter(|backend| backend.locate(&clean_relative_path).is_some())
+        .ok_or_else(|| format!("Mod folder '{}' not found on disk.", clean_relative_path))?;
+    let to = match target_root_id {
+        None => activation::backend_for(&base_mods_path),
+        Some(root_id) => {
+            let root = activation::mod_roots().into_iter().find(|r| r.id == root_id)
+                .ok_or_else(|| format!("Mod folder with ID {} not found.", root_id))?;
+            activation::backend_for_root(&root, &activation::active_settings())
+        }
+    };
+    let to_role = activation::root_role(target_root_id);
+    if activation::root_role(from_root_id) == activation::ROOT_ROLE_READONLY || to_role == activation::ROOT_ROLE_READONLY {
+        return Err("Mods can't be moved into or out of a read-only folder.".to_string());
+    }
+    ensure_folder_unclaimed(&conn, target_root_id, &clean_relative_path, asset_id)?;
+
+    let migrated = migrate_asset_activation(from.as_ref(), to.as_ref(), &to_role, &clean_relative_path);
+    persist_materialized_folders(&conn);
+    let was_enabled = migrated.map_err(|e| e.to_string())?;
+    conn.execute("UPDATE assets SET root_id = ?1 WHERE id = ?2", params![target_root_id, asset_id])
+        .map_err(|e| format!("Mod was moved but the database could not be updated: {}", e))?;
+    println!("[move_asset_to_root] Moved '{}' (was enabled: {})", clean_relative_path, was_enabled);
+    Ok(())
+}
+
+// --- Operation Journal ---
+
+fn fetch_asset_snapshot(conn: &Connection, asset_id: i64) -> Result<AssetRecordSnapshot, AppError> {
+    conn.query_row(
+        "SELECT entity_id, name, description, author, category_tag, image_filename, folder_name FROM assets WHERE id = ?1",
+        params![asset_id],
+        |row| Ok(AssetRecordSnapshot {
+            entity_id: row.get(0)?,
+            name: row.get(1)?,
+            description: row.get(2)?,
+            author: row.get(3)?,
+            category_tag: row.get(4)?,
+            image_filename: row.get(5)?,
+            folder_name: row.get::<_, String>(6)?.replace("\\", "/"),
+        }),
+    ).map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => AppError::NotFound(format!("Asset with ID {} not found.", asset_id)),
+        _ => AppError::from(e),
+    })
+}
+
+fn record_operation(conn: &Connection, summary: &str, op: &JournalOp) -> Result<(), AppError> {
+    let operation_json = serde_json::to_string(op)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    // A new operation invalidates everything that could still be redone
+    conn.execute("DELETE FROM operation_journal WHERE undone = 1", [])?;
+    conn.execute(
+        "INSERT INTO operation_journal (kind, summary, operation, created_at) VALUES (?1, ?2, ?3, ?4)",
+        params![op.kind(), summary, operation_json, now],
+    )?;
+    conn.execute(
+        "DELETE FROM operation_journal WHERE id <= (SELECT MAX(id) FROM operation_journal) - ?1",
+        params![JOURNAL_MAX_ENTRIES],
+    )?;
+    Ok(())
+}
+
+// Records an operation for undo, and writes back the link-mode trees it touched.
+// Failures are only logged, they never fail the command itself.
+fn journal_operation(conn: &Connection, summary: &str, op: &JournalOp) {
+    persist_materialized_folders(conn);
+    if let JournalOp::SetEnabled { assets, inis } = op {
+        if assets.is_empty() && inis.is_empty() {
+            return; // Nothing changed on disk
+        }
+    }
+    match record_operation(conn, summary, op) {
+        Ok(()) => println!("[journal] Recorded: {}", summary),
+        Err(e) => eprintln!("[journal] Failed to record '{}': {}", summary, e),
+    }
+}
+
+// Puts an asset's folder into the given state. Returns false if it already was (Internal error type)
+fn set_asset_enabled(conn: &Connection, base_mods_path: &Path, asset_id: i64, enable: bool) -> Result<bool, AppError> {
+    let location = get_asset_location_info(conn, asset_id)?;
+    let backend = activation::backend_for_asset(base_mods_path, location.root_id)
+        .filter(|backend| backend.locate(&location.clean_relative_path).is_some())
+        .ok_or_else(|| AppError::NotFound(format!("Mod folder '{}' not found on disk.", location.clean_relative_path)))?;
+    let is_enabled = backend.locate(&location.clean_relative_path).is_some_and(|(_, is_enabled)| is_enabled);
+    if is_enabled == enable {
+        return Ok(false);
+    }
+    backend.set_enabled(&location.clean_relative_path, enable)?;
+    persist_materialized_folders(conn);
+    Ok(true)
+}
+
+// Stamps last_enabled_at on the assets that were just enabled. Failures are only logged.
+fn record_enabled_at(conn: &Connection, changes: &[AssetStateChange]) {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    for change in changes.iter().filter(|c| c.is_enabled) {
+        if let Err(e) = conn.execute("UPDATE assets SET last_enabled_at = ?1 WHERE id = ?2", params![now, change.asset_id]) {
+            eprintln!("[record_enabled_at] Failed to update asset {}: {}", change.asset_id, e);
+        }
+    }
+}
+
+// Redoes (forward) or undoes an operation. Returns the operation to store afterwards,
+// since a redone delete ends up in a new trash entry.
+fn replay_operation(conn: &Connection, app_handle: &AppHandle, base_mods_path: &Path, op: JournalOp, forward: bool) -> CmdResult<JournalOp> {
+    match op {
+        JournalOp::SetEnabled { assets, inis } => {
+            let mut errors = Vec::new();
+            for change in &inis {
+                let target = change.is_enabled == forward;
+                let result = locate_asset_mod_folder(conn, change.asset_id, base_mods_path)
+                    .and_then(|folder| set_ini_file_enabled(&folder, &change.ini_file, target));
+                if let Err(e) = result {
+                    errors.push(format!("INI '{}' of asset {}: {}", change.ini_file, change.asset_id, e));
+                }
+            }
+            refresh_ini_changed_assets(conn, base_mods_path, &inis, &mut errors);
+            let mut applied = Vec::new();
+            for change in &assets {
+                let target = change.is_enabled == forward;
+                match set_asset_enabled(conn, base_mods_path, change.asset_id, target) {
+                    Ok(_) => applied.push(AssetStateChange { asset_id: change.asset_id, is_enabled: target }),
+                    Err(e) => errors.push(format!("Asset {}: {}", change.asset_id, e)),
+                }
+            }
+            record_enabled_at(conn, &applied);
+            // Every step sets an explicit state, so retrying after a partial failure is safe
+            if !errors.is_empty() {
+                return Err(errors.join("\n"));
+            }
+            Ok(JournalOp::SetEnabled { assets, inis })
+        }
+        JournalOp::UpdateAsset { asset_id, before, after } => {
+            let (from, to) = if forward { (&before, &after) } else { (&after, &before) };
+            if from.folder_name != to.folder_name {
+                let root_id = get_asset_location_info(conn, asset_id).map_err(|e| e.to_string())?.root_id;
+                ensure_folder_unclaimed(conn, root_id, &to.folder_name, asset_id)?;
+                let backend = activation::backend_for_asset(base_mods_path, root_id)
+                    .ok_or_else(|| format!("Mod folder '{}' not found on disk.", from.folder_name))?;
+                activation::relocate_asset(backend.as_ref(), &from.folder_name, &to.folder_name).map_err(|e| e.to_string())?;
+            }
+            conn.execute(
+                "UPDATE assets SET entity_id = ?1, name = ?2, description = ?3, author = ?4, category_tag = ?5, image_filename = ?6, folder_name = ?7, updated_at = ?9 WHERE id = ?8",
+                params![to.entity_id, to.name, to.description, to.author, to.category_tag, to.image_filename, to.folder_name, asset_id,
+                    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)],
+            ).map_err(|e| format!("Failed to update asset {}: {}", asset_id, e))?;
+            sync_asset_sidecars(conn, &[asset_id]);
+            Ok(JournalOp::UpdateAsset { asset_id, before, after })
+        }
+        JournalOp::Trash { asset_id, trash_id } => {
+            if forward {
+                let trash_dir = get_trash_dir(app_handle, conn).map_err(|e| e.to_string())?;
+                let trash_id = trash_asset(conn, base_mods_path, &trash_dir, asset_id)?;
+                return Ok(JournalOp::Trash { asset_id, trash_id });
+            }
+            match trash_id {
+                Some(trash_id) => {
+                    restore_trashed_asset(conn, base_mods_path, trash_id)?;
+                }
+                None => {
+                    // The folder was already gone; only the deletion mark is reverted
+                    conn.execute("UPDATE assets SET missing_since = NULL WHERE id = ?1", params![asset_id])
+                        .map_err(|e| format!("Failed to update asset {}: {}", asset_id, e))?;
+                }
+            }
+            Ok(JournalOp::Trash { asset_id, trash_id })
+        }
+    }
+}
+
+fn fetch_journal_entry(conn: &Connection, entry_id: i64) -> SqlResult<JournalEntry> {
+    conn.query_row(
+        "SELECT id, kind, summary, created_at, undone, stale FROM operation_journal WHERE id = ?1",
+        params![entry_id],
+        journal_entry_from_row,
+    )
+}
+
+fn journal_entry_from_row(row: &rusqlite::Row) -> SqlResult<JournalEntry> {
+    Ok(JournalEntry {
+        id: row.get(0)?,
+        kind: row.get(1)?,
+        summary: row.get(2)?,
+        created_at: row.get(3)?,
+        undone: row.get::<_, i64>(4)? == 1,
+        stale: row.get::<_, i64>(5)? == 1,
+    })
+}
+
+// Undoes (forward = false) the newest active entry or redoes the oldest undone one.
+// An entry that fails to replay is marked stale, so the next step moves on to the entries behind it.
+fn step_journal(db_state: &DbState, app_handle: &AppHandle, forward: bool) -> CmdResult<Option<JournalEntry>> {
+    let base_mods_path = get_mods_base_path_from_settings(db_state).map_err(|e| e.to_string())?;
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let sql = if forward {
+        "SELECT id, summary, operation FROM operation_journal WHERE undone = 1 AND stale = 0 ORDER BY id ASC LIMIT 1"
+    } else {
+        "SELECT id, summary, operation FROM operation_journal WHERE undone = 0 AND stale = 0 ORDER BY id DESC LIMIT 1"
+    };
+    let Some((entry_id, summary, operation_json)) = conn.query_row(sql, [], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))
+        .optional().map_err(|e| format!("Failed to read operation history: {}", e))? else {
+        return Ok(None);
+    };
+    let action = if forward { "redo" } else { "undo" };
+    let mark_stale = |error: String| -> String {
+        if let Err(e) = conn.execute("UPDATE operation_journal SET stale = 1 WHERE id = ?1", params![entry_id]) {
+            eprintln!("[step_journal] Failed to mark entry {} as stale: {}", entry_id, e);
+        }
+        format!("Failed to {} '{}': {}. It was skipped; the next {} continues with the operation before it.", action, summary, error, action)
+    };
+    let op: JournalOp = serde_json::from_str(&operation_json).map_err(|e| mark_stale(format!("corrupt journal entry ({})", e)))?;
+    println!("[step_journal] {} '{}'", if forward { "Redoing" } else { "Undoing" }, summary);
+
+    let replayed = replay_operation(&conn, app_handle, &base_mods_path, op, forward);
+    persist_materialized_folders(&conn);
+    let op = replayed.map_err(mark_stale)?;
+    let operation_json = serde_json::to_string(&op).map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE operation_journal SET undone = ?1, operation = ?2 WHERE id = ?3",
+        params![!forward, operation_json, entry_id],
+    ).map_err(|e| format!("Failed to update operation history: {}", e))?;
+    fetch_journal_entry(&conn, entry_id).map(Some).map_err(|e| e.to_string())
+}
+
+// Newest first; includes undone entries that can still be redone
+#[command]
+fn get_operation_history(limit: Option<i64>, db_state: State<DbState>) -> CmdResult<Vec<JournalEntry>> {
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let mut stmt = conn.prepare("SELECT id, kind, summary, created_at, undone, stale FROM operation_journal ORDER BY id DESC LIMIT ?1")
+        .map_err(|e| format!("Failed to prepare history query: {}", e))?;
+    let entries = stmt.query_map(params![limit.unwrap_or(JOURNAL_MAX_ENTRIES)], journal_entry_from_row)
+        .and_then(|rows| rows.collect::<SqlResult<Vec<JournalEntry>>>())
+        .map_err(|e| format!("Failed to read operation history: {}", e))?;
+    Ok(entries)
+}
+
+// Returns the undone entry, or None if there is nothing to undo
+#[command]
+fn undo_last_operation(db_state: State<DbState>, app_handle: AppHandle) -> CmdResult<Option<JournalEntry>> {
+    step_journal(&db_state, &app_handle, false)
+}
+
+// Returns the redone entry, or None if there is nothing to redo
+#[command]
+fn redo(db_state: State<DbState>, app_handle: AppHandle) -> CmdResult<Option<JournalEntry>> {
+    step_journal(&db_state, &app_handle, true)
+}
+
+// Enables or disables several assets as one undoable operation. Returns the number of changed assets.
+#[command]
+fn set_assets_enabled(asset_ids: Vec<i64>, enable: bool, db_state: State<DbState>) -> CmdResult<usize> {
+    println!("[set_assets_enabled] Setting {} assets enabled = {}", asset_ids.len(), enable);
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let mut changes = Vec::new();
+    let mut errors = Vec::new();
+    for asset_id in &asset_ids {
+        match set_asset_enabled(&conn, &base_mods_path, *asset_id, enable) {
+            Ok(true) => changes.push(AssetStateChange { asset_id: *asset_id, is_enabled: enable }),
+            Ok(false) => {}
+            Err(e) => errors.push(format!("Asset {}: {}", asset_id, e)),
+        }
+    }
+    let changed = changes.len();
+    record_enabled_at(&conn, &changes);
+    journal_operation(&conn, &format!("{} {} mods", if enable { "Enabled" } else { "Disabled" }, changed),
+        &JournalOp::SetEnabled { assets: changes, inis: Vec::new() });
+    if !errors.is_empty() {
+        return Err(format!("{} of {} mods could not be changed:\n{}", errors.len(), asset_ids.len(), errors.join("\n")));
+    }
+    Ok(changed)
+}
+
+// --- Tags ---
+
+// Trims and collapses whitespace. None for blank names.
+fn normalize_tag_name(name: &str) -> Option<String> {
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!name.is_empty()).then_some(name)
+}
+
+// Tag lists in INI metadata are written as "a, b; c | d"
+fn split_tag_list(value: &str) -> Vec<String> {
+    value.split([',', ';', '|']).filter_map(normalize_tag_name).collect()
+}
+
+// Tag names of an asset as GROUP_CONCAT'ed by ASSET_COLUMNS
+fn split_tag_concat(value: Option<String>) -> Vec<String> {
+    let mut tags: Vec<String> = value.map(|v| v.split('\u{1f}').map(String::from).collect()).unwrap_or_default();
+    tags.sort_by_key(|t| t.to_lowercase());
+    tags
+}
+
+// Id of the tag with this name (case-insensitive), created when missing
+fn ensure_tag(conn: &Connection, name: &str) -> Result<i64, AppError> {
+    let name = normalize_tag_name(name).ok_or_else(|| AppError::Config("Tag name cannot be empty.".to_string()))?;
+    conn.execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", params![name])?;
+    Ok(conn.query_row("SELECT id FROM tags WHERE name = ?1", params![name], |row| row.get(0))?)
+}
+
+// Links the named tags to an asset. Returns how many links are new.
+fn assign_tags(conn: &Connection, asset_id: i64, names: &[String]) -> Result<usize, AppError> {
+    let mut added = 0;
+    for name in names {
+        let tag_id = ensure_tag(conn, name)?;
+        added += conn.execute("INSERT OR IGNORE INTO asset_tags (asset_id, tag_id) VALUES (?1, ?2)", params![asset_id, tag_id])?;
+    }
+    Ok(added)
+}
+
+fn fetch_asset_tag_names(conn: &Connection, asset_id: i64) -> Result<Vec<String>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT t.name FROM asset_tags ast JOIN tags t ON t.id = ast.tag_id WHERE ast.asset_id = ?1 ORDER BY t.name COLLATE NOCASE"
+    )?;
+    let names = stmt.query_map(params![asset_id], |row| row.get(0))?.collect::<SqlResult<Vec<String>>>()?;
+    Ok(names)
+}
+
+fn tagged_asset_ids(conn: &Connection, tag_ids: &[i64]) -> Vec<i64> {
+    let mut asset_ids: Vec<i64> = tag_ids.iter().flat_map(|tag_id| {
+        conn.prepare("SELECT asset_id FROM asset_tags WHERE tag_id = ?1")
+            .and_then(|mut stmt| stmt.query_map(params![tag_id], |row| row.get(0))?.collect::<SqlResult<Vec<i64>>>())
+            .unwrap_or_default()
+    }).collect();
+    asset_ids.sort_unstable();
+    asset_ids.dedup();
+    asset_ids
+}
+
+const TAG_QUERY: &str = "SELECT t.id, t.name, t.color, COUNT(a.id) FROM tags t
+     LEFT JOIN asset_tags ast ON ast.tag_id = t.id
+     LEFT JOIN assets a ON a.id = ast.asset_id AND a.missing_since IS NULL";
+
+fn tag_from_row(row: &rusqlite::Row) -> SqlResult<Tag> {
+    Ok(Tag { id: row.get(0)?, name: row.get(1)?, color: row.get(2)?, asset_count: row.get(3)? })
+}
+
+fn fetch_tag(conn: &Connection, tag_id: i64) -> Result<Tag, AppError> {
+    conn.query_row(&format!("{} WHERE t.id = ?1 GROUP BY t.id", TAG_QUERY), params![tag_id], tag_from_row)
+        .optional()?
+        .ok_or_else(|| AppError::NotFound(format!("Tag with ID {} not found.", tag_id)))
+}
+
+// Tag candidates from the metadata sections of a mod's INI files: the Tags list and the Type/Category value
+fn read_ini_tag_hints(mod_folder_path: &Path) -> Vec<String> {
+    let mut tags = Vec::new();
+    for entry in WalkDir::new(mod_folder_path).min_depth(1).max_depth(1).into_iter().filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() || !entry.path().extension().is_some_and(|ext| ext.eq_ignore_ascii_case("ini")) { continue; }
+        let Ok(ini) = Ini::load_from_file(entry.path()) else { continue };
+        let meta = read_ini_mod_metadata(&ini);
+        tags.extend(meta.tags);
+        tags.extend(meta.mod_type.as_deref().and_then(normalize_tag_name));
+    }
+    tags
+}
+
+#[command]
+fn get_tags(db_state: State<DbState>) -> CmdResult<Vec<Tag>> {
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let mut stmt = conn.prepare(&format!("{} GROUP BY t.id ORDER BY t.name COLLATE NOCASE", TAG_QUERY)).map_err(|e| e.to_string())?;
+    let tags = stmt.query_map([], tag_from_row).and_then(|rows| rows.collect::<SqlResult<Vec<Tag>>>())
+        .map_err(|e| format!("Failed to fetch tags: {}", e))?;
+    Ok(tags)
+}
+
+#[command]
+fn create_tag(name: String, color: Option<String>, db_state: State<DbState>) -> CmdResult<Tag> {
+    let name = normalize_tag_name(&name).ok_or_else(|| "Tag name cannot be empty.".to_string())?;
+    println!("[create_tag] Creating tag '{}'", name);
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let inserted = conn.execute("INSERT OR IGNORE INTO tags (name, color) VALUES (?1, ?2)", params![name, color])
+        .map_err(|e| format!("Failed to create tag: {}", e))?;
+    if inserted == 0 {
+        return Err(format!("Tag '{}' already exists.", name));
+    }
+    fetch_tag(&conn, conn.last_insert_rowid()).map_err(|e| e.to_string())
+}
+
+// Renames and/or recolors a tag. Renaming onto another existing tag is refused; merge them instead.
+#[command]
+fn update_tag(tag_id: i64, name: String, color: Option<String>, db_state: State<DbState>) -> CmdResult<Tag> {
+    let name = normalize_tag_name(&name).ok_or_else(|| "Tag name cannot be empty.".to_string())?;
+    println!("[update_tag] Tag ID {}: name '{}', color {:?}", tag_id, name, color);
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let existing: Option<i64> = conn.query_row("SELECT id FROM tags WHERE name = ?1 AND id != ?2", params![name, tag_id], |row| row.get(0))
+        .optional().map_err(|e| e.to_string())?;
+    if existing.is_some() {
+        return Err(format!("A tag named '{}' already exists. Merge the two tags instead.", name));
+    }
+    let changes = conn.execute("UPDATE tags SET name = ?1, color = ?2 WHERE id = ?3", params![name, color, tag_id])
+        .map_err(|e| format!("Failed to update tag: {}", e))?;
+    if changes == 0 {
+        return Err(format!("Tag with ID {} not found.", tag_id));
+    }
+    sync_asset_sidecars(&conn, &tagged_asset_ids(&conn, &[tag_id]));
+    fetch_tag(&conn, tag_id).map_err(|e| e.to_string())
+}
+
+#[command]
+fn delete_tag(tag_id: i64, db_state: State<DbState>) -> CmdResult<()> {
+    println!("[delete_tag] Deleting tag ID {}", tag_id);
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let affected = tagged_asset_ids(&conn, &[tag_id]); // Gone after the cascade
+    let changes = conn.execute("DELETE FROM tags WHERE id = ?1", params![tag_id])
+        .map_err(|e| format!("Failed to delete tag: {}", e))?;
+    if changes == 0 {
+        return Err(format!("Tag with ID {} not found.", tag_id));
+    }
+    sync_asset_sidecars(&conn, &affected);
+    Ok(())
+}
+
+// Moves every assignment and preset selector of the source tags onto the target, then deletes the sources
+#[command]
+fn merge_tags(source_tag_ids: Vec<i64>, target_tag_id: i64, db_state: State<DbState>) -> CmdResult<Tag> {
+    println!("[merge_tags] Merging {:?} into tag ID {}", source_tag_ids, target_tag_id);
+    let mut conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    fetch_tag(&conn, target_tag_id).map_err(|e| e.to_string())?;
+    let source_tag_ids: Vec<i64> = source_tag_ids.into_iter().filter(|id| *id != target_tag_id).collect();
+    let affected = tagged_asset_ids(&conn, &source_tag_ids);
+
+    let tx = conn.transaction().map_err(|e| format!("Failed to start transaction: {}", e))?;
+    for source_id in &source_tag_ids {
+        tx.execute("INSERT OR IGNORE INTO asset_tags (asset_id, tag_id) SELECT asset_id, ?2 FROM asset_tags WHERE tag_id = ?1", params![source_id, target_tag_id])
+            .map_err(|e| format!("Failed to move assignments of tag {}: {}", source_id, e))?;
+        // Presets selecting both keep the target's selector
+        tx.execute("UPDATE OR IGNORE preset_tag_selectors SET tag_id = ?2 WHERE tag_id = ?1", params![source_id, target_tag_id])
+            .map_err(|e| format!("Failed to move preset selectors of tag {}: {}", source_id, e))?;
+        tx.execute("DELETE FROM tags WHERE id = ?1", params![source_id])
+            .map_err(|e| format!("Failed to delete tag {}: {}", source_id, e))?;
+    }
+    tx.commit().map_err(|e| format!("Failed to commit transaction: {}", e))?;
+
+    sync_asset_sidecars(&conn, &affected);
+    fetch_tag(&conn, target_tag_id).map_err(|e| e.to_string())
+}
+
+// Bulk assignment by name; missing tags are created. Returns the number of new assignments.
+#[command]
+fn add_tags_to_assets(asset_ids: Vec<i64>, tag_names: Vec<String>, db_state: State<DbState>) -> CmdResult<usize> {
+    println!("[add_tags_to_assets] Tagging {} assets with {:?}", asset_ids.len(), tag_names);
+    let mut conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let tag_names: Vec<String> = tag_names.iter().filter_map(|n| normalize_tag_name(n)).collect();
+    let tx = conn.transaction().map_err(|e| format!("Failed to start transaction: {}", e))?;
+    let mut added = 0;
+    for asset_id in &asset_ids {
+        added += assign_tags(&tx, *asset_id, &tag_names).map_err(|e| format!("Failed to tag asset {}: {}", asset_id, e))?;
+    }
+    tx.commit().map_err(|e| format!("Failed to commit transaction: {}", e))?;
+    sync_asset_sidecars(&conn, &asset_ids);
+    Ok(added)
+}
+
+#[command]
+fn remove_tags_from_assets(asset_ids: Vec<i64>, tag_ids: Vec<i64>, db_state: State<DbState>) -> CmdResult<usize> {
+    println!("[remove_tags_from_assets] Removing tags {:?} from {} assets", tag_ids, asset_ids.len());
+    let mut conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let tx = conn.transaction().map_err(|e| format!("Failed to start transaction: {}", e))?;
+    let mut removed = 0;
+    for asset_id in &asset_ids {
+        for tag_id in &tag_ids {
+            removed += tx.execute("DELETE FROM asset_tags WHERE asset_id = ?1 AND tag_id = ?2", params![asset_id, tag_id])
+                .map_err(|e| format!("Failed to untag asset {}: {}", asset_id, e))?;
+        }
+    }
+    tx.commit().map_err(|e| format!("Failed to commit transaction: {}", e))?;
+    sync_asset_sidecars(&conn, &asset_ids);
+    Ok(removed)
+}
+
+// Present assets of the whole library carrying any (or, with match_all, every) of the tags
+#[command]
+fn get_assets_by_tags(tag_ids: Vec<i64>, match_all: bool, db_state: State<DbState>) -> CmdResult<Vec<Asset>> {
+    if tag_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let placeholders = tag_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+    let required = if match_all { tag_ids.iter().collect::<HashSet<_>>().len() } else { 1 };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM assets WHERE missing_since IS NULL
+         AND (SELECT COUNT(*) FROM asset_tags WHERE asset_id = assets.id AND tag_id IN ({})) >= {}
+         ORDER BY name", ASSET_COLUMNS, placeholders, required
+    )).map_err(|e| format!("Failed to prepare tag query: {}", e))?;
+    let assets = stmt.query_map(rusqlite::params_from_iter(&tag_ids), asset_from_row)
+        .and_then(|rows| rows.collect::<SqlResult<Vec<Asset>>>())
+        .map_err(|e| format!("Failed to fetch tagged assets: {}", e))?;
+    Ok(assets.into_iter().filter_map(|asset| resolve_asset_on_disk(&base_mods_path, asset)).collect())
+}
+
+// Tags assets from their INI metadata (Tags, Type/Category) and stored category tag.
+// Covers every present asset when asset_ids is None. Returns the number of new assignments.
+#[command]
+fn import_tags_from_ini(asset_ids: Option<Vec<i64>>, db_state: State<DbState>) -> CmdResult<usize> {
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+    let mut conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let asset_ids: Vec<i64> = match asset_ids {
+        Some(ids) => ids,
+        None => conn.prepare("SELECT id FROM assets WHERE missing_since IS NULL")
+            .and_then(|mut stmt| stmt.query_map([], |row| row.get(0))?.collect())
+            .map_err(|e| format!("Failed to list assets: {}", e))?,
+    };
+    println!("[import_tags_from_ini] Reading tags of {} assets", asset_ids.len());
+
+    let tx = conn.transaction().map_err(|e| format!("Failed to start transaction: {}", e))?;
+    let mut added = 0;
+    let mut tagged = Vec::new();
+    for asset_id in &asset_ids {
+        let Ok(location) = get_asset_location_info(&tx, *asset_id) else { continue };
+        let mut names = locate_asset_folder(&base_mods_path, location.root_id, &location.clean_relative_path)
+            .map(|(folder, _)| read_ini_tag_hints(&folder))
+            .unwrap_or_default();
+        let category_tag: Option<String> = tx.query_row("SELECT category_tag FROM assets WHERE id = ?1", params![asset_id], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        names.extend(category_tag.as_deref().and_then(normalize_tag_name));
+        let new_links = assign_tags(&tx, *asset_id, &names).map_err(|e| format!("Failed to tag asset {}: {}", asset_id, e))?;
+        if new_links > 0 {
+            added += new_links;
+            tagged.push(*asset_id);
+        }
+    }
+    tx.commit().map_err(|e| format!("Failed to commit transaction: {}", e))?;
+    sync_asset_sidecars(&conn, &tagged);
+    println!("[import_tags_from_ini] Added {} tag assignments to {} assets.", added, tagged.len());
+    Ok(added)
+}
+
+#[command]
+fn set_preset_tag_selectors(preset_id: i64, selectors: Vec<PresetTagSelector>, db_state: State<DbState>) -> CmdResult<Preset> {
+    println!("[set_preset_tag_selectors] Setting {} tag selectors on preset ID {}", selectors.len(), preset_id);
+    let mut conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    fetch_preset(&conn, preset_id).map_err(|e| e.to_string())?;
+    let mut seen = HashSet::new();
+    for selector in &selectors {
+        if !seen.insert(selector.tag_id) {
+            return Err(format!("Tag ID {} is selected more than once.", selector.tag_id));
+        }
+        fetch_tag(&conn, selector.tag_id).map_err(|e| e.to_string())?;
+    }
+
+    let tx = conn.transaction().map_err(|e| format!("Failed to start transaction: {}", e))?;
+    tx.execute("DELETE FROM preset_tag_selectors WHERE preset_id = ?1", params![preset_id])
+        .map_err(|e| format!("Failed to clear tag selectors: {}", e))?;
+    for (position, selector) in selectors.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO preset_tag_selectors (preset_id, tag_id, is_enabled, position) VALUES (?1, ?2, ?3, ?4)",
+            params![preset_id, selector.tag_id, selector.is_enabled as i64, position as i64],
+        ).map_err(|e| format!("Failed to save selector for tag {}: {}", selector.tag_id, e))?;
+    }
+    tx.commit().map_err(|e| format!("Failed to commit transaction: {}", e))?;
+
+    fetch_preset(&conn, preset_id).map_err(|e| e.to_string())
+}
+
+// --- Search ---
+
+const SEARCH_INDEX_INSERT_SQL: &str = "INSERT INTO assets_fts (rowid, name, author, description, tags, folder_path, ini_sections)
+     SELECT a.id, a.name, COALESCE(a.author, ''), COALESCE(a.description, ''),
+            COALESCE((SELECT GROUP_CONCAT(t.name, ' ') FROM asset_tags ast JOIN tags t ON t.id = ast.tag_id WHERE ast.asset_id = a.id), ''),
+            REPLACE(a.folder_name, '\\', '/'), COALESCE(a.ini_sections, '')
+     FROM assets a";
+
+// Statements rewriting the search index rows of the assets matching `condition` (on alias `a`)
+fn search_index_refresh_sql(condition: &str) -> String {
+    format!(
+        "DELETE FROM assets_fts WHERE rowid IN (SELECT a.id FROM assets a WHERE {condition}); {} WHERE {condition};",
+        SEARCH_INDEX_INSERT_SQL
+    )
+}
+
+fn rebuild_search_index_rows(conn: &Connection) -> Result<(), AppError> {
+    conn.execute_batch(&format!("DELETE FROM assets_fts; {};", SEARCH_INDEX_INSERT_SQL))?;
+    Ok(())
+}
+
+fn search_ini_sections_enabled(conn: &Connection) -> bool {
+    matches!(get_setting_value(conn, SETTINGS_KEY_SEARCH_INI_SECTIONS), Ok(Some(v)) if v == "true")
+}
+
+// Re-reads the INI section names of a mod after its files changed, when they are indexed
+fn refresh_asset_ini_sections(conn: &Connection, mod_folder_path: &Path, asset_id: i64) -> Result<(), AppError> {
+    if search_ini_sections_enabled(conn) {
+        conn.execute(
+            "UPDATE assets SET ini_sections = ?1 WHERE id = ?2 AND ini_sections IS NOT ?1",
+            params![collect_ini_section_names(mod_folder_path), asset_id],
+        )?;
+    }
+    Ok(())
+}
+
+// Distinct section names of a mod's loaded INI files, plus their CamelCase words
+// so "TextureOverrideKeqingBody" is found by "keqing"
+fn collect_ini_section_names(mod_folder_path: &Path) -> String {
+    let mut terms: Vec<String> = Vec::new();
+    let mut seen = HashSet::new();
+    for (ini_file, ini_path) in list_ini_files_recursive(mod_folder_path) {
+        if is_disabled_ini(&ini_file) { continue; }
+        let Ok(doc) = IniDocument::load(&ini_path) else { continue };
+        for section in &doc.sections {
+            if !seen.insert(section.name.to_lowercase()) { continue; }
+            terms.push(section.name.clone());
+            let mut word = String::new();
+            for c in section.name.chars() {
+                if c.is_uppercase() && !word.is_empty() && !word.chars().last().is_some_and(char::is_uppercase) {
+                    terms.push(std::mem::take(&mut word));
+                }
+                word.push(c);
+            }
+            if word.len() < section.name.len() { terms.push(word); }
+        }
+    }
+    terms.join(" ")
+}
 
-            // Insert the current state into the preset
-            insert_stmt.execute(params![preset_id, asset_id, is_currently_enabled_on_disk])
-                .map_err(|e| format!("Failed to save state for asset {}: {}", asset_id, e))?;
-            saved_count += 1;
+// Levenshtein distance, only used to match query typos against index terms
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            current[j + 1] = (previous[j] + (ca != *cb) as usize).min(previous[j + 1] + 1).min(current[j] + 1);
         }
-    } // --- End scope for insert_stmt --- `insert_stmt` is dropped here, releasing the borrow on `tx`
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+// Turns user input into an FTS5 expression: every word must match as a prefix.
+// With `fuzzy`, a word also matches indexed terms within a small edit distance.
+fn build_search_match_expression(conn: &Connection, query: &str, fuzzy: bool) -> Result<Option<String>, AppError> {
+    let words: Vec<String> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect();
+    if words.is_empty() {
+        return Ok(None);
+    }
+    // Typo candidates share the word's first letter (a range fts5vocab can seek to)
+    // and differ in length by at most the allowed distance
+    let mut candidate_stmt = if fuzzy {
+        Some(conn.prepare_cached(
+            "SELECT term FROM assets_fts_vocab WHERE term >= ?1 AND term < ?2 AND length(term) BETWEEN ?3 AND ?4"
+        )?)
+    } else {
+        None
+    };
+    let mut clauses = Vec::new();
+    for word in &words {
+        let length = word.chars().count();
+        let max_distance = match length { 0..=3 => 0, 4..=7 => 1, _ => 2 };
+        let mut alternatives = vec![format!("\"{}\"*", word)];
+        if let (Some(stmt), true) = (candidate_stmt.as_mut(), max_distance > 0) {
+            let first = word.chars().next().unwrap_or_default();
+            let after_first = char::from_u32(first as u32 + 1).unwrap_or(char::MAX);
+            let candidates = stmt.query_map(
+                params![first.to_string(), after_first.to_string(), (length - max_distance) as i64, (length + max_distance) as i64],
+                |row| row.get::<_, String>(0),
+            )?.collect::<SqlResult<Vec<String>>>()?;
+            alternatives.extend(candidates.into_iter()
+                .filter(|term| !term.starts_with(word.as_str()) && edit_distance(word, term) <= max_distance)
+                .map(|term| format!("\"{}\"", term)));
+        }
+        clauses.push(format!("({})", alternatives.join(" OR ")));
+    }
+    Ok(Some(clauses.join(" AND ")))
+}
 
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+struct AssetSearchFilters {
+    enabled: Option<bool>,
+    entity_slug: Option<String>,
+    category_slug: Option<String>,
+    tag_ids: Vec<i64>, // Any of these tags
+}
 
-    // 4. Commit the transaction (Now safe as insert_stmt is out of scope)
+#[derive(Serialize, Debug, Clone)]
+struct AssetSearchResults {
+    total: usize, // Matches after filtering, before pagination
+    assets: Vec<Asset>,
+}
+
+// Library-wide search over name, author, description, tags, folder path and (if indexed) INI section names.
+// Results are ranked by relevance; an empty query lists every asset passing the filters by name.
+#[command]
+fn search_assets(
+    query: String,
+    filters: Option<AssetSearchFilters>,
+    fuzzy: Option<bool>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    db_state: State<DbState>,
+) -> CmdResult<AssetSearchResults> {
+    let filters = filters.unwrap_or_default();
+    println!("[search_assets] Query '{}', filters {:?}", query, filters);
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+
+    let match_expression = build_search_match_expression(&conn, &query, fuzzy.unwrap_or(false)).map_err(|e| e.to_string())?;
+    let mut conditions = vec!["assets.missing_since IS NULL".to_string()];
+    let mut values: Vec<rusqlite::types::Value> = Vec::new();
+    if let Some(expression) = &match_expression {
+        values.push(expression.clone().into()); // Bound in the join below, ahead of the filters
+    }
+    if let Some(slug) = &filters.entity_slug {
+        conditions.push("assets.entity_id = (SELECT id FROM entities WHERE slug = ?)".to_string());
+        values.push(slug.clone().into());
+    }
+    if let Some(slug) = &filters.category_slug {
+        conditions.push("assets.entity_id IN (SELECT e.id FROM entities e JOIN categories c ON e.category_id = c.id WHERE c.slug = ?)".to_string());
+        values.push(slug.clone().into());
+    }
+    if !filters.tag_ids.is_empty() {
+        conditions.push(format!(
+            "EXISTS (SELECT 1 FROM asset_tags WHERE asset_id = assets.id AND tag_id IN ({}))",
+            filters.tag_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",")
+        ));
+        values.extend(filters.tag_ids.iter().map(|id| rusqlite::types::Value::from(*id)));
+    }
+    let (join, order) = match match_expression {
+        // Column weights follow the index order: name, author, description, tags, folder_path, ini_sections
+        Some(_) => (
+            "JOIN (SELECT rowid AS hit_id, bm25(assets_fts, 10.0, 4.0, 1.0, 5.0, 2.0, 0.5) AS score FROM assets_fts WHERE assets_fts MATCH ?) hits ON hits.hit_id = assets.id",
+            "hits.score, name COLLATE NOCASE",
+        ),
+        None => ("", "name COLLATE NOCASE"),
+    };
+    let from = format!("FROM assets {} WHERE {}", join, conditions.join(" AND "));
+    let (offset, limit) = (offset.unwrap_or(0), limit.unwrap_or(DEFAULT_SEARCH_PAGE_SIZE));
+
+    // Enabled state lives on disk, so that filter (and pagination after it) runs on resolved assets.
+    // Without it the database pages the results and only the page is looked up on disk.
+    let Some(enabled) = filters.enabled else {
+        let total: i64 = conn.query_row(&format!("SELECT COUNT(*) {}", from), rusqlite::params_from_iter(values.iter()), |row| row.get(0))
+            .map_err(|e| format!("Search failed: {}", e))?;
+        values.push((limit as i64).into());
+        values.push((offset as i64).into());
+        let sql = format!("SELECT {} {} ORDER BY {} LIMIT ? OFFSET ?", ASSET_COLUMNS, from, order);
+        let mut stmt = conn.prepare(&sql).map_err(|e| format!("Failed to prepare search query: {}", e))?;
+        let assets = stmt.query_map(rusqlite::params_from_iter(values), asset_from_row)
+            .and_then(|rows| rows.collect::<SqlResult<Vec<Asset>>>())
+            .map_err(|e| format!("Search failed: {}", e))?;
+        let assets = assets.into_iter().filter_map(|asset| resolve_asset_on_disk(&base_mods_path, asset)).collect();
+        return Ok(AssetSearchResults { total: total as usize, assets });
+    };
+
+    let sql = format!("SELECT {} {} ORDER BY {}", ASSET_COLUMNS, from, order);
+    let mut stmt = conn.prepare(&sql).map_err(|e| format!("Failed to prepare search query: {}", e))?;
+    let assets = stmt.query_map(rusqlite::params_from_iter(values), asset_from_row)
+        .and_then(|rows| rows.collect::<SqlResult<Vec<Asset>>>())
+        .map_err(|e| format!("Search failed: {}", e))?;
+    let matches: Vec<Asset> = assets.into_iter()
+        .filter_map(|asset| resolve_asset_on_disk(&base_mods_path, asset))
+        .filter(|asset| asset.is_enabled == enabled)
+        .collect();
+    let total = matches.len();
+    let assets = matches.into_iter().skip(offset).take(limit).collect();
+    Ok(AssetSearchResults { total, assets })
+}
+
+// Rebuilds the search index. `include_ini_sections` (saved as a setting when given) re-reads
+// every mod's INI section names into the index, or drops them when false.
+#[command]
+fn rebuild_search_index(include_ini_sections: Option<bool>, db_state: State<DbState>) -> CmdResult<()> {
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+    let mut conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    if let Some(include) = include_ini_sections {
+        conn.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+            params![SETTINGS_KEY_SEARCH_INI_SECTIONS, include.to_string()],
+        ).map_err(|e| format!("Failed to save search setting: {}", e))?;
+    }
+    let include = search_ini_sections_enabled(&conn);
+    println!("[rebuild_search_index] Rebuilding (INI sections: {})", include);
+
+    let tx = conn.transaction().map_err(|e| format!("Failed to start transaction: {}", e))?;
+    if include {
+        let assets: Vec<(i64, String, Option<i64>)> = tx.prepare("SELECT id, folder_name, root_id FROM assets WHERE missing_since IS NULL")
+            .and_then(|mut stmt| stmt.query_map([], |row| Ok((row.get(0)?, row.get::<_, String>(1)?.replace("\\", "/"), row.get(2)?)))?.collect())
+            .map_err(|e| format!("Failed to list assets: {}", e))?;
+        for (asset_id, folder_name, root_id) in assets {
+            let Some((folder, _)) = locate_asset_folder(&base_mods_path, root_id, &folder_name) else { continue };
+            tx.execute("UPDATE assets SET ini_sections = ?1 WHERE id = ?2", params![collect_ini_section_names(&folder), asset_id])
+                .map_err(|e| format!("Failed to index INI sections of asset {}: {}", asset_id, e))?;
+        }
+    } else {
+        tx.execute("UPDATE assets SET ini_sections = NULL WHERE ini_sections IS NOT NULL", [])
+            .map_err(|e| format!("Failed to clear INI sections: {}", e))?;
+    }
+    rebuild_search_index_rows(&tx).map_err(|e| format!("Failed to rebuild search index: {}", e))?;
     tx.commit().map_err(|e| format!("Failed to commit transaction: {}", e))?;
+    Ok(())
+}
 
-    println!("[overwrite_preset] Preset ID {} overwritten successfully. Saved state for {} assets (skipped {} not found).", preset_id, saved_count, not_found_count);
+// --- Trash & Missing Mods ---
+
+// Deleted mod folders of the active game (Internal error type)
+fn get_trash_dir(app_handle: &AppHandle, conn: &Connection) -> Result<PathBuf, AppError> {
+    let game_slug = get_setting_value(conn, DB_INTERNAL_GAME_SLUG_KEY)?.unwrap_or_else(|| DEFAULT_GAME_SLUG.to_string());
+    Ok(get_app_data_dir(app_handle)?.join(TRASH_DIR_NAME).join(game_slug))
+}
+
+// Deletes trashed folders for good, together with the asset rows they belonged to.
+// Entries whose folder can't be removed are kept. Returns the number of purged entries.
+fn purge_trash_entries(conn: &Connection, trash_ids: &[i64]) -> Result<usize, AppError> {
+    let mut purged = 0;
+    for trash_id in trash_ids {
+        let Some((asset_id, trash_path)) = conn.query_row(
+            "SELECT asset_id, trash_path FROM trash WHERE id = ?1",
+            params![trash_id],
+            |row| Ok((row.get::<_, Option<i64>>(0)?, PathBuf::from(row.get::<_, String>(1)?))),
+        ).optional()? else { continue };
+        if trash_path.exists() {
+            if let Err(e) = fs::remove_dir_all(&trash_path) {
+                eprintln!("[purge_trash] Failed to delete '{}': {}", trash_path.display(), e);
+                continue;
+            }
+        }
+        if let Some(asset_id) = asset_id {
+            conn.execute("DELETE FROM assets WHERE id = ?1 AND missing_since IS NOT NULL", params![asset_id])?;
+        }
+        conn.execute("DELETE FROM trash WHERE id = ?1", params![trash_id])?;
+        mark_trash_journal_stale(conn, *trash_id)?;
+        purged += 1;
+    }
+    Ok(purged)
+}
+
+// Deletions whose trash entry was restored or purged outside undo can't be replayed anymore
+fn mark_trash_journal_stale(conn: &Connection, trash_id: i64) -> SqlResult<usize> {
+    conn.execute(
+        "UPDATE operation_journal SET stale = 1 WHERE kind = 'trash' AND json_extract(operation, '$.trash_id') = ?1",
+        params![trash_id],
+    )
+}
+
+fn purge_expired_trash(conn: &Connection) -> Result<usize, AppError> {
+    let retention_days = get_setting_value(conn, SETTINGS_KEY_TRASH_RETENTION_DAYS)?
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .unwrap_or(DEFAULT_TRASH_RETENTION_DAYS);
+    if retention_days <= 0 {
+        return Ok(0); // Keep forever
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    let expired_ids = conn.prepare("SELECT id FROM trash WHERE deleted_at < ?1")?
+        .query_map(params![now - retention_days * 86_400], |row| row.get::<_, i64>(0))?
+        .collect::<SqlResult<Vec<i64>>>()?;
+    purge_trash_entries(conn, &expired_ids)
+}
+
+#[command]
+fn get_trash(db_state: State<DbState>) -> CmdResult<Vec<TrashEntry>> {
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let mut stmt = conn.prepare(
+        "SELECT id, asset_id, name, folder_name, trash_path, was_enabled, deleted_at FROM trash ORDER BY deleted_at DESC"
+    ).map_err(|e| format!("Failed to prepare trash query: {}", e))?;
+    let entries = stmt.query_map([], |row| Ok(TrashEntry {
+        id: row.get(0)?,
+        asset_id: row.get(1)?,
+        name: row.get(2)?,
+        folder_name: row.get(3)?,
+        trash_path: row.get(4)?,
+        was_enabled: row.get::<_, i64>(5)? == 1,
+        deleted_at: row.get(6)?,
+    })).and_then(|rows| rows.collect::<SqlResult<Vec<TrashEntry>>>())
+        .map_err(|e| format!("Failed to read trash: {}", e))?;
+    Ok(entries)
+}
+
+// Moves a trashed folder back to where it was and revives its asset
+#[command]
+fn restore_trash_entry(trash_id: i64, db_state: State<DbState>) -> CmdResult<()> {
+    println!("[restore_trash_entry] Restoring trash entry {}", trash_id);
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    restore_trashed_asset(&conn, &base_mods_path, trash_id)?;
+    mark_trash_journal_stale(&conn, trash_id).map_err(|e| format!("Failed to update operation history: {}", e))?;
+    Ok(())
+}
+
+// Shared by restore_trash_entry and undo. Returns the revived asset, if it still exists.
+fn restore_trashed_asset(conn: &Connection, base_mods_path: &Path, trash_id: i64) -> CmdResult<Option<i64>> {
+    let (asset_id, folder_name, root_id, trash_path, was_enabled) = conn.query_row(
+        "SELECT asset_id, folder_name, root_id, trash_path, was_enabled FROM trash WHERE id = ?1",
+        params![trash_id],
+        |row| Ok((
+            row.get::<_, Option<i64>>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<i64>>(2)?,
+            PathBuf::from(row.get::<_, String>(3)?),
+            row.get::<_, i64>(4)? == 1,
+        )),
+    ).map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => format!("Trash entry {} not found.", trash_id),
+        _ => format!("Failed to fetch trash entry: {}", e),
+    })?;
+    if !trash_path.is_dir() {
+        return Err(format!("The trashed folder '{}' no longer exists.", trash_path.display()));
+    }
+
+    // Back into its old root if that is still registered, the primary Mods folder otherwise
+    let root = root_id.and_then(|id| activation::mod_roots().into_iter().find(|r| r.id == id));
+    let backend = match &root {
+        Some(root) => activation::backend_for_root(root, &activation::active_settings()),
+        None => activation::backend_for(base_mods_path),
+    };
+    if backend.locate(&folder_name).is_some() {
+        return Err(format!("A mod folder already exists at '{}'.", folder_name));
+    }
+    let target_path = backend.inactive_path(&folder_name)
+        .ok_or_else(|| format!("'{}' can't be restored into a read-only folder.", folder_name))?;
+    activation::move_folder(&trash_path, &target_path).map_err(|e| format!("Failed to restore '{}': {}", folder_name, e))?;
+    if was_enabled && activation::root_role(root.as_ref().map(|r| r.id)) != activation::ROOT_ROLE_LIBRARY {
+        if let Err(e) = backend.set_enabled(&folder_name, true) {
+            eprintln!("[restore_trashed_asset] Warning: Restored mod could not be re-enabled: {}", e);
+        }
+        persist_materialized_folders(conn);
+    }
+
+    match asset_id {
+        Some(asset_id) => {
+            conn.execute(
+                "UPDATE assets SET missing_since = NULL, root_id = ?1 WHERE id = ?2",
+                params![root.as_ref().map(|r| r.id), asset_id],
+            ).map_err(|e| format!("Folder was restored but the asset could not be updated: {}", e))?;
+        }
+        None => println!("[restore_trashed_asset] No asset left for '{}'; the next scan will add it.", folder_name),
+    }
+    conn.execute("DELETE FROM trash WHERE id = ?1", params![trash_id])
+        .map_err(|e| format!("Failed to remove trash entry: {}", e))?;
+    Ok(asset_id)
+}
+
+// Permanently deletes the given trash entries (all of them if None). Returns the number purged.
+#[command]
+fn purge_trash(trash_ids: Option<Vec<i64>>, db_state: State<DbState>, app_handle: AppHandle) -> CmdResult<usize> {
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    backup_before_operation(&conn, &app_handle, "purge_trash");
+    let trash_ids = match trash_ids {
+        Some(ids) => ids,
+        None => conn.prepare("SELECT id FROM trash")
+            .and_then(|mut stmt| stmt.query_map([], |row| row.get::<_, i64>(0))?.collect::<SqlResult<Vec<i64>>>())
+            .map_err(|e| format!("Failed to read trash: {}", e))?,
+    };
+    let purged = purge_trash_entries(&conn, &trash_ids).map_err(|e| e.to_string())?;
+    println!("[purge_trash] Purged {} of {} entries.", purged, trash_ids.len());
+    Ok(purged)
+}
+
+// Assets marked missing by a scan (trashed ones are listed by get_trash)
+#[command]
+fn get_missing_assets(db_state: State<DbState>) -> CmdResult<Vec<MissingAsset>> {
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let mut stmt = conn.prepare(
+        "SELECT a.id, a.name, a.folder_name, e.slug, a.missing_since
+         FROM assets a JOIN entities e ON a.entity_id = e.id
+         WHERE a.missing_since IS NOT NULL AND NOT EXISTS (SELECT 1 FROM trash t WHERE t.asset_id = a.id)
+         ORDER BY a.missing_since DESC, a.name"
+    ).map_err(|e| format!("Failed to prepare missing asset query: {}", e))?;
+    let assets = stmt.query_map([], |row| Ok(MissingAsset {
+        asset_id: row.get(0)?,
+        name: row.get(1)?,
+        folder_name: row.get(2)?,
+        entity_slug: row.get(3)?,
+        missing_since: row.get(4)?,
+    })).and_then(|rows| rows.collect::<SqlResult<Vec<MissingAsset>>>())
+        .map_err(|e| format!("Failed to read missing assets: {}", e))?;
+    Ok(assets)
+}
+
+// Brings back a missing asset, either where it was (e.g. the drive is plugged in again)
+// or at `folder_path`, an absolute folder inside the Mods folder or one of the extra roots.
+#[command]
+fn restore_missing_asset(asset_id: i64, folder_path: Option<String>, db_state: State<DbState>) -> CmdResult<()> {
+    println!("[restore_missing_asset] Restoring asset {} (new location: {:?})", asset_id, folder_path);
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let current_folder_name = conn.query_row("SELECT folder_name FROM assets WHERE id = ?1", params![asset_id], |row| row.get::<_, String>(0))
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => format!("Asset with ID {} not found.", asset_id),
+            _ => format!("Failed to fetch asset: {}", e),
+        })?
+        .replace("\\", "/");
+
+    let (folder_name, root_id) = match folder_path.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+        None => {
+            // Where it was, in whichever root it shows up again
+            let (_, root_id) = activation::find_asset_root(&base_mods_path, &current_folder_name)
+                .filter(|_| !current_folder_name.is_empty())
+                .ok_or_else(|| format!("Mod folder '{}' is still missing. Pick its new location.", current_folder_name))?;
+            (current_folder_name, root_id)
+        }
+        Some(folder_path) => {
+            let folder = PathBuf::from(folder_path);
+            if !folder.is_dir() {
+                return Err(format!("Folder not found: {}", folder.display()));
+            }
+            // Candidate roots with the disabled marker used inside them
+            let settings = activation::active_settings();
+            let primary_marker = (settings.mode == activation::ACTIVATION_MODE_RENAME).then(|| settings.disabled_marker.clone());
+            let mut candidates = vec![(None, activation::backend_with(&base_mods_path, &settings).scan_root().to_path_buf(), primary_marker)];
+            for root in activation::mod_roots() {
+                let marker = (root.role == activation::ROOT_ROLE_ACTIVE).then(|| settings.disabled_marker.clone());
+                candidates.push((Some(root.id), root.path, marker));
+            }
+            let (root_id, relative_path, marker) = candidates.into_iter()
+                .find_map(|(root_id, root_path, marker)| folder.strip_prefix(&root_path).ok().map(|rel| (root_id, rel.to_path_buf(), marker)))
+                .ok_or_else(|| "The folder must be inside the Mods folder or a registered mod folder.".to_string())?;
+            let clean_relative_path = match marker {
+                Some(marker) => marker.strip(&relative_path).0,
+                None => relative_path,
+            };
+            let folder_name = clean_relative_path.to_string_lossy().replace("\\", "/");
+            locate_asset_folder(&base_mods_path, root_id, &folder_name)
+                .filter(|_| !folder_name.is_empty())
+                .ok_or_else(|| format!("Mod folder '{}' is still missing. Pick its new location.", folder_name))?;
+            (folder_name, root_id)
+        }
+    };
+
+    ensure_folder_unclaimed(&conn, root_id, &folder_name, asset_id)?;
+    conn.execute(
+        "UPDATE assets SET folder_name = ?1, root_id = ?2, missing_since = NULL WHERE id = ?3",
+        params![folder_name, root_id, asset_id],
+    ).map_err(|e| format!("Failed to update asset: {}", e))?;
+    // A trashed copy no longer belongs to this asset
+    conn.execute("UPDATE trash SET asset_id = NULL WHERE asset_id = ?1", params![asset_id])
+        .map_err(|e| format!("Failed to update trash: {}", e))?;
+    println!("[restore_missing_asset] Asset {} restored at '{}'", asset_id, folder_name);
+    Ok(())
+}
+
+// Drops missing assets (and their preset links) for good. Returns the number removed.
+#[command]
+fn purge_missing_assets(asset_ids: Vec<i64>, db_state: State<DbState>, app_handle: AppHandle) -> CmdResult<usize> {
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    backup_before_operation(&conn, &app_handle, "purge_missing");
+    let mut removed = 0;
+    for asset_id in &asset_ids {
+        removed += conn.execute(
+            "DELETE FROM assets WHERE id = ?1 AND missing_since IS NOT NULL AND NOT EXISTS (SELECT 1 FROM trash WHERE asset_id = ?1)",
+            params![asset_id],
+        ).map_err(|e| format!("Failed to remove asset {}: {}", asset_id, e))?;
+    }
+    println!("[purge_missing_assets] Removed {} of {} assets.", removed, asset_ids.len());
+    Ok(removed)
+}
+
+// --- Per-INI Toggling ---
+
+// Strips the DISABLED_ prefix (any case) from the file name of a relative INI path
+fn clean_ini_file_name(disk_file_name: &str) -> String {
+    let (folder, name) = disk_file_name.rsplit_once('/').map_or(("", disk_file_name), |(folder, name)| (folder, name));
+    let name = if is_disabled_ini(name) { &name[DISABLED_PREFIX.len()..] } else { name };
+    if folder.is_empty() { name.to_string() } else { format!("{}/{}", folder, name) }
+}
+
+fn list_asset_ini_states(mod_folder: &Path) -> Vec<AssetIniFile> {
+    let mut files: Vec<AssetIniFile> = list_ini_files_recursive(mod_folder).into_iter()
+        .map(|(disk_file_name, _)| {
+            let file_name = clean_ini_file_name(&disk_file_name);
+            let name = file_name.rsplit('/').next().unwrap_or(&file_name);
+            AssetIniFile {
+                is_enabled: file_name == disk_file_name,
+                is_helper: EXCLUDED_INI_FILENAMES.contains(&name.to_lowercase()),
+                file_name,
+                disk_file_name,
+            }
+        })
+        .collect();
+    files.sort_by_key(|f| f.file_name.to_lowercase());
+    files
+}
+
+// Renames one INI to or from its DISABLED_ form. Returns the new enabled state (Internal error type)
+fn set_ini_file_enabled(mod_folder: &Path, ini_file: &str, enable: bool) -> Result<bool, AppError> {
+    let clean_name = clean_ini_file_name(ini_file);
+    let current = list_asset_ini_states(mod_folder).into_iter()
+        .find(|f| f.file_name.eq_ignore_ascii_case(&clean_name))
+        .ok_or_else(|| AppError::NotFound(format!("INI file '{}' not found in {}", clean_name, mod_folder.display())))?;
+    if current.is_enabled == enable {
+        return Ok(enable);
+    }
+
+    let target_name = match (enable, current.file_name.rsplit_once('/')) {
+        (true, _) => current.file_name.clone(),
+        (false, Some((folder, name))) => format!("{}/{}{}", folder, DISABLED_PREFIX, name),
+        (false, None) => format!("{}{}", DISABLED_PREFIX, current.file_name),
+    };
+    let target_path = mod_folder.join(&target_name);
+    if target_path.exists() {
+        return Err(AppError::ModOperation(format!("Cannot rename INI: '{}' already exists.", target_path.display())));
+    }
+    fs::rename(mod_folder.join(&current.disk_file_name), &target_path)?;
+    println!("[set_ini_file_enabled] Renamed '{}' -> '{}'", current.disk_file_name, target_name);
+    Ok(enable)
+}
+
+// Refreshes the link-mode copies of every asset with a toggled INI, pushing failures to `errors`
+fn refresh_ini_changed_assets(conn: &Connection, base_mods_path: &Path, changes: &[IniStateChange], errors: &mut Vec<String>) {
+    let asset_ids: HashSet<i64> = changes.iter().map(|change| change.asset_id).collect();
+    for asset_id in asset_ids {
+        if let Err(e) = refresh_edited_asset(conn, base_mods_path, asset_id) {
+            errors.push(format!("Failed to update the active copy of asset {}: {}", asset_id, e));
+        }
+    }
+}
+
+fn locate_asset_mod_folder(conn: &Connection, asset_id: i64, base_mods_path: &Path) -> Result<PathBuf, AppError> {
+    let location = get_asset_location_info(conn, asset_id)?;
+    locate_asset_folder(base_mods_path, location.root_id, &location.clean_relative_path)
+        .map(|(folder, _)| folder)
+        .ok_or_else(|| AppError::ModOperation(format!("Mod folder for asset ID {} not found on disk.", asset_id)))
+}
+
+// Re-materializes a mod whose files were just edited in its source folder, so a hardlinked or
+// copied link-mode tree in the Mods folder picks up the edit, and re-indexes its INI sections (Internal error type)
+fn refresh_edited_asset(conn: &Connection, base_mods_path: &Path, asset_id: i64) -> Result<(), AppError> {
+    let location = get_asset_location_info(conn, asset_id)?;
+    if let Some((folder, _)) = locate_asset_folder(base_mods_path, location.root_id, &location.clean_relative_path) {
+        refresh_asset_ini_sections(conn, &folder, asset_id)?;
+    }
+    if let Some(backend) = activation::backend_for_asset(base_mods_path, location.root_id) {
+        let result = backend.refresh(&location.clean_relative_path);
+        persist_materialized_folders(conn);
+        result?;
+    }
     Ok(())
 }
 
+// Stores the INI states of every INI of the preset's enabled assets, so restoring also re-enables INIs toggled off later (Internal error type)
+fn capture_preset_ini_states(conn: &Connection, preset_id: i64, base_mods_path: &Path) -> Result<usize, AppError> {
+    conn.execute("DELETE FROM preset_asset_inis WHERE preset_id = ?1", params![preset_id])?;
+    let mut stmt = conn.prepare(
+        "SELECT pa.asset_id, a.folder_name, a.root_id FROM preset_assets pa JOIN assets a ON pa.asset_id = a.id
+         WHERE pa.preset_id = ?1 AND pa.is_enabled = 1"
+    )?;
+    let assets = stmt.query_map(params![preset_id], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?.replace("\\", "/"), row.get::<_, Option<i64>>(2)?)))?
+        .collect::<SqlResult<Vec<(i64, String, Option<i64>)>>>()?;
+
+    let mut saved = 0;
+    for (asset_id, clean_relative_path, root_id) in assets {
+        let mod_folder = match locate_asset_folder(base_mods_path, root_id, &clean_relative_path) {
+            Some((folder, _)) => folder,
+            None => continue,
+        };
+        for ini in list_asset_ini_states(&mod_folder) {
+            conn.execute(
+                "INSERT OR REPLACE INTO preset_asset_inis (preset_id, asset_id, ini_file, is_enabled) VALUES (?1, ?2, ?3, ?4)",
+                params![preset_id, asset_id, ini.file_name, ini.is_enabled],
+            )?;
+            saved += 1;
+        }
+    }
+    println!("[capture_preset_ini_states] Saved {} INI states for preset {}", saved, preset_id);
+    Ok(saved)
+}
+
+// Applies the stored INI states of a preset (and its layers, in order). Returns the number of renamed INIs.
+// A failed rename is pushed to `errors` and the remaining INIs are still restored
+fn restore_preset_ini_states(conn: &Connection, preset: &Preset, base_mods_path: &Path, changes: &mut Vec<IniStateChange>, errors: &mut Vec<String>) -> Result<usize, AppError> {
+    let mut preset_order = Vec::new();
+    collect_preset_layer_order(conn, preset, &mut preset_order)?;
+
+    // (asset_id, lowercase ini_file) -> (ini_file, is_enabled), later presets override earlier ones
+    let mut stored: HashMap<(i64, String), (String, bool)> = HashMap::new();
+    for preset_id in preset_order {
+        let mut stmt = conn.prepare("SELECT asset_id, ini_file, is_enabled FROM preset_asset_inis WHERE preset_id = ?1")?;
+        let rows = stmt.query_map(params![preset_id], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, bool>(2)?)))?
+            .collect::<SqlResult<Vec<_>>>()?;
+        for (asset_id, ini_file, is_enabled) in rows {
+            stored.insert((asset_id, ini_file.to_lowercase()), (ini_file, is_enabled));
+        }
+    }
+
+    let mut folder_cache: HashMap<i64, Option<PathBuf>> = HashMap::new();
+    let mut renamed = 0;
+    for ((asset_id, _), (ini_file, is_enabled)) in stored {
+        let folder = folder_cache.entry(asset_id)
+            .or_insert_with(|| locate_asset_mod_folder(conn, asset_id, base_mods_path).ok());
+        let folder = match folder {
+            Some(f) => f,
+            None => continue,
+        };
+        let was_enabled = list_asset_ini_states(folder).iter()
+            .find(|f| f.file_name.eq_ignore_ascii_case(&ini_file))
+            .map(|f| f.is_enabled);
+        match was_enabled {
+            Some(current) if current != is_enabled => match set_ini_file_enabled(folder, &ini_file, is_enabled) {
+                Ok(_) => {
+                    changes.push(IniStateChange { asset_id, ini_file: ini_file.clone(), is_enabled });
+                    renamed += 1;
+                }
+                Err(e) => errors.push(format!("Failed to {} INI '{}' of asset {}: {}", if is_enabled { "enable" } else { "disable" }, ini_file, asset_id, e)),
+            },
+            Some(_) => {}
+            None => println!("[restore_preset_ini_states] INI '{}' of asset {} no longer exists, skipping.", ini_file, asset_id),
+        }
+    }
+    refresh_ini_changed_assets(conn, base_mods_path, changes, errors);
+    Ok(renamed)
+}
+
+#[command]
+fn get_asset_ini_files(asset_id: i64, db_state: State<DbState>) -> CmdResult<Vec<AssetIniFile>> {
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let mod_folder = locate_asset_mod_folder(&conn, asset_id, &base_mods_path).map_err(|e| e.to_string())?;
+    Ok(list_asset_ini_states(&mod_folder))
+}
+
 #[command]
-fn get_ini_keybinds(asset_id: i64, db_state: State<DbState>) -> Result<Vec<KeybindInfo>, String> { // CmdResult is Result<T, String>
-    println!("[get_ini_keybinds] COMMAND START for asset ID: {}", asset_id);
+fn toggle_asset_ini(asset_id: i64, ini_file: String, enable: bool, db_state: State<DbState>) -> CmdResult<bool> {
+    println!("[toggle_asset_ini] Asset {}: {} '{}'", asset_id, if enable { "enabling" } else { "disabling" }, ini_file);
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let mod_folder = locate_asset_mod_folder(&conn, asset_id, &base_mods_path).map_err(|e| e.to_string())?;
+
+    let ini_states = list_asset_ini_states(&mod_folder);
+    let remaining_enabled = ini_states.iter()
+        .filter(|f| f.is_enabled && !f.is_helper && !f.file_name.eq_ignore_ascii_case(&clean_ini_file_name(&ini_file)))
+        .count();
+    if !enable && remaining_enabled == 0 {
+        // A mod without any active INI loads nothing while still showing as enabled
+        return Err("Cannot disable the last active INI of a mod. Disable the mod itself instead.".to_string());
+    }
+    let was_enabled = ini_states.iter()
+        .find(|f| f.file_name.eq_ignore_ascii_case(&clean_ini_file_name(&ini_file)))
+        .map(|f| f.is_enabled);
+    let result = set_ini_file_enabled(&mod_folder, &ini_file, enable).map_err(|e| e.to_string())?;
+    if was_enabled.is_some_and(|current| current != enable) {
+        let clean_name = clean_ini_file_name(&ini_file);
+        journal_operation(&conn, &format!("{} INI '{}'", if enable { "Enabled" } else { "Disabled" }, clean_name),
+            &JournalOp::SetEnabled { assets: Vec::new(), inis: vec![IniStateChange { asset_id, ini_file: clean_name, is_enabled: enable }] });
+        refresh_edited_asset(&conn, &base_mods_path, asset_id)
+            .map_err(|e| format!("INI renamed, but the active copy of the mod could not be updated: {}", e))?;
+    }
+    Ok(result)
+}
 
-    let result: Result<Vec<KeybindInfo>, String> = (|| {
-        println!("[get_ini_keybinds] Attempting to acquire DB lock...");
-        let conn_guard = db_state.0.lock().map_err(|_| {
-            eprintln!("[get_ini_keybinds] ERROR: DB lock poisoned!");
-            "DB lock poisoned".to_string()
-        })?;
-        println!("[get_ini_keybinds] DB lock acquired.");
-        let conn = &*conn_guard; // Dereference the guard to get the connection
+// --- INI Linting ---
 
-        println!("[get_ini_keybinds] Attempting to get base mods path setting directly...");
-        // --- FIX: Provide arguments to query_row ---
-        let mods_folder_path_str_opt: Option<String> = conn.query_row(
-            "SELECT value FROM settings WHERE key = ?1", // SQL query
-            params![SETTINGS_KEY_MODS_FOLDER],          // Parameters
-            |row| row.get(0),                           // Mapping closure
-        ).optional().map_err(|e| format!("DB error fetching mods folder setting: {}", e))?;
-        // --- End Fix ---
+fn ini_diagnostic(severity: &str, code: &str, message: String, ini_file: &str, line: Option<usize>, section: Option<&str>) -> IniDiagnostic {
+    IniDiagnostic {
+        severity: severity.to_string(),
+        code: code.to_string(),
+        message,
+        ini_file: Some(ini_file.to_string()),
+        line: line.map(|l| l + 1),
+        section: section.map(str::to_string),
+    }
+}
 
-        let base_mods_path = mods_folder_path_str_opt
-            .map(PathBuf::from)
-            .ok_or_else(|| {
-                eprintln!("[get_ini_keybinds] ERROR: Mods folder path not set in settings.");
-                "Mods folder path not set".to_string()
-            })?;
-        println!("[get_ini_keybinds] Base mods path obtained directly: {}", base_mods_path.display());
+// Checks every active INI of a mod folder (Internal, never fails; problems become diagnostics)
+fn lint_mod_folder(mod_folder: &Path) -> Vec<IniDiagnostic> {
+    let mut diagnostics = Vec::new();
+    // Lowercase section name -> first INI file that defined it
+    let mut seen_sections: HashMap<String, String> = HashMap::new();
 
+    for (ini_file, ini_path) in list_ini_files_recursive(mod_folder) {
+        if is_disabled_ini(&ini_file) { continue; }
 
-        println!("[get_ini_keybinds] Calling find_asset_ini_paths...");
-        let ini_paths = find_asset_ini_paths(conn, asset_id, &base_mods_path)
-            .map_err(|e| {
-                eprintln!("[get_ini_keybinds] ERROR from find_asset_ini_paths: {}", e);
-                format!("Error finding INI paths: {}", e)
-            })?;
-        println!("[get_ini_keybinds] find_asset_ini_paths returned {} paths.", ini_paths.len());
+        let file_name = ini_file.rsplit('/').next().unwrap_or(&ini_file);
+        if EXCLUDED_INI_FILENAMES.contains(&file_name.to_lowercase()) {
+            diagnostics.push(ini_diagnostic("warning", "helper_ini_in_mod",
+                format!("'{}' is a shared helper INI. It belongs in the Mods root once; a copy inside a mod runs the fix twice.", ini_file),
+                &ini_file, None, None));
+            continue;
+        }
 
-        // --- Release the lock explicitly before file I/O ---
-        drop(conn_guard);
-        println!("[get_ini_keybinds] DB lock released before file parsing.");
+        let doc = match IniDocument::load(&ini_path) {
+            Ok(d) => d,
+            Err(e) => {
+                diagnostics.push(ini_diagnostic("error", "unreadable", format!("Failed to read INI: {}", e), &ini_file, None, None));
+                continue;
+            }
+        };
+        if doc.lossy {
+            diagnostics.push(ini_diagnostic("info", "encoding", "INI is not valid UTF-8. It can be read but not edited from GMM.".to_string(), &ini_file, None, None));
+        }
 
-        if ini_paths.is_empty() {
-             println!("[get_ini_keybinds] No INI files found for asset ID {}", asset_id);
-             return Ok(Vec::new()); // Return empty early if no INIs exist
+        // --- Syntax: unrecognised lines and unbalanced if/endif per section ---
+        let mut depth: usize = 0;
+        let mut current_section: Option<String> = None;
+        for (line_index, line) in doc.lines.iter().enumerate() {
+            match &line.kind {
+                migoto_ini::IniLineKind::Section { name } => {
+                    if depth > 0 {
+                        diagnostics.push(ini_diagnostic("error", "unclosed_if",
+                            format!("{} 'if' block(s) not closed with 'endif'.", depth), &ini_file, Some(line_index), current_section.as_deref()));
+                    }
+                    depth = 0;
+                    current_section = Some(name.clone());
+                }
+                migoto_ini::IniLineKind::Condition { keyword, .. } => match keyword {
+                    migoto_ini::ConditionKeyword::If => depth += 1,
+                    migoto_ini::ConditionKeyword::EndIf if depth == 0 => diagnostics.push(ini_diagnostic("error", "unmatched_endif",
+                        "'endif' without a matching 'if'.".to_string(), &ini_file, Some(line_index), current_section.as_deref())),
+                    migoto_ini::ConditionKeyword::EndIf => depth -= 1,
+                    _ if depth == 0 => diagnostics.push(ini_diagnostic("error", "unmatched_else",
+                        "'else' without a matching 'if'.".to_string(), &ini_file, Some(line_index), current_section.as_deref())),
+                    _ => {}
+                },
+                migoto_ini::IniLineKind::Other => diagnostics.push(ini_diagnostic("warning", "syntax",
+                    format!("Unrecognised line: '{}'", line.raw.trim()), &ini_file, Some(line_index), current_section.as_deref())),
+                _ => {}
+            }
+        }
+        if depth > 0 {
+            diagnostics.push(ini_diagnostic("error", "unclosed_if",
+                format!("{} 'if' block(s) not closed with 'endif'.", depth), &ini_file, None, current_section.as_deref()));
         }
 
-        let mut found_keybinds: Vec<KeybindInfo> = Vec::new();
+        // --- Duplicate sections (within this INI and across the mod's INIs) ---
+        for section in &doc.sections {
+            let lower = section.name.to_lowercase();
+            match seen_sections.get(&lower) {
+                Some(first_file) if first_file == &ini_file => diagnostics.push(ini_diagnostic("error", "duplicate_section",
+                    format!("Section [{}] is defined more than once in this INI.", section.name), &ini_file, Some(section.header_line), Some(&section.name))),
+                Some(first_file) if doc.namespace().is_none() => diagnostics.push(ini_diagnostic("warning", "duplicate_section",
+                    format!("Section [{}] is also defined in '{}'.", section.name, first_file), &ini_file, Some(section.header_line), Some(&section.name))),
+                Some(_) => {}
+                None => { seen_sections.insert(lower, ini_file.clone()); }
+            }
+        }
 
-        for ini_path in ini_paths {
-            println!("[get_ini_keybinds] Parsing INI at: {}", ini_path.display());
+        // --- Keybinds without [Constants] ---
+        let key_sections = doc.key_sections();
+        let has_constants = doc.sections.iter().any(|s| s.kind == migoto_ini::SectionKind::Constants);
+        if !key_sections.is_empty() && !has_constants {
+            diagnostics.push(ini_diagnostic("warning", "missing_constants",
+                format!("{} keybind section(s) but no [Constants] section declaring their variables.", key_sections.len()), &ini_file, None, None));
+        }
 
-            let file = match File::open(&ini_path) {
-                 Ok(f) => f,
-                 Err(e) => {
-                     eprintln!("[get_ini_keybinds] ERROR: Failed to open INI file {}: {}. Skipping.", ini_path.display(), e);
-                     continue; // Skip to the next file if this one can't be opened
-                 }
-            };
-            let reader = BufReader::new(file);
+        // --- Referenced resource files ---
+        let ini_dir = ini_path.parent().unwrap_or(mod_folder);
+        for (section_index, section) in doc.sections.iter().enumerate() {
+            if section.kind != migoto_ini::SectionKind::Resource { continue; }
+            for (line_index, _, key, value) in doc.assignments(section_index) {
+                if !key.eq_ignore_ascii_case("filename") { continue; }
+                let reference = match value {
+                    Some(v) if !v.is_empty() => v.trim_matches('"').replace('\\', "/"),
+                    _ => continue,
+                };
+                let resource_path = ini_dir.join(&reference);
+                if resource_path.is_file() { continue; }
+                match find_case_insensitive_path(ini_dir, &reference) {
+                    Some(actual) => diagnostics.push(ini_diagnostic("warning", "case_mismatch",
+                        format!("'{}' only matches '{}' when ignoring case.", reference, actual), &ini_file, Some(line_index), Some(&section.name))),
+                    None => diagnostics.push(ini_diagnostic("error", "missing_resource",
+                        format!("Referenced file '{}' does not exist.", reference), &ini_file, Some(line_index), Some(&section.name))),
+                }
+            }
+        }
+    }
+    diagnostics
+}
 
-            let mut current_section_title: Option<String> = None;
-            let mut found_constants_tag = false; // Track if we are past '; Constants'
+// Resolves a relative path component by component, ignoring case. Returns the on-disk spelling.
+fn find_case_insensitive_path(base: &Path, relative: &str) -> Option<String> {
+    let mut current = base.to_path_buf();
+    let mut actual_parts = Vec::new();
+    for part in relative.split('/').filter(|p| !p.is_empty() && *p != ".") {
+        if part == ".." {
+            current.pop();
+            actual_parts.push(part.to_string());
+            continue;
+        }
+        let entry_name = fs::read_dir(&current).ok()?
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .find(|name| name.eq_ignore_ascii_case(part))?;
+        current.push(&entry_name);
+        actual_parts.push(entry_name);
+    }
+    if current.is_file() { Some(actual_parts.join("/")) } else { None }
+}
 
-            for line_result in reader.lines() {
-                let line_raw = match line_result {
-                    Ok(l) => l,
-                    Err(_) => {
-                         println!("[get_ini_keybinds] Warning: Skipping unreadable line in {}", ini_path.display());
-                         continue;
-                    }
-                };
-                let line = line_raw.trim(); // Trimmed version for processing
+#[command]
+fn lint_asset(asset_id: i64, db_state: State<DbState>) -> CmdResult<AssetHealth> {
+    println!("[lint_asset] Linting asset ID {}", asset_id);
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+    let (asset_name, clean_relative_path, entity_slug, root_id) = {
+        let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+        conn.query_row(
+            "SELECT a.name, a.folder_name, e.slug, a.root_id FROM assets a JOIN entities e ON a.entity_id = e.id WHERE a.id = ?1",
+            params![asset_id],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?.replace("\\", "/"), row.get::<_, String>(2)?, row.get::<_, Option<i64>>(3)?)),
+        ).map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => format!("Asset with ID {} not found.", asset_id),
+            _ => format!("DB error fetching asset: {}", e),
+        })?
+    };
 
-                // Check for '; Constants' tag
-                 if !found_constants_tag && line.starts_with(';') && line[1..].trim_start().to_lowercase().contains("constants") {
-                    println!("[get_ini_keybinds] Found '; Constants' marker in {}", ini_path.display());
-                    found_constants_tag = true;
-                    continue; // Move to next line after finding the marker
-                }
+    let (mod_folder, is_enabled) = locate_asset_folder(&base_mods_path, root_id, &clean_relative_path)
+        .ok_or_else(|| format!("Mod folder for asset '{}' not found on disk.", asset_name))?;
+    let diagnostics = lint_mod_folder(&mod_folder);
+    println!("[lint_asset] {} diagnostics for '{}'", diagnostics.len(), asset_name);
+    Ok(AssetHealth { asset_id, asset_name, entity_slug, is_enabled, diagnostics })
+}
 
-                // Only process sections/keys if constants tag was found
-                if found_constants_tag {
-                    if line.starts_with('[') && line.ends_with(']') {
-                        let section_name = line[1..line.len()-1].trim().to_string();
-                        // Check if it's a keybind section
-                        if section_name.to_lowercase().starts_with("key") {
-                            current_section_title = Some(section_name); // Store the title
-                        } else {
-                            current_section_title = None; // Reset title if not a keybind section
-                        }
-                    } else if current_section_title.is_some() && line.to_lowercase().starts_with("key") && line.contains('=') {
-                         // Only process 'key =' lines if we are inside a valid [Key...] section *after* '; Constants'
-                         if let Some(value_part) = line.splitn(2, '=').nth(1) {
-                             let keybind_value = value_part.trim().to_string();
-                             if !keybind_value.is_empty() {
-                                  // .unwrap() is safe because we checked is_some()
-                                  found_keybinds.push(KeybindInfo {
-                                      title: current_section_title.clone().unwrap(),
-                                      key: keybind_value,
-                                  });
-                             }
-                         }
-                    }
-                }
-            } // End line loop
+#[command]
+fn get_library_health(include_disabled: Option<bool>, db_state: State<DbState>) -> CmdResult<LibraryHealthReport> {
+    let include_disabled = include_disabled.unwrap_or(true);
+    println!("[get_library_health] Checking library health (include disabled: {})", include_disabled);
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+    let assets = {
+        let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+        let mut stmt = conn.prepare("SELECT a.id, a.name, a.folder_name, e.slug, a.root_id FROM assets a JOIN entities e ON a.entity_id = e.id WHERE a.missing_since IS NULL ORDER BY a.name")
+            .map_err(|e| format!("Failed to prepare asset fetch: {}", e))?;
+        let rows = stmt.query_map([], |row| Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?.replace("\\", "/"),
+            row.get::<_, String>(3)?,
+            row.get::<_, Option<i64>>(4)?,
+        ))).and_then(|rows| rows.collect::<SqlResult<Vec<_>>>())
+           .map_err(|e| format!("Failed to fetch assets: {}", e))?;
+        rows
+    }; // Lock released before reading INI files
+
+    let mut report = LibraryHealthReport {
+        scanned_assets: 0,
+        assets_with_errors: 0,
+        assets_with_warnings: 0,
+        counts_by_code: HashMap::new(),
+        assets: Vec::new(),
+    };
+    for (asset_id, asset_name, clean_relative_path, entity_slug, root_id) in assets {
+        let (mod_folder, is_enabled) = match locate_asset_folder(&base_mods_path, root_id, &clean_relative_path) {
+            Some(found) => found,
+            None => continue,
+        };
+        if !is_enabled && !include_disabled { continue; }
+        report.scanned_assets += 1;
+
+        let diagnostics = lint_mod_folder(&mod_folder);
+        if diagnostics.is_empty() { continue; }
+        if diagnostics.iter().any(|d| d.severity == "error") {
+            report.assets_with_errors += 1;
+        } else if diagnostics.iter().any(|d| d.severity == "warning") {
+            report.assets_with_warnings += 1;
+        }
+        for diagnostic in &diagnostics {
+            *report.counts_by_code.entry(diagnostic.code.clone()).or_insert(0) += 1;
+        }
+        report.assets.push(AssetHealth { asset_id, asset_name, entity_slug, is_enabled, diagnostics });
+    }
 
-             // Check if keybinds were found in *this* file (after Constants)
-             if !found_keybinds.is_empty() {
-                println!("[get_ini_keybinds] SUCCESS: Found {} keybinds (after Constants) in {}. Returning.", found_keybinds.len(), ini_path.display());
-                return Ok(found_keybinds); // Found binds, return them
-            } else {
-                 println!("[get_ini_keybinds] No keybinds found (after Constants) in {}. Checking next file.", ini_path.display());
-             }
-        } // --- End loop through INI paths ---
+    println!("[get_library_health] Scanned {} assets: {} with errors, {} with warnings.", report.scanned_assets, report.assets_with_errors, report.assets_with_warnings);
+    Ok(report)
+}
 
-        // If loop finishes, no keybinds were found in any file
-        println!("[get_ini_keybinds] No keybinds found (after Constants) after checking all INI files for asset ID {}", asset_id);
-        Ok(Vec::new()) // Return empty Vec<KeybindInfo>
+#[command]
+fn get_asset_persisted_variables(asset_id: i64, db_state: State<DbState>) -> CmdResult<Vec<PersistedVariable>> {
+    println!("[get_asset_persisted_variables] Reading persisted variables for asset ID {}", asset_id);
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+    let location = {
+        let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+        get_asset_location_info(&conn, asset_id).map_err(|e| e.to_string())?
+    };
+    let (_, user_doc) = load_d3dx_user_ini(&base_mods_path).map_err(|e| e.to_string())?;
+    let user_values = read_d3dx_user_values(&user_doc);
+    Ok(collect_persisted_variables(&base_mods_path, location.root_id, &location.clean_relative_path, &user_values))
+}
 
-    })(); // Execute the closure
+#[command]
+fn reset_asset_persisted_variables(asset_id: i64, db_state: State<DbState>) -> CmdResult<usize> {
+    println!("[reset_asset_persisted_variables] Resetting persisted variables for asset ID {}", asset_id);
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+    let location = {
+        let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+        get_asset_location_info(&conn, asset_id).map_err(|e| e.to_string())?
+    };
+    let (user_path, user_doc) = load_d3dx_user_ini(&base_mods_path).map_err(|e| e.to_string())?;
+    let user_values = read_d3dx_user_values(&user_doc);
 
-    println!("[get_ini_keybinds] COMMAND END for asset ID: {}", asset_id);
-    result // Return the result of the closure (Result<Vec<KeybindInfo>, String>)
+    // Removing the entries makes 3DMigoto fall back to the INI defaults
+    let updates: Vec<(String, Option<String>)> = collect_persisted_variables(&base_mods_path, location.root_id, &location.clean_relative_path, &user_values)
+        .into_iter()
+        .filter(|v| v.value.is_some())
+        .map(|v| (v.user_ini_key, None))
+        .collect();
+    write_d3dx_user_values(&user_path, user_doc, &updates).map_err(|e| format!("Failed to update {}: {}", D3DX_USER_INI, e))
 }
 
 #[command]
@@ -4414,29 +9178,7 @@ fn open_asset_folder(asset_id: i64, db_state: State<DbState>) -> CmdResult<()> {
 
 
         // --- Determine the actual mod folder path on disk ---
-        // Ensure clean_relative_path uses OS-specific separators when joining with base_mods_path
-        // PathBuf::join handles this, but let's be explicit if needed later.
-        // For now, assume PathBuf construction is correct.
-        let relative_path_buf = PathBuf::from(&asset_info.clean_relative_path.replace("/", std::path::MAIN_SEPARATOR_STR)); // Ensure DB path uses native sep before join? Less critical usually.
-
-        let filename_osstr = relative_path_buf.file_name().ok_or_else(|| format!("Could not extract filename from DB path: {}", asset_info.clean_relative_path))?;
-        let filename_str = filename_osstr.to_string_lossy();
-        let disabled_filename = format!("{}{}", DISABLED_PREFIX, filename_str);
-        let relative_parent_path = relative_path_buf.parent();
-
-        let full_path_if_enabled = base_mods_path.join(&relative_path_buf);
-        let full_path_if_disabled = match relative_parent_path {
-            Some(parent) if parent.as_os_str().len() > 0 => base_mods_path.join(parent).join(&disabled_filename),
-            _ => base_mods_path.join(&disabled_filename),
-        };
-
-        let mod_folder_path_on_disk = if full_path_if_enabled.is_dir() {
-            Some(full_path_if_enabled)
-        } else if full_path_if_disabled.is_dir() {
-            Some(full_path_if_disabled)
-        } else {
-            None
-        };
+        let mod_folder_path_on_disk = locate_asset_folder(&base_mods_path, asset_info.root_id, &asset_info.clean_relative_path).map(|(path, _)| path);
 
         drop(conn_guard);
         println!("[open_asset_folder] DB lock released.");
@@ -4504,7 +9246,7 @@ fn add_asset_to_presets(asset_id: i64, preset_ids: Vec<i64>, db_state: State<DbS
 
     // Get current enabled state *once* before the loop
     let current_is_enabled = match get_current_asset_enabled_state(&tx, asset_id, &base_mods_path) {
-         Ok(enabled) => if enabled { 1 } else { 0 },
+         Ok(enabled) => if enabled == Some(true) { 1 } else { 0 }, // Missing folder counts as disabled
          Err(e) => {
              eprintln!("[add_asset_to_presets] Error getting current state for asset {}: {}. Aborting.", asset_id, e);
              return Err(format!("Failed to determine current enabled state for asset {}: {}", asset_id, e));
@@ -4529,11 +9271,33 @@ fn add_asset_to_presets(asset_id: i64, preset_ids: Vec<i64>, db_state: State<DbS
     }
 
     tx.commit().map_err(|e| format!("Failed to commit transaction: {}", e))?;
+    sync_asset_sidecars(&conn_guard, &[asset_id]);
 
     println!("[add_asset_to_presets] Successfully added/updated asset {} in presets. Rows affected: {}", asset_id, changes_made);
     Ok(())
 }
 
+// Writes the sidecar of every present asset, e.g. right after enabling write_sidecars.
+// Returns how many were written.
+#[command]
+fn write_all_sidecars(db_state: State<DbState>) -> CmdResult<usize> {
+    let base_mods_path = get_mods_base_path_from_settings(&db_state).map_err(|e| e.to_string())?;
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let asset_ids: Vec<i64> = conn.prepare("SELECT id FROM assets WHERE missing_since IS NULL")
+        .and_then(|mut stmt| stmt.query_map([], |row| row.get(0))?.collect())
+        .map_err(|e| format!("Failed to list assets: {}", e))?;
+
+    let mut written = 0;
+    for asset_id in &asset_ids {
+        match write_asset_sidecar(&conn, &base_mods_path, *asset_id) {
+            Ok(_) => written += 1,
+            Err(e) => eprintln!("[write_all_sidecars] Skipping asset {}: {}", asset_id, e),
+        }
+    }
+    println!("[write_all_sidecars] Wrote {} of {} sidecars.", written, asset_ids.len());
+    Ok(written)
+}
+
 #[command]
 fn get_available_games(app_handle: AppHandle) -> CmdResult<Vec<String>> {
     let data_dir = get_app_data_dir(&app_handle).map_err(|e| e.to_string())?;
@@ -4625,6 +9389,198 @@ fn run_traveler_migration(db_state: State<DbState>, app_handle: AppHandle) -> Cm
     run_traveler_migration_logic(&db_state, &app_handle)
 }
 
+// --- Database Backups ---
+// Taken with SQLite's online backup API, so the live connection can be copied consistently.
+
+fn get_backups_dir(data_dir: &Path, game_slug: &str) -> PathBuf {
+    data_dir.join(BACKUP_DIR_NAME).join(game_slug)
+}
+
+fn active_backups_dir(app_handle: &AppHandle, conn: &Connection) -> Result<PathBuf, AppError> {
+    let game_slug = get_setting_value(conn, DB_INTERNAL_GAME_SLUG_KEY)?.unwrap_or_else(|| DEFAULT_GAME_SLUG.to_string());
+    Ok(get_backups_dir(&get_app_data_dir(app_handle)?, &game_slug))
+}
+
+// Copies a database into a new backup file. Old backups are left alone, see prune_backups.
+fn backup_connection(conn: &Connection, backups_dir: &Path, reason: &str) -> Result<PathBuf, AppError> {
+    fs::create_dir_all(backups_dir)?;
+    let now_millis = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+    let target_path = backups_dir.join(format!("{}_{}.sqlite", now_millis, reason));
+    let mut target = Connection::open(&target_path)?;
+    {
+        let backup = rusqlite::backup::Backup::new(conn, &mut target)?;
+        backup.run_to_completion(BACKUP_PAGES_PER_STEP, std::time::Duration::from_millis(5), None)?;
+    }
+    println!("[backup] Saved '{}' backup to {}", reason, target_path.display());
+    Ok(target_path)
+}
+
+fn backup_database_file(db_path: &Path, backups_dir: &Path, reason: &str) -> Result<PathBuf, AppError> {
+    let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+    let path = backup_connection(&conn, backups_dir, reason)?;
+    prune_backups(backups_dir);
+    Ok(path)
+}
+
+// Backup before a large operation on the active game. Failures are logged, never fatal.
+fn backup_before_operation(conn: &Connection, app_handle: &AppHandle, reason: &str) {
+    let result = active_backups_dir(app_handle, conn)
+        .and_then(|dir| backup_connection(conn, &dir, reason).map(|_| dir));
+    match result {
+        Ok(dir) => prune_backups(&dir),
+        Err(e) => eprintln!("[backup] Warning: '{}' backup failed: {}", reason, e),
+    }
+}
+
+// Newest first
+fn list_backup_files(backups_dir: &Path, game_slug: &str) -> Vec<BackupInfo> {
+    let mut backups: Vec<BackupInfo> = fs::read_dir(backups_dir).into_iter().flatten()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let stem = file_name.strip_suffix(".sqlite")?;
+            let (millis, reason) = stem.split_once('_')?;
+            Some(BackupInfo {
+                created_at: (millis.parse::<u128>().ok()? / 1000) as i64,
+                reason: reason.to_string(),
+                game_slug: game_slug.to_string(),
+                size_bytes: entry.metadata().map(|m| m.len()).unwrap_or(0),
+                file_name,
+            })
+        })
+        .collect();
+    backups.sort_by(|a, b| b.file_name.cmp(&a.file_name));
+    backups
+}
+
+fn prune_backups(backups_dir: &Path) {
+    for old in list_backup_files(backups_dir, "").into_iter().skip(BACKUPS_KEEP) {
+        if let Err(e) = fs::remove_file(backups_dir.join(&old.file_name)) {
+            eprintln!("[backup] Failed to delete old backup '{}': {}", old.file_name, e);
+        }
+    }
+}
+
+// Overwrites the live database with a backup file, then migrates it (the backup may predate newer tables and settings)
+fn restore_connection(conn: &mut Connection, backup_path: &Path) -> Result<(), AppError> {
+    let source = Connection::open_with_flags(backup_path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+    {
+        let backup = rusqlite::backup::Backup::new(&source, conn)?;
+        backup.run_to_completion(BACKUP_PAGES_PER_STEP, std::time::Duration::from_millis(5), None)?;
+    }
+    conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+    run_schema_migrations(conn)
+}
+
+// None when the database is healthy, otherwise the reported problems
+// Opened writable (never created) since FTS5 can't validate its index on a read-only connection.
+fn check_database_integrity(db_path: &Path) -> Result<Option<String>, AppError> {
+    let conn = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+    let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+    let problems = stmt.query_map([], |row| row.get::<_, String>(0))?.collect::<SqlResult<Vec<String>>>()?;
+    if problems.len() == 1 && problems[0] == "ok" {
+        return Ok(None);
+    }
+    Ok(Some(problems.into_iter().take(5).collect::<Vec<_>>().join("\n")))
+}
+
+// Startup check of the active DB, before it is opened for the app. Offers the newest backup on failure.
+fn verify_active_database(app_handle: &AppHandle, data_dir: &Path, game_slug: &str) {
+    let active_db_path = data_dir.join(ACTIVE_DB_FILENAME);
+    if !active_db_path.exists() {
+        return;
+    }
+    let problem = match check_database_integrity(&active_db_path) {
+        Ok(None) => {
+            println!("Integrity check passed for {}.", active_db_path.display());
+            return;
+        }
+        Ok(Some(problem)) => problem,
+        Err(e) => e.to_string(),
+    };
+    eprintln!("WARNING: Integrity check failed for {}: {}", active_db_path.display(), problem);
+
+    let backups_dir = get_backups_dir(data_dir, game_slug);
+    let Some(latest) = list_backup_files(&backups_dir, game_slug).into_iter().next() else {
+        dialog::blocking::message(
+            app_handle.get_window("main").as_ref(),
+            "Database Problem",
+            format!("The database for '{}' failed its integrity check:\n{}\n\nNo backup is available. The app will try to continue.", game_slug, problem),
+        );
+        return;
+    };
+    let restore = dialog::blocking::ask(
+        app_handle.get_window("main").as_ref(),
+        "Database Problem",
+        format!("The database for '{}' failed its integrity check:\n{}\n\nRestore the latest backup ({}, '{}')?", game_slug, problem, latest.file_name, latest.reason),
+    );
+    if !restore {
+        return;
+    }
+    // Keep the damaged file next to the active one instead of deleting it
+    let now_millis = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+    let corrupt_path = data_dir.join(format!("{}.corrupt-{}", ACTIVE_DB_FILENAME, now_millis));
+    let result = fs::rename(&active_db_path, &corrupt_path)
+        .and_then(|_| fs::copy(backups_dir.join(&latest.file_name), &active_db_path));
+    match result {
+        Ok(_) => println!("Restored {} from backup '{}'. Damaged file kept as {}.", ACTIVE_DB_FILENAME, latest.file_name, corrupt_path.display()),
+        Err(e) => {
+            eprintln!("ERROR: Failed to restore backup '{}': {}", latest.file_name, e);
+            dialog::blocking::message(app_handle.get_window("main").as_ref(), "Restore Failed", format!("Failed to restore the backup: {}", e));
+        }
+    }
+}
+
+// Backups of the active game, newest first
+#[command]
+fn list_backups(db_state: State<DbState>, app_handle: AppHandle) -> CmdResult<Vec<BackupInfo>> {
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let game_slug = get_setting_value(&conn, DB_INTERNAL_GAME_SLUG_KEY).map_err(|e| e.to_string())?
+        .unwrap_or_else(|| DEFAULT_GAME_SLUG.to_string());
+    let backups_dir = active_backups_dir(&app_handle, &conn).map_err(|e| e.to_string())?;
+    Ok(list_backup_files(&backups_dir, &game_slug))
+}
+
+#[command]
+fn create_backup(db_state: State<DbState>, app_handle: AppHandle) -> CmdResult<BackupInfo> {
+    let conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let game_slug = get_setting_value(&conn, DB_INTERNAL_GAME_SLUG_KEY).map_err(|e| e.to_string())?
+        .unwrap_or_else(|| DEFAULT_GAME_SLUG.to_string());
+    let backups_dir = active_backups_dir(&app_handle, &conn).map_err(|e| e.to_string())?;
+    let path = backup_connection(&conn, &backups_dir, BACKUP_REASON_MANUAL).map_err(|e| format!("Backup failed: {}", e))?;
+    prune_backups(&backups_dir);
+    let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    list_backup_files(&backups_dir, &game_slug).into_iter().find(|b| b.file_name == file_name)
+        .ok_or_else(|| "Backup was written but could not be listed.".to_string())
+}
+
+// Replaces the active database with a backup of the same game. The current state is backed up first.
+#[command]
+fn restore_backup(file_name: String, db_state: State<DbState>, app_handle: AppHandle) -> CmdResult<()> {
+    println!("[restore_backup] Restoring '{}'", file_name);
+    if file_name.contains(['/', '\\']) || !file_name.ends_with(".sqlite") {
+        return Err(format!("Invalid backup name '{}'.", file_name));
+    }
+    let mut conn = db_state.0.lock().map_err(|_| "DB lock poisoned".to_string())?;
+    let backups_dir = active_backups_dir(&app_handle, &conn).map_err(|e| e.to_string())?;
+    let backup_path = backups_dir.join(&file_name);
+    if !backup_path.is_file() {
+        return Err(format!("Backup '{}' not found.", file_name));
+    }
+    if let Some(problem) = check_database_integrity(&backup_path).map_err(|e| format!("Failed to check backup: {}", e))? {
+        return Err(format!("Backup '{}' is damaged: {}", file_name, problem));
+    }
+
+    backup_connection(&conn, &backups_dir, BACKUP_REASON_PRE_RESTORE).map_err(|e| format!("Failed to back up the current state: {}", e))?;
+    restore_connection(&mut conn, &backup_path).map_err(|e| format!("Failed to restore backup: {}", e))?;
+    prune_backups(&backups_dir);
+    activation::set_active_settings(load_activation_settings(&conn).map_err(|e| e.to_string())?);
+    activation::set_mod_roots(load_mod_roots(&conn).map_err(|e| e.to_string())?);
+    activation::set_materialized_folders(load_materialized_folders(&conn).map_err(|e| e.to_string())?);
+    println!("[restore_backup] Restored '{}'", file_name);
+    Ok(())
+}
+
 // --- Main Function ---
 fn main() {
     let context = generate_context!(); // Generates context based on tauri.conf.json
@@ -4713,6 +9669,9 @@ fn main() {
                 // Step A: Archive the current active DB (if it exists).
                 // This should correspond to the 'last_slug'.
                 if active_db_path.exists() {
+                    if let Err(e) = backup_database_file(&active_db_path, &get_backups_dir(&data_dir, last_slug), BACKUP_REASON_GAME_SWITCH) {
+                        eprintln!("Warning: Backup before switching away from '{}' failed: {}", last_slug, e);
+                    }
                     println!("Archiving '{}' (from '{}') to '{}'", ACTIVE_DB_FILENAME, last_slug, last_game_archive_path.display());
                     // Attempt to rename the active DB file to its archived name.
                     if let Err(e) = fs::rename(&active_db_path, &last_game_archive_path) {
@@ -4779,6 +9738,7 @@ fn main() {
                  }
             }
             println!("Pre-initialization DB check complete.");
+            verify_active_database(&app_handle, &data_dir, requested_slug);
 
             // --- 3. Initialize DB Connection for State ---
             // Initialize the database connection using the (now correctly named) active DB file.
@@ -4852,9 +9812,28 @@ fn main() {
             // Presets
             create_preset, get_presets, get_favorite_presets, apply_preset,
             toggle_preset_favorite, delete_preset, overwrite_preset,
-            add_asset_to_presets,
+            add_asset_to_presets, preview_preset_apply, update_preset_settings,
+            set_preset_layers, shuffle_assets, get_shuffle_options, save_shuffle_options,
+            set_ini_keybind, reset_ini_keybind, get_keybind_collisions,
+            get_asset_persisted_variables, reset_asset_persisted_variables,
+            lint_asset, get_library_health,
+            get_asset_ini_files, toggle_asset_ini,
+            get_activation_settings, set_activation_mode, set_disabled_marker,
+            relocate_mods_library,
+            get_mod_roots, add_mod_root, update_mod_root, remove_mod_root, move_asset_to_root,
+            get_operation_history, undo_last_operation, redo, set_assets_enabled,
+            list_backups, create_backup, restore_backup,
+            get_trash, restore_trash_entry, purge_trash, get_missing_assets, restore_missing_asset, purge_missing_assets,
+            write_all_sidecars,
+            select_library_export_file, select_library_import_file, export_library, import_library,
+            select_preset_export_file, select_preset_import_file,
+            export_preset, analyze_preset_import, import_preset,
             // Dashboard & Version
-            get_dashboard_stats, get_app_version,
+            get_dashboard_stats, get_app_version, get_recently_added_assets, get_recently_used_assets,
+            set_asset_favorite, set_asset_rating, set_asset_notes,
+            get_tags, create_tag, update_tag, delete_tag, merge_tags, add_tags_to_assets, remove_tags_from_assets,
+            get_assets_by_tags, import_tags_from_ini, set_preset_tag_selectors,
+            search_assets, rebuild_search_index,
             // Keybinds
             get_ini_keybinds, open_asset_folder,
             // Multi-Game Commands
@@ -4863,4 +9842,354 @@ fn main() {
         ])
         .run(context) // Runs the Tauri application loop.
         .expect("error while running tauri application"); // Panic if the app fails to run.
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::ScratchDir;
+
+    // In-memory database with the original schema (assets.folder_name UNIQUE), migrated to the current one
+    fn migrated_connection() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "PRAGMA foreign_keys = ON;
+             CREATE TABLE categories ( id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT UNIQUE NOT NULL, slug TEXT UNIQUE NOT NULL );
+             CREATE TABLE entities ( id INTEGER PRIMARY KEY AUTOINCREMENT, category_id INTEGER NOT NULL, name TEXT NOT NULL, slug TEXT UNIQUE NOT NULL, description TEXT, details TEXT, base_image TEXT, FOREIGN KEY (category_id) REFERENCES categories (id) ON DELETE CASCADE );
+             CREATE TABLE assets ( id INTEGER PRIMARY KEY AUTOINCREMENT, entity_id INTEGER NOT NULL, name TEXT NOT NULL, description TEXT, folder_name TEXT NOT NULL UNIQUE, image_filename TEXT, author TEXT, category_tag TEXT, FOREIGN KEY (entity_id) REFERENCES entities (id) ON DELETE CASCADE );
+             CREATE TABLE settings ( key TEXT PRIMARY KEY NOT NULL, value TEXT NOT NULL );
+             CREATE TABLE presets ( id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT UNIQUE NOT NULL, is_favorite INTEGER NOT NULL DEFAULT 0 );
+             CREATE TABLE preset_assets ( preset_id INTEGER NOT NULL, asset_id INTEGER NOT NULL, is_enabled INTEGER NOT NULL, PRIMARY KEY (preset_id, asset_id), FOREIGN KEY (preset_id) REFERENCES presets(id) ON DELETE CASCADE, FOREIGN KEY (asset_id) REFERENCES assets(id) ON DELETE CASCADE );
+             INSERT INTO categories (name, slug) VALUES ('Characters', 'characters');
+             INSERT INTO entities (category_id, name, slug) VALUES (1, 'Diluc', 'diluc');
+             INSERT INTO assets (entity_id, name, folder_name) VALUES (1, 'Red Coat', 'characters/diluc/RedCoat');
+             INSERT INTO presets (name) VALUES ('Daily');
+             INSERT INTO preset_assets (preset_id, asset_id, is_enabled) VALUES (1, 1, 1);"
+        ).unwrap();
+        run_schema_migrations(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn folder_names_are_unique_per_root() {
+        let conn = migrated_connection();
+        conn.execute("INSERT INTO mod_roots (path, role) VALUES ('D:/Archive', 'library')", []).unwrap();
+        // Same path in another root is a different mod
+        conn.execute("INSERT INTO assets (entity_id, name, folder_name, root_id) VALUES (1, 'Red Coat (archive)', 'characters/diluc/RedCoat', 1)", []).unwrap();
+        // ...but not twice in the same root
+        assert!(conn.execute("INSERT INTO assets (entity_id, name, folder_name) VALUES (1, 'Copy', 'characters/diluc/RedCoat')", []).is_err());
+        assert!(conn.execute("INSERT INTO assets (entity_id, name, folder_name, root_id) VALUES (1, 'Copy', 'characters/diluc/RedCoat', 1)", []).is_err());
+    }
+
+    #[test]
+    fn assets_rebuild_keeps_rows_links_and_search() {
+        let conn = migrated_connection();
+        let name: String = conn.query_row("SELECT name FROM assets WHERE id = 1", [], |row| row.get(0)).unwrap();
+        assert_eq!(name, "Red Coat");
+        let hits: i64 = conn.query_row("SELECT COUNT(*) FROM assets_fts WHERE assets_fts MATCH 'coat'", [], |row| row.get(0)).unwrap();
+        assert_eq!(hits, 1);
+
+        // Search triggers are back after the rebuild
+        conn.execute("UPDATE assets SET name = 'Crimson Coat' WHERE id = 1", []).unwrap();
+        let hits: i64 = conn.query_row("SELECT COUNT(*) FROM assets_fts WHERE assets_fts MATCH 'crimson'", [], |row| row.get(0)).unwrap();
+        assert_eq!(hits, 1);
+
+        // Foreign keys of other tables still point at the rebuilt table
+        conn.execute("DELETE FROM assets WHERE id = 1", []).unwrap();
+        let links: i64 = conn.query_row("SELECT COUNT(*) FROM preset_assets", [], |row| row.get(0)).unwrap();
+        assert_eq!(links, 0);
+        let foreign_key_errors: i64 = conn.query_row("SELECT COUNT(*) FROM pragma_foreign_key_check", [], |row| row.get(0)).unwrap();
+        assert_eq!(foreign_key_errors, 0);
+    }
+
+    #[test]
+    fn remaps_onto_the_same_asset_are_reported() {
+        let mut resolved = vec![Some(10), None, Some(11), None];
+        apply_preset_import_remaps(&mut resolved, &[
+            PresetImportRemap { entry_index: 1, asset_id: Some(10) },
+            PresetImportRemap { entry_index: 3, asset_id: Some(12) },
+            PresetImportRemap { entry_index: 9, asset_id: Some(13) }, // Out of range, ignored
+        ]);
+        assert_eq!(resolved, vec![Some(10), Some(10), Some(11), Some(12)]);
+        let duplicates = find_duplicate_preset_targets(&resolved);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!((duplicates[0].asset_id, duplicates[0].entry_indices.clone()), (10, vec![0, 1]));
+    }
+
+    #[test]
+    fn presets_of_another_game_are_flagged() {
+        let conn = migrated_connection();
+        conn.execute("INSERT INTO settings (key, value) VALUES (?1, 'genshin')", params![DB_INTERNAL_GAME_SLUG_KEY]).unwrap();
+        assert!(check_preset_game(&conn, Some("zzz")).unwrap().is_some());
+        assert!(check_preset_game(&conn, Some("Genshin")).unwrap().is_none());
+        assert!(check_preset_game(&conn, None).unwrap().is_none());
+    }
+
+    #[test]
+    fn unknown_preset_scope_is_refused() {
+        let conn = migrated_connection();
+        let mut portable = PortablePreset {
+            format_version: PORTABLE_PRESET_FORMAT_VERSION,
+            name: "Outfit".to_string(),
+            game: None,
+            mode: Some(PRESET_MODE_STRICT.to_string()),
+            scope_entity_slug: Some("diluc".to_string()),
+            scope_category_slug: None,
+            tag_selectors: Vec::new(),
+            assets: Vec::new(),
+        };
+        assert_eq!(resolve_portable_preset_scope(&conn, &portable).unwrap(), (Some(1), None));
+        portable.scope_entity_slug = Some("furina".to_string());
+        assert!(resolve_portable_preset_scope(&conn, &portable).is_err());
+        portable.scope_entity_slug = None;
+        portable.scope_category_slug = Some("weapons".to_string());
+        assert!(resolve_portable_preset_scope(&conn, &portable).is_err());
+    }
+
+    #[test]
+    fn later_layers_win_including_implied_disables() {
+        let conn = migrated_connection();
+        conn.execute_batch(
+            "INSERT INTO assets (entity_id, name, folder_name) VALUES (1, 'Blue Coat', 'characters/diluc/BlueCoat');
+             INSERT INTO presets (name, mode) VALUES ('Only Blue', 'strict');
+             INSERT INTO preset_assets (preset_id, asset_id, is_enabled) VALUES (2, 2, 1);
+             INSERT INTO presets (name) VALUES ('Daily then Only Blue');
+             INSERT INTO preset_layers (preset_id, included_preset_id, position) VALUES (3, 1, 0), (3, 2, 1);
+             INSERT INTO presets (name) VALUES ('Only Blue then Daily');
+             INSERT INTO preset_layers (preset_id, included_preset_id, position) VALUES (4, 2, 0), (4, 1, 1);"
+        ).unwrap();
+        let enabled_of = |preset_id: i64, asset_id: i64| {
+            let preset = fetch_preset(&conn, preset_id).unwrap();
+            resolve_preset_target_states(&conn, &preset).unwrap().into_iter().find(|s| s.asset_id == asset_id).map(|s| s.is_enabled)
+        };
+        // The strict layer comes last, so its implied disable overrides Daily's explicit enable
+        assert_eq!(enabled_of(3, 1), Some(false));
+        assert_eq!(enabled_of(3, 2), Some(true));
+        // Daily comes last and enables Red Coat again
+        assert_eq!(enabled_of(4, 1), Some(true));
+        assert_eq!(enabled_of(4, 2), Some(true));
+    }
+
+    #[test]
+    fn shuffle_disables_the_whole_entity_and_defers_history() {
+        let conn = migrated_connection();
+        conn.execute_batch(
+            "UPDATE assets SET is_favorite = 1 WHERE id = 1;
+             INSERT INTO assets (entity_id, name, folder_name, is_favorite) VALUES (1, 'Blue Coat', 'characters/diluc/BlueCoat', 1);
+             INSERT INTO assets (entity_id, name, folder_name, is_favorite) VALUES (1, 'Green Coat', 'characters/diluc/GreenCoat', 0);"
+        ).unwrap();
+        let mods = ScratchDir::new("shuffle");
+        for folder in ["RedCoat", "BlueCoat", "GreenCoat"] {
+            fs::create_dir_all(mods.join("characters/diluc").join(folder)).unwrap();
+        }
+
+        let options = ShuffleOptions { favorites_only: true, ..ShuffleOptions::default() };
+        let (states, picks) = pick_shuffle_states(&conn, &mods, &options).unwrap();
+        assert_eq!(picks.len(), 1);
+        assert!(picks[0].asset_id != 3, "Green Coat isn't a favorite");
+        let state_of = |asset_id: i64| states.iter().find(|s| s.asset_id == asset_id).map(|s| s.is_enabled);
+        assert_eq!(state_of(picks[0].asset_id), Some(true));
+        assert_eq!(state_of(3), Some(false));
+        assert_eq!(states.iter().filter(|s| s.is_enabled).count(), 1);
+
+        let history: i64 = conn.query_row("SELECT COUNT(*) FROM shuffle_history", [], |row| row.get(0)).unwrap();
+        assert_eq!(history, 0);
+        record_shuffle_picks(&conn, &picks).unwrap();
+        let history: i64 = conn.query_row("SELECT COUNT(*) FROM shuffle_history", [], |row| row.get(0)).unwrap();
+        assert_eq!(history, 1);
+    }
+
+    #[test]
+    fn preset_capture_records_fully_enabled_mods() {
+        let conn = migrated_connection();
+        let mods = ScratchDir::new("capture");
+        let folder = mods.join("characters/diluc/RedCoat");
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("Coat.ini"), "[TextureOverrideCoat]\n").unwrap();
+        fs::write(folder.join("Hat.ini"), "[TextureOverrideHat]\n").unwrap();
+
+        assert_eq!(capture_preset_ini_states(&conn, 1, &mods).unwrap(), 2);
+        let enabled: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM preset_asset_inis WHERE preset_id = 1 AND is_enabled = 1", [], |row| row.get(0)
+        ).unwrap();
+        assert_eq!(enabled, 2);
+    }
+
+    #[test]
+    fn missing_assets_keep_their_folder_claimed() {
+        let conn = migrated_connection();
+        conn.execute_batch(
+            "UPDATE assets SET missing_since = 1 WHERE id = 1;
+             INSERT INTO assets (entity_id, name, folder_name) VALUES (1, 'Blue Coat', 'characters/diluc/BlueCoat');"
+        ).unwrap();
+        let error = ensure_folder_unclaimed(&conn, None, "characters/diluc/RedCoat", 2).unwrap_err();
+        assert!(error.contains("deleted or went missing"), "{}", error);
+        assert!(ensure_folder_unclaimed(&conn, None, "characters/diluc/BlueCoat", 1).is_err());
+        assert!(ensure_folder_unclaimed(&conn, None, "characters/diluc/RedCoat", 1).is_ok());
+        assert!(ensure_folder_unclaimed(&conn, Some(5), "characters/diluc/RedCoat", 2).is_ok());
+    }
+
+    #[test]
+    fn consumed_trash_entries_go_stale_in_the_journal() {
+        let conn = migrated_connection();
+        record_operation(&conn, "Deleted 'Red Coat'", &JournalOp::Trash { asset_id: 1, trash_id: Some(7) }).unwrap();
+        record_operation(&conn, "Deleted 'Blue Coat'", &JournalOp::Trash { asset_id: 2, trash_id: Some(8) }).unwrap();
+        assert_eq!(mark_trash_journal_stale(&conn, 7).unwrap(), 1);
+        let stale: Vec<bool> = conn.prepare("SELECT stale FROM operation_journal ORDER BY id").unwrap()
+            .query_map([], |row| row.get(0)).unwrap().collect::<SqlResult<_>>().unwrap();
+        assert_eq!(stale, vec![true, false]);
+    }
+
+    #[test]
+    fn edited_ini_sections_reach_the_search_index() {
+        let conn = migrated_connection();
+        let folder = ScratchDir::new("sections");
+        fs::create_dir_all(folder.join("Variants")).unwrap();
+        fs::write(folder.join("Coat.ini"), "[TextureOverrideCoat]\n").unwrap();
+        fs::write(folder.join("DISABLED_Hat.ini"), "[TextureOverrideHat]\n").unwrap();
+        fs::write(folder.join("Variants/Scarf.ini"), "[TextureOverrideScarf]\n").unwrap();
+        let hits = |term: &str| -> i64 {
+            conn.query_row("SELECT COUNT(*) FROM assets_fts WHERE assets_fts MATCH ?1", params![term], |row| row.get(0)).unwrap()
+        };
+
+        // Not indexed until the setting is on
+        refresh_asset_ini_sections(&conn, &folder, 1).unwrap();
+        assert_eq!(hits("textureoverridecoat"), 0);
+        conn.execute("INSERT INTO settings (key, value) VALUES (?1, 'true')", params![SETTINGS_KEY_SEARCH_INI_SECTIONS]).unwrap();
+        refresh_asset_ini_sections(&conn, &folder, 1).unwrap();
+        assert_eq!((hits("textureoverridecoat"), hits("scarf"), hits("hat")), (1, 1, 0));
+
+        // Re-enabling the INI makes its sections searchable
+        fs::rename(folder.join("DISABLED_Hat.ini"), folder.join("Hat.ini")).unwrap();
+        refresh_asset_ini_sections(&conn, &folder, 1).unwrap();
+        assert_eq!(hits("hat"), 1);
+    }
+
+    #[test]
+    fn backups_restore_the_saved_state() {
+        let dir = ScratchDir::new("backup");
+        let mut conn = migrated_connection();
+        assert_eq!(get_schema_version(&conn).unwrap(), SCHEMA_VERSION);
+        let backup_path = backup_connection(&conn, &dir, BACKUP_REASON_MANUAL).unwrap();
+        assert_eq!(check_database_integrity(&backup_path).unwrap(), None);
+
+        conn.execute("UPDATE assets SET name = 'Renamed' WHERE id = 1", []).unwrap();
+        restore_connection(&mut conn, &backup_path).unwrap();
+        let name: String = conn.query_row("SELECT name FROM assets WHERE id = 1", [], |row| row.get(0)).unwrap();
+        assert_eq!(name, "Red Coat");
+        // The search index came back with the rows
+        let hits: i64 = conn.query_row("SELECT COUNT(*) FROM assets_fts WHERE assets_fts MATCH 'red'", [], |row| row.get(0)).unwrap();
+        assert_eq!(hits, 1);
+    }
+
+    #[test]
+    fn pruning_keeps_the_newest_backups() {
+        let dir = ScratchDir::new("prune");
+        let total = BACKUPS_KEEP + 3;
+        for millis in 1..=total {
+            fs::write(dir.join(format!("{:013}_{}.sqlite", millis, BACKUP_REASON_MANUAL)), "").unwrap();
+        }
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        prune_backups(&dir);
+        let kept = list_backup_files(&dir, "");
+        assert_eq!(kept.len(), BACKUPS_KEEP);
+        assert_eq!(kept.last().unwrap().file_name, format!("{:013}_{}.sqlite", total - BACKUPS_KEEP + 1, BACKUP_REASON_MANUAL));
+        assert!(dir.join("notes.txt").is_file());
+    }
+
+    #[test]
+    fn only_old_schemas_need_a_migration_backup() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert_eq!(get_schema_version(&conn).unwrap(), 0);
+        let conn = migrated_connection();
+        run_schema_migrations(&conn).unwrap();
+        assert_eq!(get_schema_version(&conn).unwrap(), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn persisted_variables_of_nested_inis_use_their_path() {
+        let mods = ScratchDir::new("persisted");
+        let folder = mods.join("characters/diluc/RedCoat");
+        fs::create_dir_all(folder.join("Variants")).unwrap();
+        fs::write(folder.join("Variants/Coat.ini"), "[Constants]\nglobal persist $swap = 0\n").unwrap();
+        fs::write(folder.join("Variants/DISABLED_Hat.ini"), "[Constants]\nglobal persist $hat = 0\n").unwrap();
+        let key = format!("$\\{}\\characters\\diluc\\redcoat\\variants\\coat.ini\\swap", mods.file_name().unwrap().to_string_lossy().to_lowercase());
+        let user_values = HashMap::from([(key.clone(), (0, "2".to_string()))]);
+
+        let variables = collect_persisted_variables(&mods, None, "characters/diluc/RedCoat", &user_values);
+        assert_eq!(variables.len(), 1);
+        assert_eq!(variables[0].ini_file, "Variants/Coat.ini");
+        assert_eq!(variables[0].user_ini_key, key);
+        assert_eq!(variables[0].value.as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn lint_covers_inis_in_subfolders() {
+        let folder = ScratchDir::new("lint");
+        fs::create_dir_all(folder.join("Variants")).unwrap();
+        fs::write(folder.join("Coat.ini"), "[TextureOverrideCoat]\nhash = 1\n").unwrap();
+        fs::write(folder.join("Variants/Coat.ini"), "[TextureOverrideCoat]\nhash = 2\n[ResourceCoat]\nfilename = Coat.dds\n").unwrap();
+        fs::write(folder.join("Variants/DISABLED_Hat.ini"), "[ResourceHat]\nfilename = Hat.dds\n").unwrap();
+
+        let diagnostics = lint_mod_folder(&folder);
+        let codes: Vec<(&str, &str)> = diagnostics.iter().map(|d| (d.code.as_str(), d.ini_file.as_deref().unwrap_or(""))).collect();
+        assert!(codes.iter().any(|(code, _)| *code == "duplicate_section"), "{:?}", codes);
+        assert!(codes.contains(&("missing_resource", "Variants/Coat.ini")), "{:?}", codes);
+        assert!(!codes.iter().any(|(_, file)| file.contains("Hat")), "{:?}", codes);
+    }
+
+    #[test]
+    fn inis_in_subfolders_are_toggled_by_relative_path() {
+        let folder = ScratchDir::new("ini_states");
+        fs::create_dir_all(folder.join("Variants")).unwrap();
+        fs::write(folder.join("Coat.ini"), "").unwrap();
+        fs::write(folder.join("Variants/DISABLED_Hat.ini"), "").unwrap();
+
+        let states = list_asset_ini_states(&folder);
+        let hat = states.iter().find(|f| f.file_name == "Variants/Hat.ini").unwrap();
+        assert_eq!((hat.disk_file_name.as_str(), hat.is_enabled), ("Variants/DISABLED_Hat.ini", false));
+
+        assert!(set_ini_file_enabled(&folder, "Variants/Hat.ini", true).unwrap());
+        assert!(folder.join("Variants/Hat.ini").is_file());
+        assert!(!set_ini_file_enabled(&folder, "variants/hat.ini", false).unwrap());
+        assert!(folder.join("Variants/DISABLED_Hat.ini").is_file());
+    }
+
+    #[test]
+    fn fuzzy_search_matches_close_terms() {
+        let conn = migrated_connection();
+        assert_eq!(build_search_match_expression(&conn, "coet", false).unwrap().unwrap(), "(\"coet\"*)");
+        assert_eq!(build_search_match_expression(&conn, "coet", true).unwrap().unwrap(), "(\"coet\"* OR \"coat\")");
+        // Too far away, or only three letters long
+        assert_eq!(build_search_match_expression(&conn, "boat", true).unwrap().unwrap(), "(\"boat\"*)");
+        assert_eq!(build_search_match_expression(&conn, "rad", true).unwrap().unwrap(), "(\"rad\"*)");
+    }
+
+    #[test]
+    fn sidecars_round_trip_into_existing_presets_only() {
+        let conn = migrated_connection();
+        let mods = ScratchDir::new("sidecar");
+        let folder = mods.join("characters/diluc/RedCoat");
+        fs::create_dir_all(&folder).unwrap();
+        write_asset_sidecar(&conn, &mods, 1).unwrap();
+        let sidecar = read_mod_sidecar(&folder).unwrap();
+        assert_eq!(sidecar.name.as_deref(), Some("Red Coat"));
+        assert_eq!(sidecar.tags, None);
+        assert_eq!(sidecar.presets.iter().map(|p| (p.name.as_str(), p.is_enabled)).collect::<Vec<_>>(), vec![("Daily", true)]);
+
+        assign_tags(&conn, 1, &["Outfit".to_string()]).unwrap();
+        write_asset_sidecar(&conn, &mods, 1).unwrap();
+        assert_eq!(read_mod_sidecar(&folder).unwrap().tags, Some(vec!["Outfit".to_string()]));
+
+        conn.execute("INSERT INTO assets (entity_id, name, folder_name) VALUES (1, 'Blue Coat', 'characters/diluc/BlueCoat')", []).unwrap();
+        let entries = vec![
+            SidecarPresetEntry { name: "Daily".to_string(), is_enabled: false },
+            SidecarPresetEntry { name: "Gone".to_string(), is_enabled: true },
+        ];
+        assert_eq!(restore_sidecar_presets(&conn, 2, &entries).unwrap(), vec!["Gone".to_string()]);
+        let presets: i64 = conn.query_row("SELECT COUNT(*) FROM presets", [], |row| row.get(0)).unwrap();
+        assert_eq!(presets, 1);
+        let enabled: bool = conn.query_row("SELECT is_enabled FROM preset_assets WHERE preset_id = 1 AND asset_id = 2", [], |row| row.get(0)).unwrap();
+        assert!(!enabled);
+    }
+}
@@ -0,0 +1,534 @@
+// --- 3DMigoto / XXMI INI Parser ---
+// The generic `ini` crate can't handle this dialect (repeated keys, `if/endif` blocks,
+// `global persist $var` declarations, bare `else` lines), so mod INIs are parsed here.
+// Every line keeps its raw text, including line endings and comments, so a parsed
+// document written back out is byte-identical unless a line was explicitly edited.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConditionKeyword {
+    If,
+    ElseIf,
+    Else,
+    EndIf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IniLineKind {
+    Blank,
+    Comment { text: String },
+    Section { name: String },
+    // if / else if / elif / else / endif. `expression` is empty for else/endif.
+    Condition { keyword: ConditionKeyword, expression: String },
+    // `key = value`, `$var = value`, `global persist $var = 0` or a bare `global $var`.
+    // Modifiers are the tokens before the key (global, local, persist, pre, post).
+    Assignment { modifiers: Vec<String>, key: String, value: Option<String> },
+    Other,
+}
+
+#[derive(Debug, Clone)]
+pub struct IniLine {
+    pub raw: String, // Original text including the line terminator
+    pub kind: IniLineKind,
+    pub section_index: Option<usize>, // None for lines before the first section
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionKind {
+    Constants,
+    Present,
+    Key,
+    CommandList,
+    TextureOverride,
+    ShaderOverride,
+    ShaderRegex,
+    Resource,
+    CustomShader,
+    Other,
+}
+
+impl SectionKind {
+    fn from_name(name: &str) -> Self {
+        let lower = name.to_lowercase();
+        if lower == "constants" { SectionKind::Constants }
+        else if lower == "present" { SectionKind::Present }
+        else if lower.starts_with("key") { SectionKind::Key }
+        else if lower.starts_with("commandlist") { SectionKind::CommandList }
+        else if lower.starts_with("textureoverride") { SectionKind::TextureOverride }
+        else if lower.starts_with("shaderoverride") { SectionKind::ShaderOverride }
+        else if lower.starts_with("shaderregex") { SectionKind::ShaderRegex }
+        else if lower.starts_with("resource") { SectionKind::Resource }
+        else if lower.starts_with("customshader") { SectionKind::CustomShader }
+        else { SectionKind::Other }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IniSection {
+    pub name: String,
+    pub kind: SectionKind,
+    pub header_line: usize,    // Index into IniDocument::lines
+    pub body: Range<usize>,    // Lines after the header up to the next section
+    pub leading_comments: Vec<String>, // Comment block directly above the header
+}
+
+// A variable declared in [Constants], e.g. `global persist $swapvar = 0`
+#[derive(Debug, Clone)]
+pub struct VariableDeclaration {
+    pub name: String,
+    pub is_persist: bool,
+    pub initial_value: Option<String>,
+}
+
+// `$var = 0,1,2` inside a [Key...] section
+#[derive(Debug, Clone)]
+pub struct KeyVariable {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+// Structured view of a [Key...] section
+#[derive(Debug, Clone)]
+pub struct KeySection {
+    pub section_index: usize,
+    pub name: String,
+    pub keys: Vec<(usize, String)>, // (line index, value) per `key =` line
+    pub back: Vec<(usize, String)>, // (line index, value) per `back =` line
+    pub bind_type: Option<String>,  // cycle / toggle / hold / activate
+    pub condition: Option<String>,
+    pub variables: Vec<KeyVariable>,
+    pub runs: Vec<String>,          // `run = CommandList...` targets
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IniDocument {
+    pub lines: Vec<IniLine>,
+    pub sections: Vec<IniSection>,
+    pub lossy: bool, // File wasn't valid UTF-8; writing it back would not round-trip
+}
+
+impl IniDocument {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        Ok(match String::from_utf8(bytes) {
+            Ok(text) => Self::parse(&text),
+            Err(e) => {
+                let mut doc = Self::parse(&String::from_utf8_lossy(e.as_bytes()));
+                doc.lossy = true;
+                doc
+            }
+        })
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let mut doc = IniDocument::default();
+        let mut pending_comments: Vec<String> = Vec::new();
+
+        for raw in text.split_inclusive('\n') {
+            let content = raw.trim_end_matches(['\r', '\n']).trim_start_matches('\u{feff}').trim();
+            let kind = classify_line(content);
+            let line_index = doc.lines.len();
+
+            match &kind {
+                IniLineKind::Section { name } => {
+                    if let Some(previous) = doc.sections.last_mut() {
+                        previous.body.end = line_index;
+                    }
+                    doc.sections.push(IniSection {
+                        name: name.clone(),
+                        kind: SectionKind::from_name(name),
+                        header_line: line_index,
+                        body: (line_index + 1)..(line_index + 1),
+                        leading_comments: std::mem::take(&mut pending_comments),
+                    });
+                }
+                IniLineKind::Comment { text } => pending_comments.push(text.clone()),
+                _ => pending_comments.clear(), // Label comments must sit directly above the header
+            }
+
+            doc.lines.push(IniLine {
+                raw: raw.to_string(),
+                kind,
+                section_index: doc.sections.len().checked_sub(1),
+            });
+        }
+
+        if let Some(last) = doc.sections.last_mut() {
+            last.body.end = doc.lines.len();
+        }
+        doc
+    }
+
+    pub fn section_index(&self, name: &str) -> Option<usize> {
+        self.sections.iter().position(|s| s.name.eq_ignore_ascii_case(name))
+    }
+
+    // Assignments in a section body: (line index, modifiers, key, value)
+    pub fn assignments(&self, section_index: usize) -> impl Iterator<Item = (usize, &[String], &str, Option<&str>)> {
+        let range = self.sections.get(section_index).map(|s| s.body.clone()).unwrap_or(0..0);
+        self.lines[range.clone()].iter().zip(range).filter_map(|(line, index)| match &line.kind {
+            IniLineKind::Assignment { modifiers, key, value } => Some((index, modifiers.as_slice(), key.as_str(), value.as_deref())),
+            _ => None,
+        })
+    }
+
+    // `namespace = ...` from the top of the file (XXMI)
+    pub fn namespace(&self) -> Option<&str> {
+        self.lines.iter().take_while(|l| l.section_index.is_none()).find_map(|line| match &line.kind {
+            IniLineKind::Assignment { key, value: Some(value), .. } if key.eq_ignore_ascii_case("namespace") => Some(value.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn constants(&self) -> Vec<VariableDeclaration> {
+        let mut declarations = Vec::new();
+        for (section_index, section) in self.sections.iter().enumerate() {
+            if section.kind != SectionKind::Constants { continue; }
+            for (_, modifiers, key, value) in self.assignments(section_index) {
+                if !key.starts_with('$') { continue; }
+                declarations.push(VariableDeclaration {
+                    name: key.to_string(),
+                    is_persist: modifiers.iter().any(|m| m.eq_ignore_ascii_case("persist")),
+                    initial_value: value.map(str::to_string),
+                });
+            }
+        }
+        declarations
+    }
+
+    pub fn key_sections(&self) -> Vec<KeySection> {
+        let mut result = Vec::new();
+        for (section_index, section) in self.sections.iter().enumerate() {
+            if section.kind != SectionKind::Key { continue; }
+            let mut key_section = KeySection {
+                section_index,
+                name: section.name.clone(),
+                keys: Vec::new(),
+                back: Vec::new(),
+                bind_type: None,
+                condition: None,
+                variables: Vec::new(),
+                runs: Vec::new(),
+            };
+            for (line_index, _, key, value) in self.assignments(section_index) {
+                let value = value.unwrap_or("").to_string();
+                match key.to_lowercase().as_str() {
+                    "key" => key_section.keys.push((line_index, value)),
+                    "back" => key_section.back.push((line_index, value)),
+                    "type" => key_section.bind_type = Some(value.to_lowercase()),
+                    "condition" => key_section.condition = Some(value),
+                    "run" => key_section.runs.push(value),
+                    _ if key.starts_with('$') => key_section.variables.push(KeyVariable {
+                        name: key.to_string(),
+                        values: value.split(',').map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).collect(),
+                    }),
+                    _ => {}
+                }
+            }
+            result.push(key_section);
+        }
+        result
+    }
+
+    // Replaces the value of an assignment line, keeping indentation, spacing around `=`
+    // and the line terminator. Returns false if the line isn't an assignment with `=`.
+    pub fn set_assignment_value(&mut self, line_index: usize, new_value: &str) -> bool {
+        let line = match self.lines.get_mut(line_index) {
+            Some(l) => l,
+            None => return false,
+        };
+        let (modifiers, key) = match &line.kind {
+            IniLineKind::Assignment { modifiers, key, value: Some(_) } => (modifiers.clone(), key.clone()),
+            _ => return false,
+        };
+        let body = line.raw.trim_end_matches(['\r', '\n']);
+        let terminator = line.raw[body.len()..].to_string();
+        let eq_pos = match body.find('=') {
+            Some(p) => p,
+            None => return false,
+        };
+        let after_eq = &body[eq_pos + 1..];
+        let spacing = &after_eq[..after_eq.len() - after_eq.trim_start().len()];
+        line.raw = format!("{}{}{}{}", &body[..=eq_pos], spacing, new_value, terminator);
+        line.kind = IniLineKind::Assignment { modifiers, key, value: Some(new_value.to_string()) };
+        true
+    }
+
+    // Replaces `range` of lines with `new_lines` (given without terminators) and re-parses.
+    // New lines use the document's line ending.
+    pub fn splice_lines(&mut self, range: Range<usize>, new_lines: &[String]) {
+        let line_ending = if self.lines.first().is_some_and(|l| l.raw.ends_with("\r\n")) { "\r\n" } else { "\n" };
+        let mut text = String::new();
+        for line in &self.lines[..range.start] {
+            text.push_str(&line.raw);
+        }
+        if !new_lines.is_empty() && !text.is_empty() && !text.ends_with('\n') {
+            text.push_str(line_ending);
+        }
+        for new_line in new_lines {
+            text.push_str(new_line);
+            text.push_str(line_ending);
+        }
+        for line in &self.lines[range.end..] {
+            text.push_str(&line.raw);
+        }
+        let lossy = self.lossy;
+        *self = Self::parse(&text);
+        self.lossy = lossy;
+    }
+}
+
+impl fmt::Display for IniDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            f.write_str(&line.raw)?;
+        }
+        Ok(())
+    }
+}
+
+fn classify_line(content: &str) -> IniLineKind {
+    if content.is_empty() {
+        return IniLineKind::Blank;
+    }
+    if let Some(text) = content.strip_prefix(';') {
+        return IniLineKind::Comment { text: text.trim().to_string() };
+    }
+    if content.starts_with('[') && content.ends_with(']') {
+        return IniLineKind::Section { name: content[1..content.len() - 1].trim().to_string() };
+    }
+
+    let lower = content.to_lowercase();
+    let first_word = lower.split_whitespace().next().unwrap_or("");
+    match first_word {
+        "if" => return condition(ConditionKeyword::If, &content[2..]),
+        "elif" => return condition(ConditionKeyword::ElseIf, &content[4..]),
+        "endif" => return condition(ConditionKeyword::EndIf, ""),
+        "else" => {
+            let rest = content[4..].trim_start();
+            return if rest.to_lowercase().starts_with("if") && rest[2..].starts_with(char::is_whitespace) {
+                condition(ConditionKeyword::ElseIf, &rest[2..])
+            } else {
+                condition(ConditionKeyword::Else, "")
+            };
+        }
+        _ => {}
+    }
+
+    let (key_part, value) = match content.split_once('=') {
+        Some((k, v)) => (k.trim(), Some(v.trim().to_string())),
+        None => (content, None),
+    };
+    let mut tokens: Vec<String> = key_part.split_whitespace().map(str::to_string).collect();
+    let key = match tokens.pop() {
+        Some(k) => k,
+        None => return IniLineKind::Other,
+    };
+    // Without `=` only declarations like `global $var` are meaningful
+    if value.is_none() && !key.starts_with('$') {
+        return IniLineKind::Other;
+    }
+    IniLineKind::Assignment { modifiers: tokens, key, value }
+}
+
+fn condition(keyword: ConditionKeyword, expression: &str) -> IniLineKind {
+    IniLineKind::Condition { keyword, expression: expression.trim().to_string() }
+}
+
+// --- Key Expressions ---
+// `key = ctrl alt VK_F1`, `key = no_modifiers x`, `key = VK_UP` ...
+// Normalized so bindings written differently (VK_ prefix, case, left/right modifiers) compare equal.
+
+const MODIFIERS: [&str; 3] = ["CTRL", "ALT", "SHIFT"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyExpression {
+    pub main_key: String,      // e.g. "F1", "UP", "X"
+    pub required: Vec<String>, // Modifiers that must be held (sorted)
+    pub excluded: Vec<String>, // Modifiers that must not be held (sorted)
+}
+
+impl KeyExpression {
+    pub fn parse(expression: &str) -> Option<Self> {
+        let mut required = Vec::new();
+        let mut excluded = Vec::new();
+        let mut main_key = None;
+
+        for token in expression.split_whitespace() {
+            let upper = token.to_uppercase();
+            let name = upper.strip_prefix("VK_").unwrap_or(&upper);
+            if name == "NO_MODIFIERS" {
+                excluded.extend(MODIFIERS.iter().map(|m| m.to_string()));
+            } else if let Some(modifier) = name.strip_prefix("NO_").and_then(canonical_modifier) {
+                excluded.push(modifier.to_string());
+            } else {
+                // The last token is the main key; a modifier on its own (`key = VK_SHIFT`) is one too
+                let key_name = canonical_modifier(name).map(str::to_string).unwrap_or_else(|| canonical_key_name(name));
+                if let Some(previous) = main_key.replace(key_name) {
+                    if MODIFIERS.contains(&previous.as_str()) {
+                        required.push(previous);
+                    }
+                }
+            }
+        }
+
+        let main_key = main_key?;
+        required.sort();
+        required.dedup();
+        excluded.retain(|m| !required.contains(m) && *m != main_key);
+        excluded.sort();
+        excluded.dedup();
+        Some(KeyExpression { main_key, required, excluded })
+    }
+
+    // True if one key press can trigger both bindings
+    pub fn overlaps(&self, other: &KeyExpression) -> bool {
+        self.main_key == other.main_key
+            && !self.required.iter().chain(&other.required).any(|m| self.excluded.contains(m) || other.excluded.contains(m))
+    }
+}
+
+impl fmt::Display for KeyExpression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for modifier in &self.required {
+            write!(f, "{}+", modifier)?;
+        }
+        f.write_str(&self.main_key)
+    }
+}
+
+fn canonical_modifier(name: &str) -> Option<&'static str> {
+    match name {
+        "CTRL" | "CONTROL" | "LCTRL" | "RCTRL" | "LCONTROL" | "RCONTROL" => Some("CTRL"),
+        "ALT" | "MENU" | "LALT" | "RALT" | "LMENU" | "RMENU" => Some("ALT"),
+        "SHIFT" | "LSHIFT" | "RSHIFT" => Some("SHIFT"),
+        _ => None,
+    }
+}
+
+fn canonical_key_name(name: &str) -> String {
+    match name {
+        "RETURN" => "ENTER".to_string(),
+        "ESCAPE" => "ESC".to_string(),
+        "PRIOR" => "PAGEUP".to_string(),
+        "NEXT" => "PAGEDOWN".to_string(),
+        "BACK" => "BACKSPACE".to_string(),
+        _ => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\u{feff}; Sample mod\r\nnamespace = Sample\\Mod\r\n\r\n[Constants]\r\nglobal persist $swapvar = 0\r\nglobal $active\r\n\r\n; Toggle outfit\r\n[KeySwap]\r\ncondition = $active == 1\r\nkey = ctrl VK_F5\r\ntype = cycle\r\n$swapvar = 0,1,2\r\n\r\n[TextureOverrideBody]\r\nhash = abcd1234\r\nif $swapvar == 0\r\n    ib = ResourceBodyIB\r\nelse if $swapvar == 1\r\n    ib = null\r\nelse\r\n    run = CommandListBody\r\nendif\r\n";
+
+    #[test]
+    fn unedited_document_round_trips_byte_identical() {
+        for text in [SAMPLE, "[Present]\nx = 1", "", "\n\n", "; only a comment", "[KeyA]\r\nkey = A\n[KeyB]\rkey = B\r\n"] {
+            assert_eq!(IniDocument::parse(text).to_string(), text);
+        }
+    }
+
+    #[test]
+    fn bom_and_crlf_are_kept() {
+        let doc = IniDocument::parse(SAMPLE);
+        assert!(doc.lines[0].raw.starts_with('\u{feff}'));
+        assert_eq!(doc.lines[0].kind, IniLineKind::Comment { text: "Sample mod".to_string() });
+        assert!(doc.lines.iter().all(|l| l.raw.ends_with("\r\n")));
+        assert_eq!(doc.namespace(), Some("Sample\\Mod"));
+    }
+
+    #[test]
+    fn comments_label_the_following_section() {
+        let doc = IniDocument::parse(SAMPLE);
+        let key = &doc.sections[doc.section_index("keyswap").unwrap()];
+        assert_eq!(key.kind, SectionKind::Key);
+        assert_eq!(key.leading_comments, vec!["Toggle outfit".to_string()]);
+        assert!(doc.sections[doc.section_index("Constants").unwrap()].leading_comments.is_empty());
+    }
+
+    #[test]
+    fn condition_blocks_are_classified() {
+        let doc = IniDocument::parse(SAMPLE);
+        let keywords: Vec<(ConditionKeyword, &str)> = doc.lines.iter().filter_map(|l| match &l.kind {
+            IniLineKind::Condition { keyword, expression } => Some((*keyword, expression.as_str())),
+            _ => None,
+        }).collect();
+        assert_eq!(keywords, vec![
+            (ConditionKeyword::If, "$swapvar == 0"),
+            (ConditionKeyword::ElseIf, "$swapvar == 1"),
+            (ConditionKeyword::Else, ""),
+            (ConditionKeyword::EndIf, ""),
+        ]);
+        // Lines inside the block still belong to the section and parse as assignments
+        let section_index = doc.section_index("TextureOverrideBody").unwrap();
+        let keys: Vec<&str> = doc.assignments(section_index).map(|(_, _, key, _)| key).collect();
+        assert_eq!(keys, vec!["hash", "ib", "ib", "run"]);
+    }
+
+    #[test]
+    fn constants_and_key_sections_are_parsed() {
+        let doc = IniDocument::parse(SAMPLE);
+        let constants = doc.constants();
+        assert_eq!(constants.len(), 2);
+        assert!(constants[0].is_persist);
+        assert_eq!(constants[0].initial_value.as_deref(), Some("0"));
+        assert!(!constants[1].is_persist);
+        assert_eq!(constants[1].initial_value, None);
+
+        let keys = doc.key_sections();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].keys.iter().map(|(_, v)| v.as_str()).collect::<Vec<_>>(), vec!["ctrl VK_F5"]);
+        assert_eq!(keys[0].bind_type.as_deref(), Some("cycle"));
+        assert_eq!(keys[0].condition.as_deref(), Some("$active == 1"));
+        assert_eq!(keys[0].variables[0].values, vec!["0", "1", "2"]);
+    }
+
+    #[test]
+    fn set_assignment_value_only_touches_the_value() {
+        let mut doc = IniDocument::parse(SAMPLE);
+        let (line_index, _) = doc.key_sections()[0].keys[0].clone();
+        assert!(doc.set_assignment_value(line_index, "alt VK_F6"));
+        let expected = SAMPLE.replace("key = ctrl VK_F5\r\n", "key = alt VK_F6\r\n");
+        assert_eq!(doc.to_string(), expected);
+        assert_eq!(IniDocument::parse(&expected).key_sections()[0].keys[0].1, "alt VK_F6");
+
+        // Spacing and indentation around `=` are kept
+        let mut doc = IniDocument::parse("[KeyA]\n\tkey=A\n");
+        assert!(doc.set_assignment_value(1, "B"));
+        assert_eq!(doc.to_string(), "[KeyA]\n\tkey=B\n");
+
+        // Non-assignments are left alone
+        let mut doc = IniDocument::parse(SAMPLE);
+        let comment_line = doc.lines.iter().position(|l| matches!(l.kind, IniLineKind::Comment { .. })).unwrap();
+        assert!(!doc.set_assignment_value(comment_line, "x"));
+        assert_eq!(doc.to_string(), SAMPLE);
+    }
+
+    #[test]
+    fn splice_lines_uses_the_document_line_ending() {
+        let mut doc = IniDocument::parse("[Constants]\r\nglobal persist $a = 0\r\n");
+        doc.splice_lines(2..2, &["global persist $b = 1".to_string()]);
+        assert_eq!(doc.to_string(), "[Constants]\r\nglobal persist $a = 0\r\nglobal persist $b = 1\r\n");
+        doc.splice_lines(1..2, &[]);
+        assert_eq!(doc.to_string(), "[Constants]\r\nglobal persist $b = 1\r\n");
+    }
+
+    #[test]
+    fn key_expressions_compare_normalized() {
+        let a = KeyExpression::parse("VK_LCONTROL vk_f1").unwrap();
+        let b = KeyExpression::parse("ctrl F1").unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.to_string(), "CTRL+F1");
+        assert!(a.overlaps(&b));
+        let plain = KeyExpression::parse("no_modifiers F1").unwrap();
+        assert!(!plain.overlaps(&a));
+        assert!(KeyExpression::parse("").is_none());
+    }
+}
@@ -61,19 +61,9 @@ function ModCard({
 
   // --- Effect to derive clean path ---
   useEffect(() => {
-    const isCurrentlyDisabledPrefixed =
-      asset.folder_name?.startsWith("DISABLED_");
-    let cleanPath = asset.folder_name || "";
-    if (isCurrentlyDisabledPrefixed) {
-      const parts = asset.folder_name.split("/");
-      const filename = parts.pop() || "";
-      cleanPath =
-        parts.length > 0
-          ? `${parts.join("/")}/${filename.substring(9)}`
-          : filename.substring(9);
-    }
-    setCleanRelativePath(cleanPath);
-  }, [asset.folder_name]);
+    // The backend strips whichever disabled marker the game uses
+    setCleanRelativePath(asset.clean_folder_name || asset.folder_name || "");
+  }, [asset.clean_folder_name, asset.folder_name]);
   // -----------------------------------
 
   // Cleanup function
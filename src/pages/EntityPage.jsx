@@ -666,33 +666,9 @@ function EntityPage() {
         });
 
         // Update the asset in our map immediately after successful toggle
-        const isCurrentlyDisabledPrefixed =
-          currentAsset.folder_name.startsWith("DISABLED_");
-        let cleanRelativePath = currentAsset.folder_name;
-        if (isCurrentlyDisabledPrefixed) {
-          const parts = currentAsset.folder_name.split("/");
-          const filename = parts.pop() || "";
-          cleanRelativePath =
-            parts.length > 0
-              ? `${parts.join("/")}/${filename.substring(9)}`
-              : filename.substring(9);
-        }
-        let updatedFolderName;
-        if (newIsEnabledState) {
-          updatedFolderName = cleanRelativePath;
-        } else {
-          const parts = cleanRelativePath.split("/");
-          const filename = parts.pop() || "";
-          const disabledFilename = `DISABLED_${filename}`;
-          updatedFolderName =
-            parts.length > 0
-              ? `${parts.join("/")}/${disabledFilename}`
-              : disabledFilename;
-        }
         updatedAssetsMap.set(assetId, {
           ...currentAsset,
           is_enabled: newIsEnabledState,
-          folder_name: updatedFolderName,
         });
 
         successCount++;